@@ -9,6 +9,7 @@
 use crate::im_ast;
 use crate::names::*;
 use crate::types;
+use std::collections::HashMap;
 
 // Assumed types
 pub static BOX_NAME: [&str; 3] = ["alloc", "boxed", "Box"];
@@ -46,6 +47,23 @@ pub static INDEX_MUT_NAME: [&str; 5] = ["core", "ops", "index", "IndexMut", "ind
 // We ignore this trait, which is implicitly given to all the type parameters
 pub static MARKER_SIZED_NAME: [&str; 3] = ["core", "marker", "Sized"];
 
+/// A user-provided mapping from the names of external functions to the names
+/// of assumed functions (ex.: "mylib::utils::replace" -> "core::mem::replace").
+/// The functions in the domain of this map are then treated exactly like the
+/// assumed functions they are mapped to (see [crate::config]).
+pub type AssumedFunsMap = HashMap<String, String>;
+
+/// Apply the user mapping to a function name.
+/// We use the resulting name to lookup the assumed functions.
+pub fn map_fun_name(map: &AssumedFunsMap, name: FunName) -> FunName {
+    match map.get(&name.to_string()) {
+        Option::Some(assumed_name) => {
+            Name::from(assumed_name.split("::").map(|s| s.to_string()).collect())
+        }
+        Option::None => name,
+    }
+}
+
 /// We redefine identifiers for assumed functions here, instead of reusing the
 /// identifiers from [im_ast], because some of the functions (the panic functions)
 /// will actually not be translated to functions: there are thus missing identifiers.
@@ -0,0 +1,69 @@
+//! Compare two .llbc files generated by Charon, and report the declarations
+//! which were added, removed or changed (see [charon::llbc_diff]).
+//!
+//! As with `diff`, the exit code is 0 if there are no differences, 1 if there
+//! are differences and 2 if an error occurred.
+
+use charon::llbc_diff::{diff_crates, DeclChange};
+use charon::llbc_import::load_llbc;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "charon-diff")]
+struct CliOpts {
+    /// The old .llbc file
+    #[structopt(parse(from_os_str))]
+    old: PathBuf,
+    /// The new .llbc file
+    #[structopt(parse(from_os_str))]
+    new: PathBuf,
+    /// The number of unchanged statements to display around the changes
+    #[structopt(long = "context", default_value = "3")]
+    context: usize,
+    /// Only list the declarations which changed
+    #[structopt(long = "summary")]
+    summary: bool,
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = CliOpts::from_args();
+
+    let old = match load_llbc(&args.old) {
+        Ok(krate) => krate,
+        Err(()) => std::process::exit(2),
+    };
+    let new = match load_llbc(&args.new) {
+        Ok(krate) => krate,
+        Err(()) => std::process::exit(2),
+    };
+
+    let diffs = diff_crates(&old, &new, args.context);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for diff in &diffs {
+        match diff.change {
+            DeclChange::Added => added += 1,
+            DeclChange::Removed => removed += 1,
+            DeclChange::Changed { .. } => changed += 1,
+        }
+        if args.summary {
+            let c = match diff.change {
+                DeclChange::Added => "+",
+                DeclChange::Removed => "-",
+                DeclChange::Changed { .. } => "~",
+            };
+            println!("{} {} {}", c, diff.kind, diff.name);
+        } else {
+            println!("{}", diff);
+        }
+    }
+    println!(
+        "{} added, {} removed, {} changed declaration(s)",
+        added, removed, changed
+    );
+
+    if !diffs.is_empty() {
+        std::process::exit(1);
+    }
+}
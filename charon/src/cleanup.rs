@@ -0,0 +1,191 @@
+//! Remove the statements which are irrelevant for most backends:
+//! - the `FakeRead`s, which are only used by the borrow checker (for instance
+//!   to model the two-phase borrows);
+//! - the drops of values whose type has no drop glue (integers, shared and
+//!   mutable borrows, structures whose fields have no drop glue, etc.): those
+//!   drops don't do anything (this includes the drops of the `?` statements,
+//!   see [crate::llbc_ast::Try]);
+//! - the `Nop`s which appear in sequences (including the ones introduced by
+//!   the points above).
+//!
+//! Note that the `StorageDead`s are already removed when reconstructing the
+//! control-flow, whether this pass is applied or not (see
+//! `translate_statement` in [crate::im_to_llbc]). The drops are tested by the
+//! `test-cleanup` target of the Makefile at the root of the repository.
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES], because
+//! some consumers model the `FakeRead`s.
+//!
+//! We are conservative when computing whether a type has drop glue: the opaque
+//! types (external or not) and the type variables are considered to have drop
+//! glue. The transparent types don't implement `Drop` (we don't support trait
+//! implementations), so their drop glue is the drop glue of their fields.
+
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::im_ast::Var;
+use crate::llbc_ast::{FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
+use crate::types::*;
+use crate::values::*;
+use std::collections::HashSet;
+use std::iter::FromIterator;
+
+struct Ctx<'a> {
+    type_defs: &'a TypeDecls,
+    locals: &'a VarId::Vector<Var>,
+}
+
+/// Return true if dropping a value of the given type may do something.
+/// [visiting] contains the type declarations we are currently exploring: we
+/// use it to not loop on recursive types.
+fn has_drop_glue(type_defs: &TypeDecls, visiting: &mut HashSet<TypeDeclId::Id>, ty: &ETy) -> bool {
+    match ty {
+        Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str | Ty::Ref(_, _, _) => false,
+        Ty::TypeVar(_) => true,
+        Ty::Array(ty) | Ty::Slice(ty) => has_drop_glue(type_defs, visiting, ty),
+        Ty::Adt(TypeId::Tuple, _, tys) => {
+            tys.iter().any(|ty| has_drop_glue(type_defs, visiting, ty))
+        }
+        Ty::Adt(TypeId::Assumed(AssumedTy::Box), _, _)
+        | Ty::Adt(TypeId::Assumed(AssumedTy::Vec), _, _) => true,
+        Ty::Adt(TypeId::Assumed(AssumedTy::Option), _, tys) => {
+            has_drop_glue(type_defs, visiting, tys.get(0).unwrap())
+        }
+        Ty::Adt(TypeId::Adt(id), _, tys) => {
+            // If we are already exploring this declaration, the other fields
+            // determine whether it has drop glue
+            if visiting.contains(id) {
+                return false;
+            }
+            let def = match type_defs.get_type_def(*id) {
+                None => return true,
+                Some(def) => def,
+            };
+            let variants: Vec<Option<VariantId::Id>> = match &def.kind {
+                TypeDeclKind::Opaque => return true,
+                TypeDeclKind::Struct(_) => vec![None],
+                TypeDeclKind::Enum(variants) => variants.iter_indices().map(Some).collect(),
+            };
+            visiting.insert(*id);
+            let glue = variants.into_iter().any(|variant_id| {
+                def.get_erased_regions_instantiated_field_types(variant_id, tys)
+                    .iter()
+                    .any(|ty| has_drop_glue(type_defs, visiting, ty))
+            });
+            visiting.remove(id);
+            glue
+        }
+    }
+}
+
+/// Compute the type of a place. Return `None` if we can't (in which case we
+/// will be conservative).
+fn place_ty(ctx: &Ctx, p: &Place) -> Option<ETy> {
+    let mut ty = ctx.locals.get(p.var_id).unwrap().ty.clone();
+    for pe in &p.projection {
+        ty = match (pe, ty) {
+            (ProjectionElem::Deref, Ty::Ref(_, ty, _)) => *ty,
+            (ProjectionElem::DerefBox, Ty::Adt(TypeId::Assumed(AssumedTy::Box), _, tys)) => {
+                tys.get(0)?.clone()
+            }
+            (
+                ProjectionElem::Field(FieldProjKind::Adt(id, variant_id), field_id),
+                Ty::Adt(TypeId::Adt(_), _, tys),
+            ) => {
+                let def = ctx.type_defs.get_type_def(*id)?;
+                if def.kind.is_opaque() {
+                    return None;
+                }
+                def.get_erased_regions_instantiated_field_type(*variant_id, &tys, *field_id)
+            }
+            (
+                ProjectionElem::Field(FieldProjKind::Option(_), _),
+                Ty::Adt(TypeId::Assumed(AssumedTy::Option), _, tys),
+            ) => tys.get(0)?.clone(),
+            (
+                ProjectionElem::Field(FieldProjKind::Tuple(_), field_id),
+                Ty::Adt(TypeId::Tuple, _, tys),
+            ) => tys.get(field_id.to_usize())?.clone(),
+            _ => return None,
+        };
+    }
+    Some(ty)
+}
+
+fn is_trivial_drop(ctx: &Ctx, p: &Place) -> bool {
+    match place_ty(ctx, p) {
+        None => false,
+        Some(ty) => !has_drop_glue(ctx.type_defs, &mut HashSet::new(), &ty),
+    }
+}
+
+fn transform_st(ctx: &Ctx, st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::FakeRead(_) => RawStatement::Nop,
+        RawStatement::Drop(p) => {
+            if is_trivial_drop(ctx, &p) {
+                RawStatement::Nop
+            } else {
+                RawStatement::Drop(p)
+            }
+        }
+        RawStatement::Try(mut t) => {
+            t.drops.retain(|p| !is_trivial_drop(ctx, p));
+            RawStatement::Try(t)
+        }
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                    Box::new(transform_st(ctx, *st1)),
+                    Box::new(transform_st(ctx, *st2)),
+                ),
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets =
+                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(ctx, e))));
+                    let otherwise = transform_st(ctx, *otherwise);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(ctx, *loop_.body)),
+            ..loop_
+        }),
+        RawStatement::Sequence(st1, st2) => {
+            // Collapse the `Nop`s: if all the statements in the sequence are
+            // `Nop`s, we only keep one of them
+            let st1 = transform_st(ctx, *st1);
+            let st2 = transform_st(ctx, *st2);
+            if st1.content.is_nop() {
+                return st2;
+            }
+            if st2.content.is_nop() {
+                return st1;
+            }
+            RawStatement::Sequence(Box::new(st1), Box::new(st2))
+        }
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+fn transform_def(type_defs: &TypeDecls, mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    def.body = match def.body {
+        Option::Some(mut body) => {
+            let ctx = Ctx {
+                type_defs,
+                locals: &body.locals,
+            };
+            body.body = transform_st(&ctx, body.body);
+            Option::Some(body)
+        }
+        Option::None => Option::None,
+    };
+    def
+}
+
+pub fn transform(defs: FunDecls, type_defs: &TypeDecls) -> FunDecls {
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(type_defs, def)))
+}
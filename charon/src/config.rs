@@ -0,0 +1,175 @@
+//! Read the extraction options from a configuration file, so that they can
+//! be versioned with the code of the crate to extract.
+//!
+//! The configuration is looked up in the directory of the crate (the one
+//! containing `Cargo.toml`), in the following order:
+//! - a `Charon.toml` file
+//! - a `[package.metadata.charon]` table in `Cargo.toml`
+//!
+//! For instance:
+//! ```toml
+//! # The items (of the extracted crate) we consider as opaque or transparent
+//! opaque = ["hashmap_utils", "mycrate::net::*::parse"]
+//! transparent = ["mycrate::hashmap_utils::hash_key"]
+//! # The MIR we translate: only "built" is supported for now
+//! mir-level = "built"
+//! # The micro-passes to apply, in this order
+//! passes = ["simplify_ops", "reconstruct_asserts"]
+//! # The micro-passes not to apply
+//! skip-passes = ["remove_unused_locals"]
+//! # Keep the arithmetic checks (overflows, divisions by zero...) explicit
+//! keep-arith-checks = false
+//! # The maximal size of the functions the `inline` micro-pass inlines
+//! inline-threshold = 5
+//! no-code-duplication = true
+//! nll = false
+//! # The output directory, relative to the crate directory
+//! dest = "llbc"
+//! # The format of the generated file: "json", "cbor" or "bincode"
+//! format = "json"
+//! # What to generate: "llbc" and/or "pretty" (a human-readable .llbc.txt file)
+//! emit = ["llbc", "pretty"]
+//! # Generate one file per module
+//! split-modules = false
+//! # Derive the ids of the declarations from their names
+//! stable-ids = false
+//! # Monomorphize the crate, starting from the given functions
+//! monomorphize = false
+//! monomorphize-roots = ["mycrate::main"]
+//!
+//! # External functions we treat as assumed functions
+//! [assumed]
+//! "mylib::utils::replace" = "core::mem::replace"
+//! ```
+//!
+//! Note that the command-line options override the values given in the
+//! configuration file.
+
+use crate::assumed::AssumedFunsMap;
+use crate::common::*;
+use crate::get_mir::MirLevel;
+use crate::llbc_export::{EmitKind, OutputFormat};
+use crate::manifest;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The name of the configuration file
+pub static CONFIG_FILE_NAME: &str = "Charon.toml";
+
+/// The extraction options read from the configuration file.
+/// All the fields are optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The patterns selecting the opaque items
+    pub opaque: Vec<String>,
+    /// The patterns selecting the transparent items
+    pub transparent: Vec<String>,
+    /// The level at which to retrieve the MIR
+    pub mir_level: Option<MirLevel>,
+    /// The micro-passes to apply
+    pub passes: Option<Vec<String>>,
+    /// The micro-passes to skip
+    pub skip_passes: Vec<String>,
+    /// See the `--keep-arith-checks` option
+    pub keep_arith_checks: Option<bool>,
+    /// See the `--inline-threshold` option
+    pub inline_threshold: Option<usize>,
+    /// The mapping from external functions to assumed functions
+    pub assumed: AssumedFunsMap,
+    /// The output directory
+    pub dest: Option<PathBuf>,
+    /// The format of the generated file
+    pub format: Option<OutputFormat>,
+    /// What to generate
+    pub emit: Option<Vec<EmitKind>>,
+    /// See the `--split-modules` option
+    pub split_modules: Option<bool>,
+    /// See the `--stable-ids` option
+    pub stable_ids: Option<bool>,
+    /// See the `--no-code-duplication` option
+    pub no_code_duplication: Option<bool>,
+    /// See the `--nll` option
+    pub nll: Option<bool>,
+    /// See the `--monomorphize` option
+    pub monomorphize: Option<bool>,
+    /// See the `--monomorphize-root` option
+    pub monomorphize_roots: Vec<String>,
+}
+
+/// Read a file to a TOML value
+fn read_toml_file(path: &Path) -> Result<toml::Value> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => {
+            error!("Could not read: {:?}", path);
+            return Err(());
+        }
+    };
+    match toml::from_str(&content) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            error!("Could not parse {:?}:\n{}", path, err);
+            Err(())
+        }
+    }
+}
+
+/// Convert a TOML value to a configuration
+fn value_to_config(path: &Path, value: toml::Value) -> Result<Config> {
+    match value.try_into() {
+        Ok(config) => Ok(config),
+        Err(err) => {
+            error!("Invalid Charon configuration in {:?}:\n{}", path, err);
+            Err(())
+        }
+    }
+}
+
+/// Read the configuration for the crate whose entry point is [source_file].
+/// If we don't find any configuration, we return the default one.
+pub fn read_crate_config(source_file: &PathBuf) -> Result<Config> {
+    let crate_path = manifest::compute_crate_path(source_file)?;
+
+    // Look for a `Charon.toml` file
+    let config_path = crate_path.join(CONFIG_FILE_NAME);
+    let mut config = if config_path.exists() {
+        trace!("Reading configuration: {:?}", config_path);
+        let value = read_toml_file(&config_path)?;
+        value_to_config(&config_path, value)?
+    } else {
+        // Look for a `[package.metadata.charon]` table in the manifest
+        let manifest_path = crate_path.join("Cargo.toml");
+        let value = read_toml_file(&manifest_path)?;
+        let value = value
+            .get("package")
+            .and_then(|v| v.get("metadata"))
+            .and_then(|v| v.get("charon"));
+        match value {
+            Option::Some(value) => {
+                trace!("Reading configuration from: {:?}", manifest_path);
+                value_to_config(&manifest_path, value.clone())?
+            }
+            Option::None => Config::default(),
+        }
+    };
+
+    // The translation has only been tested on the built MIR: we reject the
+    // other levels for now
+    match config.mir_level {
+        Option::None | Option::Some(MirLevel::Built) => (),
+        Option::Some(level) => {
+            error!(
+                "Unsupported MIR level in the configuration: {:?} (only \"built\" is supported)",
+                level
+            );
+            return Err(());
+        }
+    }
+
+    // The output directory is relative to the crate directory
+    config.dest = config.dest.map(|dest| crate_path.join(dest));
+
+    trace!("Configuration: {:?}", config);
+    Ok(config)
+}
@@ -0,0 +1,586 @@
+//! A dataflow framework over the structured statements, together with the
+//! liveness, maybe-initialized and maybe-moved analyses.
+//!
+//! An analysis ([Analysis]) gives a domain, with a join, and a transfer
+//! function for the statements: the framework ([analyze]) handles the control
+//! flow (sequences, switches, loops, `break`, `continue`, `return` and
+//! `panic`). The analyses can be forward or backward. For the loops, we
+//! analyze the body until the state at the loop entry doesn't change anymore:
+//! the domains should be finite.
+//!
+//! The [transform] function implements a pass which annotates every statement
+//! with the results of the analyses (see [crate::llbc_ast::DataflowInfo]), so
+//! that the consumers can insert the drops and end the borrows precisely. This
+//! pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. The passes
+//! which transform the statements don't update the annotations: it must be
+//! applied after them, and after [crate::ssa] (see
+//! [crate::passes::check_pass_order]).
+
+use crate::expressions::*;
+use crate::llbc_ast::{DataflowInfo, FunBody, FunDecl, FunDecls, RawStatement, Statement};
+use std::collections::BTreeSet;
+use std::iter::FromIterator;
+
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A dataflow analysis
+pub trait Analysis {
+    type Domain: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    /// The state at the unreachable points: it should be the neutral element
+    /// of [Analysis::join]
+    fn bottom(&self) -> Self::Domain;
+
+    /// The state when entering the function (forward analyses), or at the
+    /// `return`s (backward analyses)
+    fn boundary(&self) -> Self::Domain;
+
+    fn join(&self, d: &mut Self::Domain, other: &Self::Domain);
+
+    /// Update the state with the effect of a statement: the state before the
+    /// statement for the forward analyses, and after the statement for the
+    /// backward analyses. The framework handles the control flow: for the
+    /// switches, the transfer function should only account for the evaluation
+    /// of the operand we switch on; it isn't called on the sequences. Note
+    /// that the `Try` statements may return early.
+    fn transfer(&self, st: &RawStatement, d: &mut Self::Domain);
+}
+
+struct Engine<'a, 'b, A: Analysis> {
+    analysis: &'a A,
+    record: &'b mut dyn FnMut(&mut Statement, &A::Domain, &A::Domain),
+    /// For the enclosing loops (the innermost loop is the last one): the
+    /// states at the `break`s and at the `continue`s (forward analyses), or
+    /// the states after the loop and at the loop entry (backward analyses)
+    loops: Vec<(A::Domain, A::Domain)>,
+}
+
+impl<'a, 'b, A: Analysis> Engine<'a, 'b, A> {
+    fn loop_index(&self, i: usize) -> usize {
+        self.loops.len() - 1 - i
+    }
+
+    /// Compute the state after a statement, given the state before it
+    fn forward(&mut self, st: &mut Statement, before: A::Domain) -> A::Domain {
+        if let RawStatement::Sequence(st1, st2) = &mut st.content {
+            let d = self.forward(st1, before);
+            return self.forward(st2, d);
+        }
+
+        let mut d = before.clone();
+        self.analysis.transfer(&st.content, &mut d);
+        let after = match &mut st.content {
+            RawStatement::Switch(_, targets) => {
+                let mut after = self.analysis.bottom();
+                for st in targets.get_targets_mut() {
+                    let d = self.forward(st, d.clone());
+                    self.analysis.join(&mut after, &d);
+                }
+                after
+            }
+            RawStatement::Loop(loop_) => {
+                // Reaching the end of the body leads to the next iteration
+                let mut entry = d;
+                loop {
+                    let bottom = self.analysis.bottom();
+                    self.loops.push((bottom.clone(), bottom));
+                    let end = self.forward(&mut loop_.body, entry.clone());
+                    let (breaks, continues) = self.loops.pop().unwrap();
+                    let mut nentry = entry.clone();
+                    self.analysis.join(&mut nentry, &continues);
+                    self.analysis.join(&mut nentry, &end);
+                    if nentry == entry {
+                        break breaks;
+                    }
+                    entry = nentry;
+                }
+            }
+            RawStatement::Break(i) => {
+                let i = self.loop_index(*i);
+                self.analysis.join(&mut self.loops[i].0, &d);
+                self.analysis.bottom()
+            }
+            RawStatement::Continue(i) => {
+                let i = self.loop_index(*i);
+                self.analysis.join(&mut self.loops[i].1, &d);
+                self.analysis.bottom()
+            }
+            RawStatement::Return | RawStatement::Panic => self.analysis.bottom(),
+            _ => d,
+        };
+        (self.record)(st, &before, &after);
+        after
+    }
+
+    /// Compute the state before a statement, given the state after it
+    fn backward(&mut self, st: &mut Statement, after: A::Domain) -> A::Domain {
+        if let RawStatement::Sequence(st1, st2) = &mut st.content {
+            let d = self.backward(st2, after);
+            return self.backward(st1, d);
+        }
+
+        let mut before = match &mut st.content {
+            RawStatement::Switch(_, targets) => {
+                let mut before = self.analysis.bottom();
+                for st in targets.get_targets_mut() {
+                    let d = self.backward(st, after.clone());
+                    self.analysis.join(&mut before, &d);
+                }
+                before
+            }
+            RawStatement::Loop(loop_) => {
+                // Reaching the end of the body leads to the next iteration
+                let mut entry = self.analysis.bottom();
+                loop {
+                    self.loops.push((after.clone(), entry.clone()));
+                    let d = self.backward(&mut loop_.body, entry.clone());
+                    self.loops.pop();
+                    let mut nentry = entry.clone();
+                    self.analysis.join(&mut nentry, &d);
+                    if nentry == entry {
+                        break entry;
+                    }
+                    entry = nentry;
+                }
+            }
+            RawStatement::Break(i) => self.loops[self.loop_index(*i)].0.clone(),
+            RawStatement::Continue(i) => self.loops[self.loop_index(*i)].1.clone(),
+            RawStatement::Return => self.analysis.boundary(),
+            RawStatement::Panic => self.analysis.bottom(),
+            _ => after.clone(),
+        };
+        self.analysis.transfer(&st.content, &mut before);
+        (self.record)(st, &before, &after);
+        before
+    }
+}
+
+/// Analyze a function body. [record] is called on every statement (but the
+/// sequences) with the states before and after the statement, in the order of
+/// execution: for the statements inside loops, it may be called several times,
+/// in which case the last call gives the result of the analysis. Return the
+/// state at the end of the body (forward analyses) or when entering the
+/// function (backward analyses).
+pub fn analyze<A: Analysis>(
+    analysis: &A,
+    body: &mut Statement,
+    record: &mut dyn FnMut(&mut Statement, &A::Domain, &A::Domain),
+) -> A::Domain {
+    let mut engine = Engine {
+        analysis,
+        record,
+        loops: Vec::new(),
+    };
+    match analysis.direction() {
+        Direction::Forward => engine.forward(body, analysis.boundary()),
+        Direction::Backward => engine.backward(body, analysis.bottom()),
+    }
+}
+
+/// A set of locals
+pub type Locals = BTreeSet<VarId::Id>;
+
+fn operand_reads(locals: &mut Locals, op: &Operand) {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => {
+            locals.insert(p.var_id);
+        }
+        Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => rvalue_reads(locals, rv),
+    }
+}
+
+fn rvalue_reads(locals: &mut Locals, rv: &Rvalue) {
+    match rv {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand_reads(locals, op),
+        Rvalue::Ref(p, _) | Rvalue::Discriminant(p) => {
+            locals.insert(p.var_id);
+        }
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
+            operand_reads(locals, op1);
+            operand_reads(locals, op2);
+        }
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                operand_reads(locals, op);
+            }
+        }
+    }
+}
+
+fn operand_moves<'a>(places: &mut Vec<&'a Place>, op: &'a Operand) {
+    match op {
+        Operand::Move(p) => places.push(p),
+        Operand::Copy(_) | Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => rvalue_moves(places, rv),
+    }
+}
+
+fn rvalue_moves<'a>(places: &mut Vec<&'a Place>, rv: &'a Rvalue) {
+    match rv {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand_moves(places, op),
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
+            operand_moves(places, op1);
+            operand_moves(places, op2);
+        }
+        Rvalue::Ref(_, _) | Rvalue::Discriminant(_) => (),
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                operand_moves(places, op);
+            }
+        }
+    }
+}
+
+fn is_deref(p: &Place) -> bool {
+    p.projection
+        .iter()
+        .any(|pe| matches!(pe, ProjectionElem::Deref | ProjectionElem::DerefBox))
+}
+
+/// The places moved by a statement, and the place it writes to
+fn moves_and_write(st: &RawStatement) -> (Vec<&Place>, Option<&Place>) {
+    let mut moves = Vec::new();
+    let write = match st {
+        RawStatement::Assign(p, rv) => {
+            rvalue_moves(&mut moves, rv);
+            Option::Some(p)
+        }
+        RawStatement::Call(call) => {
+            for op in &call.args {
+                operand_moves(&mut moves, op);
+            }
+            Option::Some(&call.dest)
+        }
+        RawStatement::Try(t) => {
+            operand_moves(&mut moves, &t.op);
+            Option::Some(&t.dest)
+        }
+        RawStatement::Assert(assert) => {
+            operand_moves(&mut moves, &assert.cond);
+            Option::None
+        }
+        RawStatement::Switch(op, _) => {
+            operand_moves(&mut moves, op);
+            Option::None
+        }
+        _ => Option::None,
+    };
+    (moves, write)
+}
+
+/// The locals which may be read later. Writing to a local (but not to a part
+/// of it) kills it, and writing through a dereference reads the pointer.
+pub struct Liveness;
+
+impl Liveness {
+    fn write(live: &mut Locals, p: &Place) {
+        if p.projection.is_empty() {
+            live.remove(&p.var_id);
+        } else if is_deref(p) {
+            live.insert(p.var_id);
+        }
+    }
+}
+
+impl Analysis for Liveness {
+    type Domain = Locals;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn bottom(&self) -> Locals {
+        BTreeSet::new()
+    }
+
+    fn boundary(&self) -> Locals {
+        // The return value is read when returning
+        let mut live = BTreeSet::new();
+        live.insert(VarId::ZERO);
+        live
+    }
+
+    fn join(&self, d: &mut Locals, other: &Locals) {
+        d.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, st: &RawStatement, live: &mut Locals) {
+        match st {
+            RawStatement::Assign(p, rv) => {
+                Liveness::write(live, p);
+                rvalue_reads(live, rv);
+            }
+            RawStatement::FakeRead(p) | RawStatement::Drop(p) => {
+                live.insert(p.var_id);
+            }
+            RawStatement::SetDiscriminant(p, _) => {
+                if is_deref(p) {
+                    live.insert(p.var_id);
+                }
+            }
+            RawStatement::Assert(assert) => operand_reads(live, &assert.cond),
+            RawStatement::Call(call) => {
+                Liveness::write(live, &call.dest);
+                for op in &call.args {
+                    operand_reads(live, op);
+                }
+            }
+            RawStatement::Try(t) => {
+                // When returning early, the return value is computed from the
+                // operand, and the places in `drops` are dropped
+                Liveness::write(live, &t.dest);
+                operand_reads(live, &t.op);
+                for p in &t.drops {
+                    live.insert(p.var_id);
+                }
+            }
+            RawStatement::Switch(op, _) => operand_reads(live, op),
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop
+            | RawStatement::Sequence(_, _)
+            | RawStatement::Loop(_) => (),
+        }
+    }
+}
+
+/// The locals which may be initialized. Moving a local (but not a part of it)
+/// or dropping it deinitializes it.
+pub struct MaybeInit {
+    /// The parameters are initialized when entering the function
+    pub arg_count: usize,
+}
+
+impl Analysis for MaybeInit {
+    type Domain = Locals;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn bottom(&self) -> Locals {
+        BTreeSet::new()
+    }
+
+    fn boundary(&self) -> Locals {
+        (1..=self.arg_count).map(VarId::Id::new).collect()
+    }
+
+    fn join(&self, d: &mut Locals, other: &Locals) {
+        d.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, st: &RawStatement, init: &mut Locals) {
+        let (moves, write) = moves_and_write(st);
+        for p in moves {
+            if p.projection.is_empty() {
+                init.remove(&p.var_id);
+            }
+        }
+        match write {
+            Option::Some(p) if !is_deref(p) => {
+                init.insert(p.var_id);
+            }
+            _ => (),
+        }
+        if let RawStatement::Drop(p) = st {
+            if p.projection.is_empty() {
+                init.remove(&p.var_id);
+            }
+        }
+    }
+}
+
+/// The locals which may have been moved, or partially moved. Writing to a
+/// local (but not to a part of it) resets it.
+pub struct MaybeMoved;
+
+impl Analysis for MaybeMoved {
+    type Domain = Locals;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn bottom(&self) -> Locals {
+        BTreeSet::new()
+    }
+
+    fn boundary(&self) -> Locals {
+        BTreeSet::new()
+    }
+
+    fn join(&self, d: &mut Locals, other: &Locals) {
+        d.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, st: &RawStatement, moved: &mut Locals) {
+        let (moves, write) = moves_and_write(st);
+        for p in moves {
+            moved.insert(p.var_id);
+        }
+        match write {
+            Option::Some(p) if p.projection.is_empty() => {
+                moved.remove(&p.var_id);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn annotation(st: &mut Statement) -> &mut DataflowInfo {
+    st.dataflow.get_or_insert_with(|| DataflowInfo {
+        live: Vec::new(),
+        maybe_init: Vec::new(),
+        maybe_moved: Vec::new(),
+    })
+}
+
+fn transform_body(body: &mut FunBody) {
+    let _ = analyze(&Liveness, &mut body.body, &mut |st, _, live| {
+        annotation(st).live = Vec::from_iter(live.iter().copied());
+    });
+    let maybe_init = MaybeInit {
+        arg_count: body.arg_count,
+    };
+    let _ = analyze(&maybe_init, &mut body.body, &mut |st, _, init| {
+        annotation(st).maybe_init = Vec::from_iter(init.iter().copied());
+    });
+    let _ = analyze(&MaybeMoved, &mut body.body, &mut |st, _, moved| {
+        annotation(st).maybe_moved = Vec::from_iter(moved.iter().copied());
+    });
+}
+
+fn transform_def(mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    if let Option::Some(body) = &mut def.body {
+        transform_body(body);
+    }
+    def
+}
+
+pub fn transform(defs: FunDecls) -> FunDecls {
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dataflow::*;
+    use crate::expressions::*;
+    use crate::llbc_ast::{DataflowInfo, RawStatement, Statement, SwitchTargets};
+    use crate::llbc_ast_utils::test_utils::*;
+    use crate::values::*;
+
+    /// The locals of the tests: `b` is the input
+    static LOCALS: [Option<&str>; 4] = [None, Some("b"), Some("x"), Some("y")];
+
+    fn info(st: &Statement) -> &DataflowInfo {
+        st.dataflow.as_ref().unwrap()
+    }
+
+    fn locals(ids: &[usize]) -> Vec<VarId::Id> {
+        ids.iter().map(|i| VarId::Id::new(*i)).collect()
+    }
+
+    #[test]
+    fn test_loop() {
+        // x := 0;
+        // loop {
+        //   if move b { x := copy x + 1; continue 0 }
+        //   else { y := move x; break 0 }
+        // };
+        // @return := move y;
+        // return
+        let mut b = body(
+            1,
+            &LOCALS,
+            seq(vec![
+                assign(2, Rvalue::Use(constant(0))),
+                loop_(if_(
+                    1,
+                    seq(vec![
+                        assign(2, add(copy(2), constant(1))),
+                        st(RawStatement::Continue(0)),
+                    ]),
+                    seq(vec![
+                        assign(3, Rvalue::Use(move_(2))),
+                        st(RawStatement::Break(0)),
+                    ]),
+                )),
+                assign(0, Rvalue::Use(move_(3))),
+                st(RawStatement::Return),
+            ]),
+        );
+        transform_body(&mut b);
+        let sts = flatten_seq(&b.body);
+        let (incr, move_x) = match &sts[1].content {
+            RawStatement::Loop(loop_) => match &loop_.body.content {
+                RawStatement::Switch(_, SwitchTargets::If(st1, st2)) => {
+                    (flatten_seq(st1)[0], flatten_seq(st2)[0])
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        };
+
+        // b is live after the increment because of the `continue`
+        assert!(info(sts[0]).live == locals(&[1, 2]));
+        assert!(info(incr).live == locals(&[1, 2]));
+        assert!(info(move_x).live == locals(&[3]));
+
+        // b is moved by the first iteration
+        assert!(info(incr).maybe_init == locals(&[2]));
+        assert!(info(sts[1]).maybe_init == locals(&[3]));
+        assert!(info(sts[2]).maybe_init == locals(&[0]));
+
+        assert!(info(incr).maybe_moved == locals(&[1]));
+        assert!(info(sts[1]).maybe_moved == locals(&[1, 2]));
+        assert!(info(sts[2]).maybe_moved == locals(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_switch() {
+        // if move b { x := 0 } else { y := 1 };
+        // @return := copy x;
+        // return
+        let mut b = body(
+            1,
+            &LOCALS,
+            seq(vec![
+                if_(
+                    1,
+                    assign(2, Rvalue::Use(constant(0))),
+                    assign(3, Rvalue::Use(constant(1))),
+                ),
+                assign(0, Rvalue::Use(copy(2))),
+                st(RawStatement::Return),
+            ]),
+        );
+        transform_body(&mut b);
+        let sts = flatten_seq(&b.body);
+        let (st1, st2) = match &sts[0].content {
+            RawStatement::Switch(_, SwitchTargets::If(st1, st2)) => (st1, st2),
+            _ => panic!(),
+        };
+
+        assert!(info(st1).live == locals(&[2]));
+        assert!(info(st2).live == locals(&[2]));
+        assert!(info(sts[0]).live == locals(&[2]));
+
+        // The join of the branches
+        assert!(info(st1).maybe_init == locals(&[2]));
+        assert!(info(st2).maybe_init == locals(&[3]));
+        assert!(info(sts[0]).maybe_init == locals(&[2, 3]));
+        assert!(info(sts[1]).maybe_init == locals(&[0, 2, 3]));
+
+        assert!(info(sts[0]).maybe_moved == locals(&[1]));
+    }
+}
@@ -5,18 +5,19 @@ use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
 fn statement_diverges(divergent: &HashMap<ast::FunDeclId::Id, bool>, st: &llbc::Statement) -> bool {
-    match st {
-        llbc::Statement::Assign(_, _)
-        | llbc::Statement::FakeRead(_)
-        | llbc::Statement::SetDiscriminant(_, _)
-        | llbc::Statement::Drop(_)
-        | llbc::Statement::Assert(_)
-        | llbc::Statement::Panic
-        | llbc::Statement::Return
-        | llbc::Statement::Break(_)
-        | llbc::Statement::Continue(_)
-        | llbc::Statement::Nop => false,
-        llbc::Statement::Call(call) => match &call.func {
+    match &st.content {
+        llbc::RawStatement::Assign(_, _)
+        | llbc::RawStatement::FakeRead(_)
+        | llbc::RawStatement::SetDiscriminant(_, _)
+        | llbc::RawStatement::Drop(_)
+        | llbc::RawStatement::Assert(_)
+        | llbc::RawStatement::Try(_)
+        | llbc::RawStatement::Panic
+        | llbc::RawStatement::Return
+        | llbc::RawStatement::Break(_)
+        | llbc::RawStatement::Continue(_)
+        | llbc::RawStatement::Nop => false,
+        llbc::RawStatement::Call(call) => match &call.func {
             ast::FunId::Regular(id) => *divergent.get(id).unwrap(),
             ast::FunId::Assumed(id) => match id {
                 ast::AssumedFunId::Replace
@@ -32,14 +33,14 @@ fn statement_diverges(divergent: &HashMap<ast::FunDeclId::Id, bool>, st: &llbc::
                 | ast::AssumedFunId::VecIndexMut => false,
             },
         },
-        llbc::Statement::Sequence(st1, st2) => {
+        llbc::RawStatement::Sequence(st1, st2) => {
             statement_diverges(divergent, &st1) || statement_diverges(divergent, &st2)
         }
-        llbc::Statement::Switch(_, tgts) => {
+        llbc::RawStatement::Switch(_, tgts) => {
             let tgts = tgts.get_targets();
             tgts.iter().any(|st| statement_diverges(divergent, st))
         }
-        llbc::Statement::Loop(_) => true,
+        llbc::RawStatement::Loop(_) => true,
     }
 }
 
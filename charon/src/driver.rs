@@ -0,0 +1,551 @@
+//! The entry point of the translation. We call the Rust compiler with the
+//! proper arguments, and translate the crate to LLBC from a compiler callback.
+
+use crate::assumed::AssumedFunsMap;
+use crate::common::*;
+use crate::divergent;
+use crate::get_mir::MirLevel;
+use crate::im_to_llbc;
+use crate::inline;
+use crate::llbc_ast::FunDecls;
+use crate::llbc_export;
+use crate::manifest;
+use crate::meta;
+use crate::monomorphize;
+use crate::opacity;
+use crate::passes;
+use crate::passes::DEFAULT_PASSES;
+use crate::register;
+use crate::reorder_decls;
+use crate::rust_to_local_ids;
+use crate::rust_to_local_ids::OrderedDecls;
+use crate::translate_functions_to_im;
+use crate::translate_types;
+use crate::types::TypeDecls;
+use rustc_driver::{Callbacks, Compilation, RunCompiler};
+use rustc_interface::{interface::Compiler, Queries};
+use rustc_middle::ty::TyCtxt;
+use rustc_session::Session;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The kind of target we extract (the library, a binary, a test, etc.).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    Lib,
+    Bin,
+    Test,
+    Example,
+    Bench,
+}
+
+impl TargetKind {
+    /// The arguments to give to rustc to compile this kind of target
+    fn rustc_args(&self) -> Vec<String> {
+        match self {
+            TargetKind::Lib => vec!["--crate-type=lib".to_string()],
+            TargetKind::Bin | TargetKind::Example => vec!["--crate-type=bin".to_string()],
+            // The tests and the benchmarks are compiled with the test harness
+            TargetKind::Test | TargetKind::Bench => vec!["--test".to_string()],
+        }
+    }
+
+    /// Return true if the target can use the dev-dependencies of its package
+    pub fn uses_dev_dependencies(&self) -> bool {
+        match self {
+            TargetKind::Lib | TargetKind::Bin => false,
+            TargetKind::Test | TargetKind::Example | TargetKind::Bench => true,
+        }
+    }
+
+    /// The cargo command which builds the dependencies of this kind of target
+    pub fn build_command(&self) -> &'static str {
+        if self.uses_dev_dependencies() {
+            "cargo build --all-targets"
+        } else {
+            "cargo build"
+        }
+    }
+}
+
+impl FromStr for TargetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<TargetKind, String> {
+        match s {
+            "lib" => Ok(TargetKind::Lib),
+            "bin" => Ok(TargetKind::Bin),
+            "test" => Ok(TargetKind::Test),
+            "example" => Ok(TargetKind::Example),
+            "bench" => Ok(TargetKind::Bench),
+            _ => Err(format!(
+                "Unknown target kind: {:?} (expected one of: lib, bin, test, example, bench)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for TargetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            TargetKind::Lib => write!(f, "lib"),
+            TargetKind::Bin => write!(f, "bin"),
+            TargetKind::Test => write!(f, "test"),
+            TargetKind::Example => write!(f, "example"),
+            TargetKind::Bench => write!(f, "bench"),
+        }
+    }
+}
+
+/// The options of the translation.
+///
+/// We serialize the options in the header of the generated files (see
+/// [crate::llbc_export]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransOptions {
+    /// The input file (the entry point of the crate to extract)
+    pub input_file: PathBuf,
+    /// The kind of target the input file is the entry point of
+    pub target_kind: TargetKind,
+    /// If true, use Polonius' non-lexical lifetimes (NLL) analysis.
+    pub use_polonius: bool,
+    /// If true, check that no code duplication happens during the control-flow
+    /// reconstruction (see [im_to_llbc]).
+    pub no_code_duplication: bool,
+    /// The patterns selecting the items of the extracted crate that we consider
+    /// as opaque (see [crate::opacity])
+    pub opaque: Vec<String>,
+    /// The patterns selecting the items of the extracted crate that we consider
+    /// as transparent, even if they are selected by an opaque pattern
+    pub transparent: Vec<String>,
+    /// If true, build the external dependencies with the proper toolchain in
+    /// a private target directory (see [crate::toolchain]).
+    pub rebuild_deps: bool,
+    /// The level at which to retrieve the MIR bodies
+    pub mir_level: MirLevel,
+    /// The micro-passes to apply on the LLBC, in this order (see
+    /// [crate::passes])
+    pub passes: Vec<String>,
+    /// The micro-passes to skip, among the passes listed in `passes`
+    pub skip_passes: Vec<String>,
+    /// If true, keep the arithmetic checks introduced by rustc (overflows,
+    /// divisions by zero...) explicit, rather than giving a precondition to
+    /// the arithmetic operations (see [crate::simplify_ops]).
+    pub keep_arith_checks: bool,
+    /// The maximal number of statements of the functions we inline, when they
+    /// don't have a `#[charon::inline]` attribute (see [crate::inline])
+    pub inline_threshold: usize,
+    /// The user mapping from external functions to assumed functions
+    pub assumed_funs: AssumedFunsMap,
+    /// If true, use stable ids in the generated files (see [crate::stable_ids])
+    pub stable_ids: bool,
+    /// If true, monomorphize the crate (see [crate::monomorphize])
+    pub monomorphize: bool,
+    /// The patterns selecting the roots of the monomorphization (by default:
+    /// all the non-generic declarations)
+    pub monomorphize_roots: Vec<String>,
+    /// If not `None`, dump the CFGs of the functions (for debugging purposes).
+    /// We don't record this option in the header of the generated files.
+    #[serde(skip)]
+    pub dump_cfg: Option<im_to_llbc::CfgDumpOptions>,
+    /// If not `None`, dump the state of the translation after some micro-passes
+    /// (for debugging purposes). We don't record this option in the header of
+    /// the generated files.
+    #[serde(skip)]
+    pub dump_after: Option<passes::PassDumpOptions>,
+}
+
+impl TransOptions {
+    /// Create options with the default values, for a given input file
+    pub fn new(input_file: PathBuf) -> TransOptions {
+        TransOptions {
+            input_file,
+            target_kind: TargetKind::Lib,
+            use_polonius: false,
+            no_code_duplication: false,
+            opaque: Vec::new(),
+            transparent: Vec::new(),
+            rebuild_deps: false,
+            mir_level: MirLevel::Built,
+            passes: DEFAULT_PASSES.iter().map(|s| s.to_string()).collect(),
+            skip_passes: Vec::new(),
+            keep_arith_checks: false,
+            inline_threshold: inline::DEFAULT_INLINE_THRESHOLD,
+            assumed_funs: AssumedFunsMap::new(),
+            stable_ids: false,
+            monomorphize: false,
+            monomorphize_roots: Vec::new(),
+            dump_cfg: None,
+            dump_after: None,
+        }
+    }
+
+    /// Return true if we apply a (valid) micro-pass
+    pub fn applies_pass(&self, name: &str) -> bool {
+        let pass = passes::get_pass(name).unwrap();
+        self.passes.iter().any(|p| p == name)
+            && !self.skip_passes.iter().any(|p| p == name)
+            && !(self.keep_arith_checks && pass.removes_arith_checks())
+    }
+}
+
+/// A translated crate.
+pub struct TranslatedCrate {
+    /// The name of the crate
+    pub crate_name: String,
+    /// The declarations, grouped and ordered so that every declaration comes
+    /// after its dependencies
+    pub ordered_decls: OrderedDecls,
+    /// The type declarations
+    pub type_defs: TypeDecls,
+    /// The function declarations
+    pub fun_defs: FunDecls,
+    /// The files referenced by the spans of the declarations and statements
+    pub files: meta::FileId::Vector<String>,
+}
+
+/// The errors which may happen when translating a crate.
+#[derive(Debug)]
+pub enum Errors {
+    /// The options are invalid (unknown pass name, for instance)
+    Options,
+    /// We couldn't retrieve the sysroot of the Rust compiler
+    Sysroot,
+    /// We couldn't read the manifest of the crate, or find its compiled
+    /// dependencies (see [manifest::read_manifest_compute_external_deps])
+    Manifest,
+    /// The Rust compiler failed before we could translate the crate (parsing
+    /// or type checking error, for instance)
+    Compiler,
+    /// The translation itself failed. The details have been reported through
+    /// the compiler session and the logger.
+    Translation,
+}
+
+impl std::fmt::Display for Errors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Errors::Options => write!(f, "Invalid options"),
+            Errors::Sysroot => write!(f, "Could not retrieve the sysroot of the Rust compiler"),
+            Errors::Manifest => write!(
+                f,
+                "Could not read the manifest or the compiled dependencies"
+            ),
+            Errors::Compiler => write!(f, "The Rust compiler failed"),
+            Errors::Translation => write!(f, "The translation to LLBC failed"),
+        }
+    }
+}
+
+struct ToInternal<'a> {
+    options: &'a TransOptions,
+    /// The result of the translation, which we set from the compiler callback
+    translated: Option<Result<TranslatedCrate>>,
+}
+
+impl<'a> Callbacks for ToInternal<'a> {
+    /// We have to be careful here: we can plug ourselves at several places
+    /// (after parsing, after expansion, after analysis). However, the MIR is
+    /// modified in place: this means that if we at some point we compute, say,
+    /// the promoted MIR, it is possible to query the optimized MIR (because
+    /// optimized MIR is further down in the compilation process). However,
+    /// it is not possible to query, say, the built MIR (which results from
+    /// the conversion to HIR to MIR) because it has been lost.
+    /// For this reason, and as we may want to plug ourselves at different
+    /// phases of the compilation process, we query the context as early as
+    /// possible (i.e., after parsing). See [get_mir].
+    fn after_parsing<'tcx>(&mut self, c: &Compiler, queries: &'tcx Queries<'tcx>) -> Compilation {
+        let options = self.options;
+        let translated = queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+            let session = c.session();
+            translate(session, tcx, options)
+        });
+        self.translated = Some(translated);
+        Compilation::Stop
+    }
+}
+
+/// Retrieve the sysroot of the Rust compiler
+fn sysroot() -> Result<String> {
+    let out = match std::process::Command::new("rustc")
+        .arg("--print=sysroot")
+        .current_dir(".")
+        .output()
+    {
+        Ok(out) => out,
+        Err(err) => {
+            error!("Could not call rustc to retrieve the sysroot: {}", err);
+            return Err(());
+        }
+    };
+    match std::str::from_utf8(&out.stdout) {
+        Ok(sysroot) if out.status.success() => Ok(sysroot.trim().to_string()),
+        _ => {
+            error!(
+                "Could not retrieve the sysroot with `rustc --print=sysroot`:\n{}",
+                String::from_utf8_lossy(&out.stderr)
+            );
+            Err(())
+        }
+    }
+}
+
+/// Translate a crate to LLBC.
+///
+/// Charon expects the project to have been built in debug mode before performing
+/// extraction: `cargo build` (or `cargo build --all-targets` for the tests, the
+/// examples and the benchmarks). In particular, it will look for already compiled
+/// external dependencies in the target directory (`/target/debug/deps/`, usually).
+/// See [manifest::read_manifest_compute_external_deps].
+pub fn translate_crate(options: &TransOptions) -> std::result::Result<TranslatedCrate, Errors> {
+    // Check the options
+    if passes::check_pass_names(&options.passes).is_err()
+        || passes::check_pass_names(&options.skip_passes).is_err()
+    {
+        return Err(Errors::Options);
+    }
+    let applied_passes: Vec<String> = options
+        .passes
+        .iter()
+        .filter(|name| options.applies_pass(name))
+        .cloned()
+        .collect();
+    if passes::check_pass_order(&applied_passes).is_err() {
+        return Err(Errors::Options);
+    }
+    if let Some(dump) = &options.dump_after {
+        for pass in &dump.passes {
+            if !options.applies_pass(pass) {
+                error!(
+                    "Can't dump the declarations after {:?}: this micro-pass is not applied",
+                    pass
+                );
+                return Err(Errors::Options);
+            }
+        }
+    }
+
+    // Retrieve the sysroot (the path to the executable of the compiler)
+    let sysroot = sysroot().map_err(|_| Errors::Sysroot)?;
+    let sysroot_arg = format!("--sysroot={}", sysroot).to_owned();
+
+    // Read the manifest, find the target package and compute the list of external
+    // dependencies.
+    let (_manifest, package, mut external_deps) = manifest::read_manifest_compute_external_deps(
+        &options.input_file,
+        options.target_kind,
+        options.rebuild_deps,
+    )
+    .map_err(|_| Errors::Manifest)?;
+
+    // Call the Rust compiler with the proper options.
+    // Note that the first argument is ignored by the compiler: it is supposed
+    // to be the path to the executable.
+    let mut compiler_args = vec![
+        "charon".to_string(),
+        sysroot_arg,
+        options.input_file.as_path().to_str().unwrap().to_string(),
+        format!("--edition={}", package.edition).to_string(),
+    ];
+    compiler_args.append(&mut options.target_kind.rustc_args());
+    // If the input file is the entry point of one of the targets of the
+    // package, use the name of this target (otherwise rustc derives the
+    // crate name from the name of the file, which is `main` for the binaries).
+    match manifest::find_target(&package, &options.input_file) {
+        Option::Some(target) => compiler_args.push(format!(
+            "--crate-name={}",
+            str::replace(&target.name, "-", "_")
+        )),
+        Option::None => (),
+    }
+    if options.use_polonius {
+        compiler_args.push("-Zpolonius".to_string());
+    }
+    compiler_args.append(&mut external_deps);
+
+    trace!("Compiler args: {:?}", compiler_args.join(" "));
+
+    // When calling the compiler we provide a callback, which allows us
+    // to retrieve the result of compiler queries
+    let mut callbacks = ToInternal {
+        options,
+        translated: None,
+    };
+    if RunCompiler::new(&compiler_args, &mut callbacks)
+        .run()
+        .is_err()
+    {
+        return Err(Errors::Compiler);
+    }
+
+    match callbacks.translated {
+        Some(Ok(translated)) => Ok(translated),
+        Some(Err(())) => Err(Errors::Translation),
+        // The compiler stopped before calling our callback
+        None => Err(Errors::Compiler),
+    }
+}
+
+/// Translate a crate to LLBC (Low-Level Borrow Calculus).
+///
+/// This function is a callback function for the Rust compiler.
+fn translate(sess: &Session, tcx: TyCtxt, options: &TransOptions) -> Result<TranslatedCrate> {
+    trace!();
+    // Retrieve the crate name.
+    let crate_name = tcx
+        .crate_name(rustc_span::def_id::LOCAL_CRATE)
+        .to_ident_string();
+    trace!("# Crate: {}", crate_name);
+
+    // Some important notes about crates and how to interact with rustc:
+    // - when calling rustc, we should give it the root of the crate, for
+    //   instance the "main.rs" file. From there, rustc will load all the
+    //   *modules* (i.e., files) in the crate
+    // - whenever there is a `mod MODULE` in a file (for instance, in the
+    //   "main.rs" file), it becomes a Module HIR item
+
+    // # Step 1: check and register all the definitions, to build the graph
+    // of dependencies between them (we need to know in which
+    // order to extract the definitions, and which ones are mutually
+    // recursive). While building this graph, we perform as many checks as
+    // we can to make sure the code is in the proper rust subset. Those very
+    // early steps mostly involve checking whether some features are used or
+    // not (ex.: raw pointers, inline ASM, etc.). More complex checks are
+    // performed later. In general, whenever there is ambiguity on the potential
+    // step in which a step could be performed, we perform it as soon as possible.
+    // Building the graph of dependencies allows us to translate the definitions
+    // in the proper order, and to figure out which definitions are mutually
+    // recursive.
+    // We iterate over the HIR items, and explore their MIR bodies/ADTs/etc.
+    // (when those exist - for instance, type aliases don't have MIR translations
+    // so we just ignore them).
+    let crate_info = register::CrateInfo {
+        crate_name: crate_name.clone(),
+        opacity: opacity::OpacityFilter::new(&crate_name, &options.opaque, &options.transparent)?,
+        mir_level: options.mir_level,
+        assumed_funs: options.assumed_funs.clone(),
+    };
+    let registered_decls = register::register_crate(&crate_info, sess, tcx)?;
+
+    // # Step 2: reorder the graph of dependencies and compute the strictly
+    // connex components to:
+    // - compute the order in which to extract the definitions
+    // - find the recursive definitions
+    // - group the mutually recursive definitions
+    let ordered_decls = reorder_decls::reorder_declarations(&registered_decls)?;
+
+    // # Step 3: generate identifiers for the types and functions, and compute
+    // the mappings from rustc identifiers to our own identifiers
+    let ordered_decls = rust_to_local_ids::rust_to_local_ids(&ordered_decls);
+
+    // # Step 4: translate the types.
+    // We register the files referenced by the spans in the file table as we go.
+    let file_table = meta::FileTable::new();
+    let (types_constraints, type_defs) =
+        translate_types::translate_types(tcx, &ordered_decls, &file_table)?;
+
+    // # Step 5: translate the functions to IM (our Internal representation of MIR).
+    // Note that from now onwards, both type and function definitions have been
+    // translated to our internal ASTs: we don't interact with rustc anymore.
+    let im_defs = translate_functions_to_im::translate_functions(
+        tcx,
+        &ordered_decls,
+        &types_constraints,
+        &type_defs,
+        options.mir_level,
+        &options.assumed_funs,
+        &file_table,
+    )?;
+
+    // # Step 6: go from IM to LLBC (Low-Level Borrow Calculus) by reconstructing
+    // the control flow.
+    let llbc_defs = im_to_llbc::translate_functions(
+        options.no_code_duplication,
+        &options.dump_cfg,
+        &type_defs,
+        &im_defs,
+    );
+
+    //
+    // =================
+    // **Micro-passes**:
+    // =================
+    // At this point, the bulk of the translation is done. From now onwards,
+    // we simply apply some micro-passes to make the code cleaner, before
+    // serializing the result.
+    //
+
+    // # Step 7: apply the micro-passes, in the order selected by the user
+    // (by default: see [DEFAULT_PASSES]), skipping the passes the user asked
+    // to skip, and the passes removing the arithmetic checks if the user
+    // asked to keep them. Note that the pass names have been checked in
+    // [translate_crate].
+    let mut llbc_defs = llbc_defs;
+    for name in &options.passes {
+        if !options.applies_pass(name) {
+            continue;
+        }
+        let pass = passes::get_pass(name).unwrap();
+        llbc_defs = pass.transform(llbc_defs, &type_defs, options);
+
+        for def in &llbc_defs {
+            trace!(
+                "# After {}:\n{}\n",
+                name,
+                def.fmt_with_defs(&type_defs, &llbc_defs)
+            );
+        }
+
+        // Dump the declarations, if the user asked for it
+        if let Some(dump) = &options.dump_after {
+            if dump.passes.contains(name) {
+                llbc_export::export_after_pass(
+                    name,
+                    crate_name.clone(),
+                    &ordered_decls,
+                    &type_defs,
+                    &llbc_defs,
+                    &file_table.files(),
+                    &dump.dest_dir,
+                    options,
+                    dump.format,
+                )?;
+            }
+        }
+    }
+
+    // # Step 8: monomorphize the crate, if the user asked for it. This changes
+    // the ids of the declarations, and the declaration groups.
+    let (ordered_decls, type_defs, llbc_defs) = if options.monomorphize {
+        monomorphize::monomorphize(
+            &crate_name,
+            &options.monomorphize_roots,
+            &ordered_decls,
+            &type_defs,
+            &llbc_defs,
+        )?
+    } else {
+        (ordered_decls, type_defs, llbc_defs)
+    };
+
+    // # Step 9: compute which functions are potentially divergent. A function
+    // is potentially divergent if it is recursive, contains a loop or transitively
+    // calls a potentially divergent function.
+    // Note that in the future, we may complement this basic analysis with a
+    // finer analysis to detect recursive functions which are actually total
+    // by construction.
+    let _divergent = divergent::compute_divergent_functions(&ordered_decls, &llbc_defs);
+
+    trace!("Done");
+
+    Ok(TranslatedCrate {
+        crate_name,
+        ordered_decls,
+        type_defs,
+        fun_defs: llbc_defs,
+        files: file_table.into_files(),
+    })
+}
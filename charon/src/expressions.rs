@@ -5,7 +5,7 @@ use crate::types::*;
 use crate::values::*;
 use im::Vector;
 use macros::{EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Place {
@@ -23,7 +23,7 @@ pub type Projection = Vector<ProjectionElem>;
 /// `((_0 as Right).0: T2) = move _1;`
 /// In MIR, downcasts always happen before field projections: in our internal
 /// language, we thus merge downcasts and field projections.
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, VariantName, Serialize, Deserialize)]
 pub enum ProjectionElem {
     /// Dereference a shared/mutable reference.
     Deref,
@@ -45,7 +45,7 @@ pub enum ProjectionElem {
     Field(FieldProjKind, FieldId::Id),
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum FieldProjKind {
     #[serde(rename = "ProjAdt")]
     Adt(TypeDeclId::Id, Option<VariantId::Id>),
@@ -57,7 +57,7 @@ pub enum FieldProjKind {
     Tuple(usize),
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum BorrowKind {
     Shared,
     Mut,
@@ -65,7 +65,7 @@ pub enum BorrowKind {
 }
 
 /// Unary operation
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
 pub enum UnOp {
     Not,
     /// This can overflow. In practice, rust introduces an assert before
@@ -82,7 +82,7 @@ pub enum UnOp {
 }
 
 /// Binary operations.
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
 pub enum BinOp {
     BitXor,
     BitAnd,
@@ -110,12 +110,18 @@ pub enum BinOp {
     // No Offset binary operation: this is an operation on raw pointers
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize, Deserialize,
+)]
 pub enum Operand {
     Copy(Place),
     Move(Place),
     /// Constant value.
     Constant(ETy, OperandConstantValue),
+    /// A nested expression, together with its type. The nested expressions
+    /// are introduced by the micro-pass [crate::inline_temporaries], which
+    /// inlines the temporaries introduced by the desugaring.
+    Expr(ETy, Box<Rvalue>),
 }
 
 /// Constant value for an operand.
@@ -143,14 +149,22 @@ pub enum OperandConstantValue {
     Adt(Option<VariantId::Id>, Vector<OperandConstantValue>),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Rvalue {
     Use(Operand),
     Ref(Place, BorrowKind),
     /// Unary operation (not, neg)
     UnaryOp(UnOp, Operand),
-    /// Binary operations (note that we merge "checked" and "unchecked" binops)
+    /// Binary operation. The operations which can fail (overflows, division
+    /// by zero...) have a precondition.
     BinaryOp(BinOp, Operand, Operand),
+    /// Checked binary operation (addition, subtraction, multiplication and
+    /// shifts): evaluates to the pair `(result, overflow)`, where `overflow`
+    /// is true if the operation overflowed. Rustc always follows those
+    /// operations with an assertion checking that there is no overflow.
+    /// The micro-pass [crate::simplify_ops] collapses them to [Rvalue::BinaryOp],
+    /// unless the user asked to keep the arithmetic checks.
+    CheckedBinaryOp(BinOp, Operand, Operand),
     /// Discriminant (for enumerations).
     /// Note that discriminant values have type isize
     Discriminant(Place),
@@ -172,13 +186,18 @@ pub enum Rvalue {
     Aggregate(AggregateKind, Vec<Operand>),
 }
 
-#[derive(Debug, Clone)]
+/// Note that we use a custom serializer, which renames the variants: the
+/// deserializer is derived, so we need to rename them here.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
 pub enum AggregateKind {
+    #[serde(rename = "AggregatedTuple")]
     Tuple,
     // TODO: treat Option in a general manner (we should extract the definitions
     // of the external enumerations - because as they are public, their variants are
     // public)
+    #[serde(rename = "AggregatedOption")]
     Option(VariantId::Id, ETy),
+    #[serde(rename = "AggregatedAdt")]
     Adt(
         TypeDeclId::Id,
         Option<VariantId::Id>,
@@ -10,7 +10,7 @@ use crate::values;
 use crate::values::*;
 use serde::ser::SerializeStruct;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 impl Serialize for Place {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
@@ -25,6 +25,27 @@ impl Serialize for Place {
     }
 }
 
+/// The shape of the serialized places (see the [Serialize] implementation for [Place])
+#[derive(Deserialize)]
+#[serde(rename = "Place")]
+struct SerializedPlace {
+    var_id: VarId::Id,
+    projection: Vec<ProjectionElem>,
+}
+
+impl<'de> Deserialize<'de> for Place {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let place = SerializedPlace::deserialize(deserializer)?;
+        Ok(Place {
+            var_id: place.var_id,
+            projection: Projection::from(place.projection),
+        })
+    }
+}
+
 impl std::fmt::Display for BorrowKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         match self {
@@ -156,6 +177,7 @@ impl Operand {
             Operand::Copy(p) => format!("copy ({})", p.fmt_with_ctx(ctx)).to_string(),
             Operand::Move(p) => format!("move ({})", p.fmt_with_ctx(ctx)).to_string(),
             Operand::Constant(_, c) => format!("const ({})", c.fmt_with_ctx(ctx)).to_string(),
+            Operand::Expr(_, rv) => format!("({})", rv.fmt_with_ctx(ctx)).to_string(),
         }
     }
 
@@ -198,6 +220,13 @@ impl Rvalue {
                 y.fmt_with_ctx(ctx)
             )
             .to_string(),
+            Rvalue::CheckedBinaryOp(binop, x, y) => format!(
+                "@checked({} {} {})",
+                x.fmt_with_ctx(ctx),
+                binop.to_string(),
+                y.fmt_with_ctx(ctx)
+            )
+            .to_string(),
             Rvalue::Discriminant(p) => {
                 format!("@discriminant({})", p.fmt_with_ctx(ctx),).to_string()
             }
@@ -260,9 +289,13 @@ impl Serialize for AggregateKind {
         // Also, it seems the "standard" way of doing is the following (this is
         // consistent with what the automatically generated serializer does):
         // - if the arity is > 0, use `serialize_tuple_variant`
-        // - otherwise simply serialize a string with the variant name
+        // - otherwise use `serialize_unit_variant` (which serializes a string
+        //   with the variant name in JSON, and the variant index in the binary
+        //   formats)
         match self {
-            AggregateKind::Tuple => "AggregatedTuple".serialize(serializer),
+            AggregateKind::Tuple => {
+                serializer.serialize_unit_variant("AggregateKind", 0, "AggregatedTuple")
+            }
             AggregateKind::Option(variant_id, ty) => {
                 let mut vs = serializer.serialize_tuple_variant(
                     "AggregateKind",
@@ -278,7 +311,7 @@ impl Serialize for AggregateKind {
             }
             AggregateKind::Adt(def_id, opt_variant_id, regions, tys) => {
                 let mut vs =
-                    serializer.serialize_tuple_variant("AggregateKind", 1, "AggregatedAdt", 4)?;
+                    serializer.serialize_tuple_variant("AggregateKind", 2, "AggregatedAdt", 4)?;
 
                 vs.serialize_field(def_id)?;
                 vs.serialize_field(opt_variant_id)?;
@@ -308,7 +341,9 @@ impl Serialize for OperandConstantValue {
         // It seems the "standard" way of doing is the following (this is
         // consistent with what the automatically generated serializer does):
         // - if the arity is > 0, use `serialize_tuple_variant`
-        // - otherwise simply serialize a string with the variant name
+        // - otherwise use `serialize_unit_variant` (which serializes a string
+        //   with the variant name in JSON, and the variant index in the binary
+        //   formats)
         if variant_arity > 0 {
             let mut vs = serializer.serialize_tuple_variant(
                 enum_name,
@@ -328,7 +363,33 @@ impl Serialize for OperandConstantValue {
             }
             vs.end()
         } else {
-            variant_name.serialize(serializer)
+            serializer.serialize_unit_variant(enum_name, variant_index, variant_name)
         }
     }
 }
+
+/// The shape of the serialized constant values (see the [Serialize] implementation
+/// for [OperandConstantValue])
+#[derive(Deserialize)]
+#[serde(rename = "OperandConstantValue")]
+enum SerializedOperandConstantValue {
+    ConstantValue((ConstantValue,)),
+    ConstantAdt(Option<VariantId::Id>, Vec<OperandConstantValue>),
+}
+
+impl<'de> Deserialize<'de> for OperandConstantValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v = match SerializedOperandConstantValue::deserialize(deserializer)? {
+            SerializedOperandConstantValue::ConstantValue((cv,)) => {
+                OperandConstantValue::ConstantValue(cv)
+            }
+            SerializedOperandConstantValue::ConstantAdt(variant_id, values) => {
+                OperandConstantValue::Adt(variant_id, im::Vector::from(values))
+            }
+        };
+        Ok(v)
+    }
+}
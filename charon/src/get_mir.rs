@@ -5,9 +5,13 @@
 use rustc_hir::def_id::{DefId, LocalDefId};
 use rustc_middle::mir::Body;
 use rustc_middle::ty::{TyCtxt, WithOptConstParam};
+use serde::{Deserialize, Serialize};
 use std::cell::Ref;
 
-#[derive(Clone, Copy)]
+/// Note that we can deserialize this enumeration, because the MIR level can be
+/// selected in the configuration file (see [crate::config]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MirLevel {
     /// Original MIR, directly translated from HIR
     Built,
@@ -43,6 +47,14 @@ fn get_mir_for_def_id_and_level<'tcx>(
     }
 }
 
-pub fn get_mir_for_def_id<'tcx>(tcx: TyCtxt<'tcx>, def_id: LocalDefId) -> &'tcx Body<'tcx> {
-    get_mir_for_def_id_and_level(tcx, def_id, MirLevel::Built)
+/// Retrieve the MIR of a definition, at a given level.
+///
+/// Note that the MIR is modified in place by the compiler: we must always use
+/// the same level during a translation (see the comments for [crate::driver]).
+pub fn get_mir_for_def_id<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: LocalDefId,
+    level: MirLevel,
+) -> &'tcx Body<'tcx> {
+    get_mir_for_def_id_and_level(tcx, def_id, level)
 }
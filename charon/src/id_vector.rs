@@ -8,7 +8,7 @@
 //! Note that this data structure is implemented by using persistent vectors.
 //! This makes the clone operation almost a no-op.
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::{FromIterator, IntoIterator};
 
 pub use std::collections::hash_map::Iter as IterAll;
@@ -262,3 +262,13 @@ impl<I: ToUsize, T: Clone + Serialize> Serialize for Vector<I, T> {
         seq.end()
     }
 }
+
+impl<'de, I: ToUsize, T: Clone + Deserialize<'de>> Deserialize<'de> for Vector<I, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v: Vec<T> = Vec::deserialize(deserializer)?;
+        Ok(Vector::from_iter(v.into_iter()))
+    }
+}
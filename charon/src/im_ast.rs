@@ -5,6 +5,7 @@
 
 use crate::expressions::*;
 pub use crate::im_ast_utils::*;
+use crate::meta::{Attribute, Span};
 use crate::names::FunName;
 use crate::regions_hierarchy::RegionGroups;
 use crate::types::*;
@@ -12,7 +13,7 @@ use crate::values::*;
 use hashlink::linked_hash_map::LinkedHashMap;
 use macros::generate_index_type;
 use macros::{EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // TODO: move this definition
 pub static TAB_INCR: &'static str = "    ";
@@ -25,7 +26,7 @@ generate_index_type!(BlockId);
 pub static START_BLOCK_ID: BlockId::Id = BlockId::ZERO;
 
 /// A variable
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Var {
     /// Unique index identifying the variable
     pub index: VarId::Id,
@@ -34,6 +35,8 @@ pub struct Var {
     pub name: Option<String>,
     /// The variable type
     pub ty: ETy,
+    /// The location of the variable declaration
+    pub span: Span,
 }
 
 /// A function signature.
@@ -42,7 +45,7 @@ pub struct Var {
 /// We need the functions' signatures *with* the region parameters in order
 /// to correctly abstract those functions (number and signature of the backward
 /// functions) - we only use regions for this purpose.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunSig {
     pub region_params: RegionVarId::Vector<RegionVar>,
     /// The region parameters contain early bound and late bound parameters.
@@ -65,7 +68,7 @@ pub struct FunSig {
 }
 
 /// A function body
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GFunBody<T: std::fmt::Debug + Clone + Serialize> {
     pub arg_count: usize,
     pub locals: VarId::Vector<Var>,
@@ -73,10 +76,14 @@ pub struct GFunBody<T: std::fmt::Debug + Clone + Serialize> {
 }
 
 /// A function definition
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GFunDecl<T: std::fmt::Debug + Clone + Serialize> {
     pub def_id: FunDeclId::Id,
     pub name: FunName,
+    /// The location of the definition in the source code
+    pub span: Span,
+    /// The doc comments and the tool attributes of the definition
+    pub attributes: Vec<Attribute>,
     /// The signature contains the inputs/output types *with* non-erased regions.
     /// It also contains the list of region and type parameters.
     pub signature: FunSig,
@@ -90,8 +97,14 @@ pub type FunBody = GFunBody<BlockId::Vector<BlockData>>;
 pub type FunDecl = GFunDecl<BlockId::Vector<BlockData>>;
 pub type FunDecls = FunDeclId::Vector<FunDecl>;
 
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub span: Span,
+    pub content: RawStatement,
+}
+
 #[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize)]
-pub enum Statement {
+pub enum RawStatement {
     Assign(Place, Rvalue),
     FakeRead(Place),
     SetDiscriminant(Place, VariantId::Id),
@@ -115,7 +128,7 @@ pub enum SwitchTargets {
 }
 
 /// A function identifier. See [`Terminator`](Terminator)
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize, Deserialize)]
 pub enum FunId {
     /// A "regular" function (function local to the crate, external function
     /// not treated as a primitive one).
@@ -128,7 +141,7 @@ pub enum FunId {
 
 /// An assumed function identifier, identifying a function coming from a
 /// standard library.
-#[derive(Debug, Clone, Copy, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, Copy, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum AssumedFunId {
     /// `core::mem::replace`
     Replace,
@@ -157,8 +170,14 @@ pub enum AssumedFunId {
     VecIndexMut,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct Terminator {
+    pub span: Span,
+    pub content: RawTerminator,
+}
+
 #[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize)]
-pub enum Terminator {
+pub enum RawTerminator {
     Goto {
         target: BlockId::Id,
     },
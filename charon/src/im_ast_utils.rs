@@ -31,6 +31,7 @@ impl Var {
             index: self.index,
             name: self.name.clone(),
             ty: self.ty.substitute_types(subst),
+            span: self.span,
         }
     }
 }
@@ -91,15 +92,19 @@ impl Serialize for SwitchTargets {
 impl Statement {
     /// Substitute the type variables and return the resulting statement.
     pub fn substitute(&self, subst: &ETypeSubst) -> Statement {
-        match self {
-            Statement::Assign(place, rvalue) => {
-                Statement::Assign(place.substitute(subst), rvalue.substitute(subst))
+        let content = match &self.content {
+            RawStatement::Assign(place, rvalue) => {
+                RawStatement::Assign(place.substitute(subst), rvalue.substitute(subst))
             }
-            Statement::FakeRead(place) => Statement::FakeRead(place.substitute(subst)),
-            Statement::SetDiscriminant(place, variant_id) => {
-                Statement::SetDiscriminant(place.substitute(subst), *variant_id)
+            RawStatement::FakeRead(place) => RawStatement::FakeRead(place.substitute(subst)),
+            RawStatement::SetDiscriminant(place, variant_id) => {
+                RawStatement::SetDiscriminant(place.substitute(subst), *variant_id)
             }
-            Statement::StorageDead(var_id) => Statement::StorageDead(*var_id),
+            RawStatement::StorageDead(var_id) => RawStatement::StorageDead(*var_id),
+        };
+        Statement {
+            span: self.span,
+            content,
         }
     }
 }
@@ -107,27 +112,27 @@ impl Statement {
 impl Terminator {
     /// Substitute the type variables and return the resulting terminator
     pub fn substitute(&self, subst: &ETypeSubst) -> Terminator {
-        match self {
-            Terminator::Goto { target } => Terminator::Goto { target: *target },
-            Terminator::Switch { discr, targets } => Terminator::Switch {
+        let content = match &self.content {
+            RawTerminator::Goto { target } => RawTerminator::Goto { target: *target },
+            RawTerminator::Switch { discr, targets } => RawTerminator::Switch {
                 discr: discr.substitute(subst),
                 targets: targets.substitute(subst),
             },
-            Terminator::Panic => Terminator::Panic,
-            Terminator::Return => Terminator::Return,
-            Terminator::Unreachable => Terminator::Unreachable,
-            Terminator::Drop { place, target } => Terminator::Drop {
+            RawTerminator::Panic => RawTerminator::Panic,
+            RawTerminator::Return => RawTerminator::Return,
+            RawTerminator::Unreachable => RawTerminator::Unreachable,
+            RawTerminator::Drop { place, target } => RawTerminator::Drop {
                 place: place.substitute(subst),
                 target: *target,
             },
-            Terminator::Call {
+            RawTerminator::Call {
                 func,
                 region_args,
                 type_args,
                 args,
                 dest,
                 target,
-            } => Terminator::Call {
+            } => RawTerminator::Call {
                 func: func.clone(),
                 region_args: region_args.clone(),
                 type_args: type_args
@@ -138,15 +143,19 @@ impl Terminator {
                 dest: dest.substitute(subst),
                 target: *target,
             },
-            Terminator::Assert {
+            RawTerminator::Assert {
                 cond,
                 expected,
                 target,
-            } => Terminator::Assert {
+            } => RawTerminator::Assert {
                 cond: cond.substitute(subst),
                 expected: *expected,
                 target: *target,
             },
+        };
+        Terminator {
+            span: self.span,
+            content,
         }
     }
 }
@@ -175,23 +184,23 @@ impl Statement {
             + Formatter<(TypeDeclId::Id, VariantId::Id)>
             + Formatter<(TypeDeclId::Id, Option<VariantId::Id>, FieldId::Id)>,
     {
-        match self {
-            Statement::Assign(place, rvalue) => format!(
+        match &self.content {
+            RawStatement::Assign(place, rvalue) => format!(
                 "{} := {}",
                 place.fmt_with_ctx(ctx),
                 rvalue.fmt_with_ctx(ctx),
             )
             .to_owned(),
-            Statement::FakeRead(place) => {
+            RawStatement::FakeRead(place) => {
                 format!("@fake_read({})", place.fmt_with_ctx(ctx),).to_owned()
             }
-            Statement::SetDiscriminant(place, variant_id) => format!(
+            RawStatement::SetDiscriminant(place, variant_id) => format!(
                 "@discriminant({}) := {}",
                 place.fmt_with_ctx(ctx),
                 variant_id.to_string()
             )
             .to_owned(),
-            Statement::StorageDead(vid) => {
+            RawStatement::StorageDead(vid) => {
                 format!("@storage_dead({})", var_id_to_pretty_string(*vid)).to_owned()
             }
         }
@@ -283,9 +292,9 @@ impl Terminator {
             + Formatter<(TypeDeclId::Id, VariantId::Id)>
             + Formatter<(TypeDeclId::Id, Option<VariantId::Id>, FieldId::Id)>,
     {
-        match self {
-            Terminator::Goto { target } => format!("goto bb{}", target.to_string()).to_string(),
-            Terminator::Switch { discr, targets } => match targets {
+        match &self.content {
+            RawTerminator::Goto { target } => format!("goto bb{}", target.to_string()).to_string(),
+            RawTerminator::Switch { discr, targets } => match targets {
                 SwitchTargets::If(true_block, false_block) => format!(
                     "if {} -> bb{} else -> bb{}",
                     discr.fmt_with_ctx(ctx),
@@ -306,16 +315,16 @@ impl Terminator {
                     format!("switch {} -> {}", discr.fmt_with_ctx(ctx), maps).to_string()
                 }
             },
-            Terminator::Panic => "panic".to_string(),
-            Terminator::Return => "return".to_string(),
-            Terminator::Unreachable => "unreachable".to_string(),
-            Terminator::Drop { place, target } => format!(
+            RawTerminator::Panic => "panic".to_string(),
+            RawTerminator::Return => "return".to_string(),
+            RawTerminator::Unreachable => "unreachable".to_string(),
+            RawTerminator::Drop { place, target } => format!(
                 "drop {} -> bb{}",
                 place.fmt_with_ctx(ctx),
                 target.to_string()
             )
             .to_string(),
-            Terminator::Call {
+            RawTerminator::Call {
                 func,
                 region_args,
                 type_args,
@@ -333,7 +342,7 @@ impl Terminator {
                 )
                 .to_string()
             }
-            Terminator::Assert {
+            RawTerminator::Assert {
                 cond,
                 expected,
                 target,
@@ -819,4 +828,16 @@ impl FunDecl {
         // Use the contexts for printing
         self.gfmt_with_ctx("", &fun_sig_ctx, &eval_ctx)
     }
+
+    /// Format a block of the body. The function must have a body.
+    pub fn fmt_block_with_defs<'ctx>(
+        &self,
+        block_id: BlockId::Id,
+        ty_ctx: &'ctx TypeDecls,
+        fun_ctx: &'ctx FunDecls,
+    ) -> String {
+        let body = self.body.as_ref().unwrap();
+        let ctx = AstFormatter::new(ty_ctx, fun_ctx, &self.signature.type_params, &body.locals);
+        body.body.get(block_id).unwrap().fmt_with_ctx("", &ctx)
+    }
 }
@@ -21,6 +21,7 @@
 use crate::im_ast as src;
 use crate::im_ast::FunDeclId;
 use crate::llbc_ast as tgt;
+use crate::meta::Span;
 use crate::types::TypeDecls;
 use crate::values as v;
 use hashlink::linked_hash_map::LinkedHashMap;
@@ -33,6 +34,7 @@ use petgraph::Direction;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::iter::FromIterator;
+use std::path::PathBuf;
 
 pub type Defs = tgt::FunDecls;
 
@@ -42,10 +44,10 @@ type Cfg = DiGraphMap<src::BlockId::Id, ()>;
 fn get_block_targets(body: &src::FunBody, block_id: src::BlockId::Id) -> Vec<src::BlockId::Id> {
     let block = body.body.get(block_id).unwrap();
 
-    match &block.terminator {
-        src::Terminator::Goto { target }
-        | src::Terminator::Drop { place: _, target }
-        | src::Terminator::Call {
+    match &block.terminator.content {
+        src::RawTerminator::Goto { target }
+        | src::RawTerminator::Drop { place: _, target }
+        | src::RawTerminator::Call {
             func: _,
             region_args: _,
             type_args: _,
@@ -53,15 +55,17 @@ fn get_block_targets(body: &src::FunBody, block_id: src::BlockId::Id) -> Vec<src
             dest: _,
             target,
         }
-        | src::Terminator::Assert {
+        | src::RawTerminator::Assert {
             cond: _,
             expected: _,
             target,
         } => {
             vec![*target]
         }
-        src::Terminator::Switch { discr: _, targets } => targets.get_targets(),
-        src::Terminator::Panic | src::Terminator::Unreachable | src::Terminator::Return => {
+        src::RawTerminator::Switch { discr: _, targets } => targets.get_targets(),
+        src::RawTerminator::Panic
+        | src::RawTerminator::Unreachable
+        | src::RawTerminator::Return => {
             vec![]
         }
     }
@@ -130,7 +134,7 @@ fn build_cfg_partial_info(body: &src::FunBody) -> CfgPartialInfo {
 
 fn block_is_switch(body: &src::FunBody, block_id: src::BlockId::Id) -> bool {
     let block = body.body.get(block_id).unwrap();
-    block.terminator.is_switch()
+    block.terminator.content.is_switch()
 }
 
 fn build_cfg_partial_info_edges(
@@ -1266,7 +1270,7 @@ fn combine_statement_and_statement(
     next_st: Option<tgt::Statement>,
 ) -> tgt::Statement {
     match next_st {
-        Some(next_st) => tgt::Statement::Sequence(Box::new(statement), Box::new(next_st)),
+        Some(next_st) => tgt::Statement::new_seq(statement, next_st),
         None => statement,
     }
 }
@@ -1331,12 +1335,16 @@ fn translate_child_block(
     parent_loops: Vector<src::BlockId::Id>,
     switch_exit_blocks: &im::HashSet<src::BlockId::Id>,
     explored: &mut HashSet<src::BlockId::Id>,
+    span: Span,
     child_id: src::BlockId::Id,
 ) -> Option<tgt::Statement> {
     // Check if this is a backward call
     match get_goto_kind(exits_info, &parent_loops, switch_exit_blocks, child_id) {
-        GotoKind::Break(index) => Some(tgt::Statement::Break(index)),
-        GotoKind::Continue(index) => Some(tgt::Statement::Continue(index)),
+        GotoKind::Break(index) => Some(tgt::Statement::new(span, tgt::RawStatement::Break(index))),
+        GotoKind::Continue(index) => Some(tgt::Statement::new(
+            span,
+            tgt::RawStatement::Continue(index),
+        )),
         // If we are going to an exit block we simply ignore the goto
         GotoKind::ExitBlock => None,
         GotoKind::Goto => {
@@ -1355,24 +1363,31 @@ fn translate_child_block(
     }
 }
 
-fn opt_statement_to_nop_if_none(opt_st: Option<tgt::Statement>) -> tgt::Statement {
+/// [span]: the span to use for the `Nop`
+fn opt_statement_to_nop_if_none(opt_st: Option<tgt::Statement>, span: Span) -> tgt::Statement {
     match opt_st {
         Some(st) => st,
-        None => tgt::Statement::Nop,
+        None => tgt::Statement::new(span, tgt::RawStatement::Nop),
     }
 }
 
+/// Return `None` for the `StorageDead`s: LLBC doesn't model the storage of
+/// the local variables, so we simply ignore them.
 fn translate_statement(st: &src::Statement) -> Option<tgt::Statement> {
-    match st {
-        src::Statement::Assign(place, rvalue) => {
-            Some(tgt::Statement::Assign(place.clone(), rvalue.clone()))
+    let content = match &st.content {
+        src::RawStatement::Assign(place, rvalue) => {
+            tgt::RawStatement::Assign(place.clone(), rvalue.clone())
         }
-        src::Statement::FakeRead(place) => Some(tgt::Statement::FakeRead(place.clone())),
-        src::Statement::SetDiscriminant(place, variant_id) => {
-            Some(tgt::Statement::SetDiscriminant(place.clone(), *variant_id))
+        src::RawStatement::FakeRead(place) => tgt::RawStatement::FakeRead(place.clone()),
+        src::RawStatement::SetDiscriminant(place, variant_id) => {
+            tgt::RawStatement::SetDiscriminant(place.clone(), *variant_id)
         }
-        src::Statement::StorageDead(_var_id) => None,
-    }
+        // LLBC doesn't model the storage of the locals: the `StorageDead`s are
+        // removed here (the caller filters them out), whatever the
+        // micro-passes the user selects, and not in [crate::cleanup].
+        src::RawStatement::StorageDead(_var_id) => return None,
+    };
+    Some(tgt::Statement::new(st.span, content))
 }
 
 fn translate_terminator(
@@ -1385,10 +1400,13 @@ fn translate_terminator(
     explored: &mut HashSet<src::BlockId::Id>,
     terminator: &src::Terminator,
 ) -> Option<tgt::Statement> {
-    match terminator {
-        src::Terminator::Panic | src::Terminator::Unreachable => Some(tgt::Statement::Panic),
-        src::Terminator::Return => Some(tgt::Statement::Return),
-        src::Terminator::Goto { target } => translate_child_block(
+    let span = terminator.span;
+    match &terminator.content {
+        src::RawTerminator::Panic | src::RawTerminator::Unreachable => {
+            Some(tgt::Statement::new(span, tgt::RawStatement::Panic))
+        }
+        src::RawTerminator::Return => Some(tgt::Statement::new(span, tgt::RawStatement::Return)),
+        src::RawTerminator::Goto { target } => translate_child_block(
             no_code_duplication,
             cfg,
             body,
@@ -1396,9 +1414,10 @@ fn translate_terminator(
             parent_loops,
             switch_exit_blocks,
             explored,
+            span,
             *target,
         ),
-        src::Terminator::Drop { place, target } => {
+        src::RawTerminator::Drop { place, target } => {
             let opt_child = translate_child_block(
                 no_code_duplication,
                 cfg,
@@ -1407,12 +1426,13 @@ fn translate_terminator(
                 parent_loops,
                 switch_exit_blocks,
                 explored,
+                span,
                 *target,
             );
-            let st = tgt::Statement::Drop(place.clone());
+            let st = tgt::Statement::new(span, tgt::RawStatement::Drop(place.clone()));
             Some(combine_statement_and_statement(st, opt_child))
         }
-        src::Terminator::Call {
+        src::RawTerminator::Call {
             func,
             region_args,
             type_args,
@@ -1428,18 +1448,20 @@ fn translate_terminator(
                 parent_loops,
                 switch_exit_blocks,
                 explored,
+                span,
                 *target,
             );
-            let st = tgt::Statement::Call(tgt::Call {
+            let st = tgt::RawStatement::Call(tgt::Call {
                 func: func.clone(),
                 region_args: region_args.clone(),
                 type_args: type_args.clone(),
                 args: args.clone(),
                 dest: dest.clone(),
             });
+            let st = tgt::Statement::new(span, st);
             Some(combine_statement_and_statement(st, opt_child))
         }
-        src::Terminator::Assert {
+        src::RawTerminator::Assert {
             cond,
             expected,
             target,
@@ -1452,15 +1474,17 @@ fn translate_terminator(
                 parent_loops,
                 switch_exit_blocks,
                 explored,
+                span,
                 *target,
             );
-            let st = tgt::Statement::Assert(tgt::Assert {
+            let st = tgt::RawStatement::Assert(tgt::Assert {
                 cond: cond.clone(),
                 expected: *expected,
             });
+            let st = tgt::Statement::new(span, st);
             Some(combine_statement_and_statement(st, opt_child))
         }
-        src::Terminator::Switch { discr, targets } => {
+        src::RawTerminator::Switch { discr, targets } => {
             // Translate the target expressions
             let targets = match &targets {
                 src::SwitchTargets::If(then_tgt, else_tgt) => {
@@ -1473,9 +1497,10 @@ fn translate_terminator(
                         parent_loops.clone(),
                         switch_exit_blocks,
                         explored,
+                        span,
                         *then_tgt,
                     );
-                    let then_exp = opt_statement_to_nop_if_none(then_exp);
+                    let then_exp = opt_statement_to_nop_if_none(then_exp, span);
                     let else_exp = translate_child_block(
                         no_code_duplication,
                         cfg,
@@ -1484,9 +1509,10 @@ fn translate_terminator(
                         parent_loops.clone(),
                         switch_exit_blocks,
                         explored,
+                        span,
                         *else_tgt,
                     );
-                    let else_exp = opt_statement_to_nop_if_none(else_exp);
+                    let else_exp = opt_statement_to_nop_if_none(else_exp, span);
 
                     // Translate
                     tgt::SwitchTargets::If(Box::new(then_exp), Box::new(else_exp))
@@ -1535,9 +1561,10 @@ fn translate_terminator(
                                 parent_loops.clone(),
                                 switch_exit_blocks,
                                 explored,
+                                span,
                                 *bid,
                             );
-                            let exp = opt_statement_to_nop_if_none(exp);
+                            let exp = opt_statement_to_nop_if_none(exp, span);
                             branches.insert(*bid, (vec![*v], exp));
                         }
                     }
@@ -1552,9 +1579,10 @@ fn translate_terminator(
                         parent_loops.clone(),
                         switch_exit_blocks,
                         explored,
+                        span,
                         *otherwise,
                     );
-                    let otherwise_exp = opt_statement_to_nop_if_none(otherwise_exp);
+                    let otherwise_exp = opt_statement_to_nop_if_none(otherwise_exp, span);
 
                     // Translate
                     tgt::SwitchTargets::SwitchInt(*int_ty, targets_exps, Box::new(otherwise_exp))
@@ -1562,7 +1590,8 @@ fn translate_terminator(
             };
 
             // Return
-            Some(tgt::Statement::Switch(discr.clone(), targets))
+            let st = tgt::RawStatement::Switch(discr.clone(), targets);
+            Some(tgt::Statement::new(span, st))
         }
     }
 }
@@ -1575,7 +1604,7 @@ fn combine_expressions(
         None => exp2,
         Some(exp1) => match exp2 {
             None => Some(exp1),
-            Some(exp2) => Some(tgt::Statement::Sequence(Box::new(exp1), Box::new(exp2))),
+            Some(exp2) => Some(tgt::Statement::new_seq(exp1, exp2)),
         },
     }
 }
@@ -1590,30 +1619,31 @@ fn is_terminal(exp: &tgt::Statement) -> bool {
 /// - a break which goes to a loop outside the expression
 /// - a continue statement
 fn is_terminal_explore(num_loops: usize, st: &tgt::Statement) -> bool {
-    match st {
-        tgt::Statement::Assign(_, _)
-        | tgt::Statement::FakeRead(_)
-        | tgt::Statement::SetDiscriminant(_, _)
-        | tgt::Statement::Drop(_)
-        | tgt::Statement::Assert(_)
-        | tgt::Statement::Call(_)
-        | tgt::Statement::Nop => false,
-        tgt::Statement::Panic | tgt::Statement::Return => true,
-        tgt::Statement::Break(index) => *index >= num_loops,
-        tgt::Statement::Continue(_index) => true,
-        tgt::Statement::Sequence(st1, st2) => {
+    match &st.content {
+        tgt::RawStatement::Assign(_, _)
+        | tgt::RawStatement::FakeRead(_)
+        | tgt::RawStatement::SetDiscriminant(_, _)
+        | tgt::RawStatement::Drop(_)
+        | tgt::RawStatement::Assert(_)
+        | tgt::RawStatement::Call(_)
+        | tgt::RawStatement::Try(_)
+        | tgt::RawStatement::Nop => false,
+        tgt::RawStatement::Panic | tgt::RawStatement::Return => true,
+        tgt::RawStatement::Break(index) => *index >= num_loops,
+        tgt::RawStatement::Continue(_index) => true,
+        tgt::RawStatement::Sequence(st1, st2) => {
             if is_terminal_explore(num_loops, st1) {
                 return true;
             } else {
                 return is_terminal_explore(num_loops, st2);
             }
         }
-        tgt::Statement::Switch(_, targets) => targets
+        tgt::RawStatement::Switch(_, targets) => targets
             .get_targets()
             .iter()
             .all(|tgt_st| is_terminal_explore(num_loops, tgt_st)),
-        tgt::Statement::Loop(loop_st) => {
-            return is_terminal_explore(num_loops + 1, loop_st);
+        tgt::RawStatement::Loop(loop_) => {
+            return is_terminal_explore(num_loops + 1, &loop_.body);
         }
     }
 }
@@ -1656,7 +1686,7 @@ fn translate_block(
     // If we enter a switch or a loop, we need to check if we own the exit
     // block, in which case we need to append it to the loop/switch body
     // in a sequence
-    let is_switch = block.terminator.is_switch();
+    let is_switch = block.terminator.content.is_switch();
     let next_block = if is_loop {
         *exits_info.owned_loop_exits.get(&block_id).unwrap()
     } else if is_switch {
@@ -1717,7 +1747,17 @@ fn translate_block(
         let exp = combine_statements_and_statement(statements, terminator);
 
         // Put the whole loop body inside a `Loop` wrapper
-        let exp = tgt::Statement::Loop(Box::new(exp.unwrap()));
+        let exp = exp.unwrap();
+        let span = exp.span;
+        let exp = tgt::Statement::new(
+            span,
+            tgt::RawStatement::Loop(tgt::Loop {
+                body: Box::new(exp),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                info: Option::None,
+            }),
+        );
 
         // Add the exit block
         let exp = if next_block.is_some() {
@@ -1850,6 +1890,8 @@ fn translate_function(
     tgt::FunDecl {
         def_id: src_def.def_id,
         name: src_def.name.clone(),
+        span: src_def.span,
+        attributes: src_def.attributes.clone(),
         signature: src_def.signature.clone(),
         body,
     }
@@ -1861,8 +1903,12 @@ fn translate_function(
 /// can be a sign that the reconstruction is of poor quality, but sometimes
 /// code duplication is necessary, in the presence of "fused" match branches for
 /// instance).
+///
+/// [dump_cfg]: if not `None`, dump the CFGs of the functions as DOT files (see
+/// [dump_cfgs]).
 pub fn translate_functions(
     no_code_duplication: bool,
+    dump_cfg: &Option<CfgDumpOptions>,
     type_defs: &TypeDecls,
     src_defs: &src::FunDecls,
 ) -> Defs {
@@ -1887,5 +1933,189 @@ pub fn translate_functions(
         );
     }
 
+    if let Some(options) = dump_cfg {
+        dump_cfgs(options, type_defs, src_defs, &out_defs);
+    }
+
     out_defs
 }
+
+/// The options of the CFG dumps (see `--dump-cfg`)
+#[derive(Debug, Clone)]
+pub struct CfgDumpOptions {
+    /// The directory in which to generate the DOT files
+    pub dir: PathBuf,
+    /// If true, print the reconstructed LLBC body next to the CFG
+    pub with_llbc: bool,
+}
+
+/// Escape a string so that it can be used in a DOT label (for the nodes of
+/// shape `box`). We align the lines to the left.
+fn dot_label(s: &str) -> String {
+    let s = s.replace('\\', "\\\\").replace('"', "\\\"");
+    let lines: Vec<&str> = s.lines().collect();
+    format!("{}\\l", lines.join("\\l"))
+}
+
+/// Generate the DOT representation of the CFG of a function, annotated with the
+/// information computed for the control-flow reconstruction: the loop entries
+/// and their exits, the switches and their exits, and the backward edges.
+///
+/// Note that we recompute this information rather than saving it during the
+/// translation: dumping the CFGs is only used for debugging.
+fn fmt_cfg_as_dot(
+    options: &CfgDumpOptions,
+    type_defs: &TypeDecls,
+    src_defs: &src::FunDecls,
+    tgt_defs: &Defs,
+    def_id: FunDeclId::Id,
+) -> Option<String> {
+    let src_def = src_defs.get(def_id).unwrap();
+    let body = src_def.body.as_ref()?;
+    let cfg_info = compute_cfg_info_from_partial(build_cfg_partial_info(body));
+    let exits_info = compute_loop_switch_exits(&cfg_info);
+
+    let mut out: Vec<String> = Vec::new();
+    out.push(format!("digraph \"{}\" {{", src_def.name));
+    out.push("  node [shape=box, fontname=\"monospace\"];".to_string());
+    out.push("  subgraph cluster_im {".to_string());
+    out.push("    label=\"IM\";".to_string());
+
+    // The blocks
+    for block_id in body.body.iter_indices() {
+        let mut label = format!("{}:\n", format!("bb{}", block_id));
+        label.push_str(&src_def.fmt_block_with_defs(block_id, type_defs, src_defs));
+        let mut attrs = Vec::new();
+        if cfg_info.loop_entries.contains(&block_id) {
+            let exit = exits_info.loop_exits.get(&block_id).unwrap();
+            let owned = exits_info.owned_loop_exits.get(&block_id).unwrap();
+            let exit = match exit {
+                None => "none".to_string(),
+                Some(exit) => {
+                    let owned = if owned.is_some() { "" } else { " (not owned)" };
+                    format!("{}{}", format!("bb{}", exit), owned)
+                }
+            };
+            label.push_str(&format!("\n[loop entry, exit: {}]", exit));
+            attrs.push("style=filled, fillcolor=lightblue".to_string());
+        }
+        if cfg_info.switch_blocks.contains(&block_id) {
+            let exit = match exits_info.owned_switch_exits.get(&block_id) {
+                Some(Some(exit)) => format!("bb{}", exit),
+                _ => "none".to_string(),
+            };
+            label.push_str(&format!("\n[switch, exit: {}]", exit));
+            if !cfg_info.loop_entries.contains(&block_id) {
+                attrs.push("style=filled, fillcolor=lightyellow".to_string());
+            }
+        }
+        attrs.push(format!("label=\"{}\"", dot_label(&label)));
+        out.push(format!(
+            "    {} [{}];",
+            format!("bb{}", block_id),
+            attrs.join(", ")
+        ));
+    }
+
+    // The edges
+    for (from, to, _) in cfg_info.cfg.all_edges() {
+        let attrs = if cfg_info.backward_edges.contains(&(from, to)) {
+            " [style=dashed, color=red]"
+        } else {
+            ""
+        };
+        out.push(format!(
+            "    {} -> {}{};",
+            format!("bb{}", from),
+            format!("bb{}", to),
+            attrs
+        ));
+    }
+
+    // The exits
+    let loop_exits = exits_info
+        .loop_exits
+        .iter()
+        .map(|(b, e)| (b, e, "loop exit"));
+    let switch_exits = exits_info
+        .owned_switch_exits
+        .iter()
+        .map(|(b, e)| (b, e, "switch exit"));
+    for (block_id, exit, kind) in loop_exits.chain(switch_exits) {
+        if let Some(exit) = exit {
+            out.push(format!(
+                "    {} -> {} [style=dotted, color=blue, constraint=false, label=\"{}\"];",
+                format!("bb{}", block_id),
+                format!("bb{}", exit),
+                kind
+            ));
+        }
+    }
+    out.push("  }".to_string());
+
+    // The reconstructed body
+    if options.with_llbc {
+        let tgt_def = tgt_defs.get(def_id).unwrap();
+        out.push("  subgraph cluster_llbc {".to_string());
+        out.push("    label=\"LLBC\";".to_string());
+        out.push(format!(
+            "    llbc [label=\"{}\"];",
+            dot_label(&tgt_def.fmt_with_defs(type_defs, tgt_defs))
+        ));
+        out.push("  }".to_string());
+    }
+
+    out.push("}".to_string());
+    Some(format!("{}\n", out.join("\n")))
+}
+
+/// Dump the CFGs of the (transparent) functions, one DOT file per function.
+/// The files are named after the functions. We only report the errors: the
+/// dumps are used for debugging purposes, and shouldn't prevent the translation.
+fn dump_cfgs(
+    options: &CfgDumpOptions,
+    type_defs: &TypeDecls,
+    src_defs: &src::FunDecls,
+    tgt_defs: &Defs,
+) {
+    if std::fs::create_dir_all(&options.dir).is_err() {
+        error!("Could not create the directory: {:?}", options.dir);
+        return;
+    }
+
+    let mut used_names = HashSet::new();
+    for def_id in src_defs.iter_indices() {
+        let dot = match fmt_cfg_as_dot(options, type_defs, src_defs, tgt_defs, def_id) {
+            None => continue,
+            Some(dot) => dot,
+        };
+
+        // Compute the file name: we use the function name, and add the
+        // function id in case of collision
+        let name: String = src_defs
+            .get(def_id)
+            .unwrap()
+            .name
+            .to_string()
+            .replace("::", ".")
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = if used_names.insert(name.clone()) {
+            name
+        } else {
+            format!("{}.{}", name, def_id)
+        };
+        let path = options.dir.join(format!("{}.dot", name));
+
+        if std::fs::write(&path, dot).is_err() {
+            error!("Could not write to {:?}", path);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Inline the calls to small non-recursive functions, like the accessors: this
+//! makes the verification of code using many tiny helpers easier.
+//!
+//! We inline a function if it has a body, is not (mutually) recursive, and
+//! either has the `#[charon::inline]` attribute, or a body of at most
+//! `inline_threshold` statements (see [crate::driver::TransOptions], and
+//! [DEFAULT_INLINE_THRESHOLD] for the default value). The `#[charon::inline(never)]` attribute
+//! prevents a function from being inlined. We don't inline the functions
+//! containing a `?` (see [crate::reconstruct_try]), which returns from the
+//! enclosing function, and we don't inline the calls with type arguments (the
+//! inlined bodies would need to be instantiated).
+//!
+//! The call `dest := f(move x, copy y)` is replaced with:
+//!   ```
+//!   arg1 := move x;
+//!   arg2 := copy y;
+//!   ... // The body of f
+//!   dest := move ret;
+//!   ```
+//! where `arg1`, `arg2`, `ret` and the other locals of `f` are fresh locals of
+//! the caller. If the `return`s of the body of `f` are all in tail position, we
+//! simply remove them. Otherwise, we wrap the body in a loop, and replace the
+//! `return`s with breaks out of this loop. Note that in this case the caller
+//! contains a loop, and is considered as potentially divergent (see
+//! [crate::divergent]).
+//!
+//! We inline the functions bottom-up, in the order of the call graph: the
+//! inlined bodies have themselves been transformed. The inlined functions are
+//! not removed from the crate.
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. It
+//! should be applied after the passes cleaning the bodies, and must be applied
+//! before [crate::remove_unused_locals].
+
+use crate::expressions::*;
+use crate::im_ast::{FunDeclId, FunId, Var};
+use crate::llbc_ast::{Call, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
+use crate::meta::{Attribute, Span};
+use crate::remove_unused_locals;
+use crate::reorder_decls::{group_declarations, GDeclarationGroup};
+use crate::values::*;
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
+
+/// The default maximal number of statements of the bodies we inline, for the
+/// functions without `#[charon::inline]` attribute
+pub static DEFAULT_INLINE_THRESHOLD: usize = 5;
+
+struct Ctx<'a> {
+    /// The function declarations, already transformed
+    defs: &'a HashMap<FunDeclId::Id, FunDecl>,
+    /// The functions we inline
+    inlinable: &'a HashSet<FunDeclId::Id>,
+    /// The locals of the function we are transforming: we add the locals of
+    /// the bodies we inline
+    locals: VarId::Vector<Var>,
+}
+
+/// Check the `#[charon::inline]` attribute of a declaration: return
+/// `Some(true)` for `#[charon::inline]`, `Some(false)` for
+/// `#[charon::inline(never)]` and `None` if there is no such attribute
+fn inline_attribute(def: &FunDecl) -> Option<bool> {
+    def.attributes.iter().find_map(|attr| match attr {
+        Attribute::Tool { path, args } if *path == ["charon", "inline"] => {
+            Some(args.as_deref() != Some("(never)"))
+        }
+        _ => None,
+    })
+}
+
+/// The number of statements (ignoring the sequences and the `nop`s)
+fn size(st: &Statement) -> usize {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => size(st1) + size(st2),
+        RawStatement::Switch(_, targets) => {
+            1 + targets
+                .get_targets()
+                .iter()
+                .map(|st| size(st))
+                .sum::<usize>()
+        }
+        RawStatement::Loop(loop_) => 1 + size(&loop_.body),
+        RawStatement::Nop => 0,
+        _ => 1,
+    }
+}
+
+/// Return true if the statement contains a statement satisfying [pred]
+fn contains(st: &Statement, pred: &dyn Fn(&RawStatement) -> bool) -> bool {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => contains(st1, pred) || contains(st2, pred),
+        RawStatement::Switch(_, targets) => {
+            targets.get_targets().iter().any(|st| contains(st, pred))
+        }
+        RawStatement::Loop(loop_) => contains(&loop_.body, pred),
+        content => pred(content),
+    }
+}
+
+fn is_inlinable(def: &FunDecl, threshold: usize) -> bool {
+    match &def.body {
+        Option::None => false,
+        Option::Some(body) => {
+            !contains(&body.body, &RawStatement::is_try)
+                && inline_attribute(def).unwrap_or_else(|| size(&body.body) <= threshold)
+        }
+    }
+}
+
+fn called_functions(st: &Statement, ids: &mut Vec<FunDeclId::Id>) {
+    match &st.content {
+        RawStatement::Call(Call {
+            func: FunId::Regular(id),
+            ..
+        }) => {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        RawStatement::Sequence(st1, st2) => {
+            called_functions(st1, ids);
+            called_functions(st2, ids);
+        }
+        RawStatement::Switch(_, targets) => {
+            for st in targets.get_targets() {
+                called_functions(st, ids);
+            }
+        }
+        RawStatement::Loop(loop_) => called_functions(&loop_.body, ids),
+        _ => (),
+    }
+}
+
+/// Return true if the `return`s of the statement are all in tail position
+fn returns_in_tail(st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => {
+            !contains(st1, &RawStatement::is_return) && returns_in_tail(st2)
+        }
+        RawStatement::Switch(_, targets) => {
+            targets.get_targets().iter().all(|st| returns_in_tail(st))
+        }
+        RawStatement::Loop(loop_) => !contains(&loop_.body, &RawStatement::is_return),
+        _ => true,
+    }
+}
+
+/// Replace the `return`s of an inlined body. If [loop_depth] is `None`, the
+/// `return`s are in tail position and we replace them with `nop`s. Otherwise,
+/// we replace them with breaks out of the loop we wrap the body in, and
+/// [loop_depth] counts the loops of the body we entered.
+fn replace_returns(st: Statement, loop_depth: Option<usize>) -> Statement {
+    let content = match st.content {
+        RawStatement::Return => match loop_depth {
+            Option::None => RawStatement::Nop,
+            Option::Some(depth) => RawStatement::Break(depth),
+        },
+        RawStatement::Sequence(st1, st2) => RawStatement::Sequence(
+            Box::new(replace_returns(*st1, loop_depth)),
+            Box::new(replace_returns(*st2, loop_depth)),
+        ),
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                    Box::new(replace_returns(*st1, loop_depth)),
+                    Box::new(replace_returns(*st2, loop_depth)),
+                ),
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets = Vec::from_iter(
+                        targets
+                            .into_iter()
+                            .map(|(v, e)| (v, replace_returns(e, loop_depth))),
+                    );
+                    let otherwise = replace_returns(*otherwise, loop_depth);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(replace_returns(
+                *loop_.body,
+                loop_depth.map(|depth| depth + 1),
+            )),
+            ..loop_
+        }),
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+/// Build the sequence `st1; st2`, keeping the sequences right-nested
+fn chain(st1: Statement, st2: Statement) -> Statement {
+    match st1.content {
+        RawStatement::Sequence(st11, st12) => Statement::new_seq(*st11, chain(*st12, st2)),
+        _ => Statement::new_seq(st1, st2),
+    }
+}
+
+fn inline_call(ctx: &mut Ctx, span: Span, id: FunDeclId::Id, call: Call) -> Statement {
+    let defs = ctx.defs;
+    let body = defs.get(&id).unwrap().body.as_ref().unwrap();
+
+    // Introduce fresh locals for the locals of the inlined body
+    let mut vids_map: HashMap<VarId::Id, VarId::Id> = HashMap::new();
+    for var in &body.locals {
+        let index = VarId::Id::new(ctx.locals.len());
+        vids_map.insert(var.index, index);
+        ctx.locals.push_back(Var {
+            index,
+            ..var.clone()
+        });
+    }
+    let local = |i: usize| Place {
+        var_id: *vids_map.get(&VarId::Id::new(i)).unwrap(),
+        projection: Projection::new(),
+    };
+
+    // Initialize the parameters (the return variable has index 0)
+    let mut sts: Vec<Statement> = Vec::new();
+    for (i, arg) in call.args.into_iter().enumerate() {
+        let content = RawStatement::Assign(local(i + 1), Rvalue::Use(arg));
+        sts.push(Statement::new(span, content));
+    }
+
+    // The body
+    let inlined = remove_unused_locals::transform_st(&vids_map, body.body.clone());
+    let inlined = if returns_in_tail(&inlined) {
+        replace_returns(inlined, Option::None)
+    } else {
+        let inlined = replace_returns(inlined, Option::Some(0));
+        let content = RawStatement::Loop(Loop {
+            body: Box::new(inlined),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            info: Option::None,
+        });
+        Statement::new(span, content)
+    };
+    sts.push(inlined);
+
+    // Retrieve the result
+    let content = RawStatement::Assign(call.dest, Rvalue::Use(Operand::Move(local(0))));
+    sts.push(Statement::new(span, content));
+
+    let mut st = sts.pop().unwrap();
+    while let Some(prev) = sts.pop() {
+        st = chain(prev, st);
+    }
+    st
+}
+
+fn transform_st(ctx: &mut Ctx, st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::Call(call) => {
+            let inlined = match call.func {
+                FunId::Regular(id) if call.type_args.is_empty() && ctx.inlinable.contains(&id) => {
+                    Option::Some(id)
+                }
+                _ => Option::None,
+            };
+            match inlined {
+                Option::Some(id) => return inline_call(ctx, st.span, id, call),
+                Option::None => RawStatement::Call(call),
+            }
+        }
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => {
+                    let st1 = transform_st(ctx, *st1);
+                    let st2 = transform_st(ctx, *st2);
+                    SwitchTargets::If(Box::new(st1), Box::new(st2))
+                }
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets =
+                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(ctx, e))));
+                    let otherwise = transform_st(ctx, *otherwise);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(ctx, *loop_.body)),
+            ..loop_
+        }),
+        RawStatement::Sequence(st1, st2) => {
+            let st1 = transform_st(ctx, *st1);
+            let st2 = transform_st(ctx, *st2);
+            return chain(st1, st2);
+        }
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+fn transform_def(
+    defs: &HashMap<FunDeclId::Id, FunDecl>,
+    inlinable: &HashSet<FunDeclId::Id>,
+    mut def: FunDecl,
+) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    def.body = match def.body {
+        Option::Some(mut body) => {
+            let mut ctx = Ctx {
+                defs,
+                inlinable,
+                locals: body.locals,
+            };
+            body.body = transform_st(&mut ctx, body.body);
+            body.locals = ctx.locals;
+            Option::Some(body)
+        }
+        Option::None => Option::None,
+    };
+    def
+}
+
+pub fn transform(defs: FunDecls, threshold: usize) -> FunDecls {
+    // Compute the call graph, and order the functions so that the callees
+    // come before their callers
+    let ids: Vec<FunDeclId::Id> = defs.iter().map(|def| def.def_id).collect();
+    let mut callees: HashMap<FunDeclId::Id, Vec<FunDeclId::Id>> = HashMap::new();
+    for def in defs.iter() {
+        let mut ids = Vec::new();
+        if let Option::Some(body) = &def.body {
+            called_functions(&body.body, &mut ids);
+        }
+        callees.insert(def.def_id, ids);
+    }
+    let groups = group_declarations(&ids, &|id| callees.get(&id).unwrap().clone());
+
+    // Transform the functions bottom-up
+    let mut defs: HashMap<FunDeclId::Id, FunDecl> =
+        defs.into_iter().map(|def| (def.def_id, def)).collect();
+    let mut inlinable: HashSet<FunDeclId::Id> = HashSet::new();
+    for group in groups {
+        let is_rec = match &group {
+            GDeclarationGroup::NonRec(_) => false,
+            GDeclarationGroup::Rec(_) => true,
+        };
+        for id in group.get_ids() {
+            let def = defs.remove(&id).unwrap();
+            let def = transform_def(&defs, &inlinable, def);
+            if !is_rec && is_inlinable(&def, threshold) {
+                inlinable.insert(id);
+            }
+            defs.insert(id, def);
+        }
+    }
+
+    FunDecls::from_iter(ids.into_iter().map(|id| defs.remove(&id).unwrap()))
+}
@@ -0,0 +1,526 @@
+//! Inline the temporaries introduced by the desugaring (the locals without
+//! names) which are assigned once and used once, in the statement directly
+//! following their assignment. For instance:
+//!   ```
+//!   tmp1 := copy x + copy y;
+//!   tmp2 := move tmp1 * const 2;
+//!   z := f(move tmp2);
+//!   ```
+//! becomes:
+//!   ```
+//!   z := f(((copy x + copy y) * const 2));
+//!   ```
+//! The nested expressions are encoded with [Operand::Expr]. The code extracted
+//! for the functional backends is then much closer to the source.
+//!
+//! We only inline a temporary in the statement directly following its
+//! assignment, and only if the other operands of this statement don't use the
+//! variables read by the inlined expression: this way, we don't change the
+//! order in which the values are moved and borrowed. We don't inline the
+//! two-phase borrows, nor the reads of discriminants (the backends rely on
+//! them to reconstruct the matches).
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. It must
+//! be applied after [crate::simplify_ops] and [crate::reconstruct_try], which
+//! expect the flat MIR style, and before [crate::remove_unused_locals], which
+//! removes the temporaries we inlined (see [crate::passes::check_pass_order]).
+
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::im_ast::Var;
+use crate::llbc_ast::{FunBody, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
+use crate::values::*;
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
+
+/// The occurrences of a variable in a function body
+#[derive(Default)]
+struct Occurrences {
+    /// The assignments to the whole variable
+    assigns: usize,
+    /// The reads of the whole variable, with `move` or `copy`
+    reads: usize,
+    /// The other occurrences: borrows, reads of the discriminant, projections,
+    /// drops, etc.
+    others: usize,
+}
+
+type OccurrencesMap = HashMap<VarId::Id, Occurrences>;
+
+fn other_in_place(occs: &mut OccurrencesMap, p: &Place) {
+    occs.entry(p.var_id).or_default().others += 1;
+}
+
+fn assign_in_place(occs: &mut OccurrencesMap, p: &Place) {
+    let occ = occs.entry(p.var_id).or_default();
+    if p.projection.is_empty() {
+        occ.assigns += 1;
+    } else {
+        occ.others += 1;
+    }
+}
+
+fn occurrences_in_operand(occs: &mut OccurrencesMap, op: &Operand) {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => {
+            let occ = occs.entry(p.var_id).or_default();
+            if p.projection.is_empty() {
+                occ.reads += 1;
+            } else {
+                occ.others += 1;
+            }
+        }
+        Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => occurrences_in_rvalue(occs, rv),
+    }
+}
+
+fn occurrences_in_rvalue(occs: &mut OccurrencesMap, rv: &Rvalue) {
+    match rv {
+        Rvalue::Ref(p, _) | Rvalue::Discriminant(p) => other_in_place(occs, p),
+        Rvalue::Use(_)
+        | Rvalue::UnaryOp(_, _)
+        | Rvalue::BinaryOp(_, _, _)
+        | Rvalue::CheckedBinaryOp(_, _, _)
+        | Rvalue::Aggregate(_, _) => {
+            for op in rvalue_operands(rv) {
+                occurrences_in_operand(occs, op)
+            }
+        }
+    }
+}
+
+/// Compute the occurrences of the variables in a statement
+fn occurrences_in_statement(occs: &mut OccurrencesMap, st: &Statement) {
+    match &st.content {
+        RawStatement::Assign(p, rv) => {
+            assign_in_place(occs, p);
+            occurrences_in_rvalue(occs, rv);
+        }
+        RawStatement::FakeRead(p) | RawStatement::SetDiscriminant(p, _) | RawStatement::Drop(p) => {
+            other_in_place(occs, p)
+        }
+        RawStatement::Assert(assert) => occurrences_in_operand(occs, &assert.cond),
+        RawStatement::Call(call) => {
+            for op in &call.args {
+                occurrences_in_operand(occs, op);
+            }
+            assign_in_place(occs, &call.dest);
+        }
+        RawStatement::Try(t) => {
+            occurrences_in_operand(occs, &t.op);
+            assign_in_place(occs, &t.dest);
+            for p in &t.drops {
+                other_in_place(occs, p);
+            }
+        }
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+        RawStatement::Sequence(st1, st2) => {
+            occurrences_in_statement(occs, st1);
+            occurrences_in_statement(occs, st2);
+        }
+        RawStatement::Switch(op, targets) => {
+            occurrences_in_operand(occs, op);
+            for st in targets.get_targets() {
+                occurrences_in_statement(occs, st);
+            }
+        }
+        RawStatement::Loop(loop_) => occurrences_in_statement(occs, &loop_.body),
+    }
+}
+
+fn vars_in_operand(op: &Operand) -> HashSet<VarId::Id> {
+    let mut occs = HashMap::new();
+    occurrences_in_operand(&mut occs, op);
+    occs.into_iter().map(|(id, _)| id).collect()
+}
+
+fn vars_in_rvalue(rv: &Rvalue) -> HashSet<VarId::Id> {
+    let mut occs = HashMap::new();
+    occurrences_in_rvalue(&mut occs, rv);
+    occs.into_iter().map(|(id, _)| id).collect()
+}
+
+fn rvalue_operands(rv: &Rvalue) -> Vec<&Operand> {
+    match rv {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => vec![op],
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => vec![op1, op2],
+        Rvalue::Aggregate(_, ops) => ops.iter().collect(),
+        Rvalue::Ref(_, _) | Rvalue::Discriminant(_) => vec![],
+    }
+}
+
+fn rvalue_operands_mut(rv: &mut Rvalue) -> Vec<&mut Operand> {
+    match rv {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => vec![op],
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => vec![op1, op2],
+        Rvalue::Aggregate(_, ops) => ops.iter_mut().collect(),
+        Rvalue::Ref(_, _) | Rvalue::Discriminant(_) => vec![],
+    }
+}
+
+/// The operands in which we can inline a temporary, if the statement is of
+/// the proper shape. Note that we don't look inside the already inlined
+/// expressions.
+fn statement_operands(content: &RawStatement) -> Option<Vec<&Operand>> {
+    match content {
+        RawStatement::Assign(_, rv) => Some(rvalue_operands(rv)),
+        RawStatement::Call(call) => Some(call.args.iter().collect()),
+        RawStatement::Assert(assert) => Some(vec![&assert.cond]),
+        RawStatement::Switch(op, _) => Some(vec![op]),
+        _ => None,
+    }
+}
+
+fn statement_operands_mut(content: &mut RawStatement) -> Vec<&mut Operand> {
+    match content {
+        RawStatement::Assign(_, rv) => rvalue_operands_mut(rv),
+        RawStatement::Call(call) => call.args.iter_mut().collect(),
+        RawStatement::Assert(assert) => vec![&mut assert.cond],
+        RawStatement::Switch(op, _) => vec![op],
+        _ => unreachable!(),
+    }
+}
+
+fn can_inline_rvalue(rv: &Rvalue) -> bool {
+    match rv {
+        Rvalue::Use(_)
+        | Rvalue::UnaryOp(_, _)
+        | Rvalue::BinaryOp(_, _, _)
+        | Rvalue::CheckedBinaryOp(_, _, _)
+        | Rvalue::Aggregate(_, _) => true,
+        Rvalue::Ref(_, kind) => *kind != BorrowKind::TwoPhaseMut,
+        Rvalue::Discriminant(_) => false,
+    }
+}
+
+fn is_use_of(op: &Operand, var_id: VarId::Id) -> bool {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => p.var_id == var_id && p.projection.is_empty(),
+        Operand::Constant(_, _) | Operand::Expr(_, _) => false,
+    }
+}
+
+struct Ctx<'a> {
+    /// The temporaries which are assigned once and used once
+    temporaries: HashSet<VarId::Id>,
+    locals: &'a VarId::Vector<Var>,
+}
+
+/// Attempt to inline the temporary assigned by `prev` in `st`, which directly
+/// follows it. Return the merged statement if we succeeded, otherwise give
+/// back `st`.
+fn try_inline(
+    ctx: &Ctx,
+    prev: &Statement,
+    mut st: Statement,
+) -> std::result::Result<Statement, Statement> {
+    let (tmp, rv) = match &prev.content {
+        RawStatement::Assign(p, rv)
+            if p.projection.is_empty() && ctx.temporaries.contains(&p.var_id) =>
+        {
+            (p.var_id, rv)
+        }
+        _ => return Err(st),
+    };
+    if !can_inline_rvalue(rv) {
+        return Err(st);
+    }
+
+    // Find the operand which uses the temporary, and check that the other
+    // operands don't use the variables read by the expression we inline
+    let pos = match statement_operands(&st.content) {
+        None => return Err(st),
+        Some(ops) => {
+            let pos = match ops.iter().position(|op| is_use_of(op, tmp)) {
+                None => return Err(st),
+                Some(pos) => pos,
+            };
+            let reads = vars_in_rvalue(rv);
+            for (i, op) in ops.iter().enumerate() {
+                if i != pos && !vars_in_operand(op).is_disjoint(&reads) {
+                    return Err(st);
+                }
+            }
+            pos
+        }
+    };
+
+    // Inline
+    let inlined = match rv {
+        Rvalue::Use(op) => op.clone(),
+        _ => {
+            let ty = ctx.locals.get(tmp).unwrap().ty.clone();
+            Operand::Expr(ty, Box::new(rv.clone()))
+        }
+    };
+    *statement_operands_mut(&mut st.content).swap_remove(pos) = inlined;
+    Ok(Statement::new(prev.span.merge(&st.span), st.content))
+}
+
+fn flatten(st: Statement, out: &mut Vec<Statement>) {
+    match st.content {
+        RawStatement::Sequence(st1, st2) => {
+            flatten(*st1, out);
+            flatten(*st2, out);
+        }
+        _ => out.push(st),
+    }
+}
+
+fn transform_sub_statements(ctx: &Ctx, st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                    Box::new(transform_st(ctx, *st1)),
+                    Box::new(transform_st(ctx, *st2)),
+                ),
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets =
+                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(ctx, e))));
+                    let otherwise = transform_st(ctx, *otherwise);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(ctx, *loop_.body)),
+            ..loop_
+        }),
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+fn transform_st(ctx: &Ctx, st: Statement) -> Statement {
+    // We work on the flattened sequence of statements: we attempt to inline
+    // the previous statement in the current one. Note that the statement
+    // resulting from an inlining may itself be inlined in the next statement.
+    let mut sts = Vec::new();
+    flatten(st, &mut sts);
+    let mut out: Vec<Statement> = Vec::new();
+    for st in sts {
+        let st = transform_sub_statements(ctx, st);
+        let st = match out.last() {
+            None => st,
+            Some(prev) => match try_inline(ctx, prev, st) {
+                Ok(st) => {
+                    out.pop();
+                    st
+                }
+                Err(st) => st,
+            },
+        };
+        out.push(st);
+    }
+
+    // Rebuild the sequence
+    let mut st = out.pop().unwrap();
+    while let Some(prev) = out.pop() {
+        st = Statement::new_seq(prev, st);
+    }
+    st
+}
+
+/// Compute the temporaries which are assigned once and used once: they have
+/// exactly one assignment, and one read with `move` or `copy`, and don't
+/// appear anywhere else. The return variable and the input variables are not
+/// temporaries.
+fn compute_temporaries(body: &FunBody) -> HashSet<VarId::Id> {
+    let mut occs = HashMap::new();
+    occurrences_in_statement(&mut occs, &body.body);
+    body.locals
+        .iter()
+        .filter(|var| {
+            var.index.to_usize() > body.arg_count
+                && var.name.is_none()
+                && matches!(
+                    occs.get(&var.index),
+                    Some(Occurrences {
+                        assigns: 1,
+                        reads: 1,
+                        others: 0
+                    })
+                )
+        })
+        .map(|var| var.index)
+        .collect()
+}
+
+fn transform_body(mut body: FunBody) -> FunBody {
+    let ctx = Ctx {
+        temporaries: compute_temporaries(&body),
+        locals: &body.locals,
+    };
+    body.body = transform_st(&ctx, body.body);
+    body
+}
+
+fn transform_def(mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    def.body = def.body.map(transform_body);
+    def
+}
+
+pub fn transform(defs: FunDecls) -> FunDecls {
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::im_ast::{FunDeclId, FunId};
+    use crate::inline_temporaries::*;
+    use crate::llbc_ast::Call;
+    use crate::llbc_ast_utils::test_utils::*;
+    use crate::types::*;
+
+    /// The locals of the tests: `x` and `y` are the inputs, `z` is a named
+    /// local, and the others are temporaries
+    static LOCALS: [Option<&str>; 7] = [None, Some("x"), Some("y"), Some("z"), None, None, None];
+
+    fn transform(st: Statement) -> Statement {
+        transform_body(body(2, &LOCALS, st)).body
+    }
+
+    fn call(args: Vec<Operand>, dest: usize) -> Statement {
+        st(RawStatement::Call(Call {
+            func: FunId::Regular(FunDeclId::Id::new(0)),
+            region_args: Vec::new(),
+            type_args: Vec::new(),
+            args,
+            dest: local(dest),
+        }))
+    }
+
+    fn expr(rv: Rvalue) -> Operand {
+        Operand::Expr(Ty::Integer(IntegerTy::U32), Box::new(rv))
+    }
+
+    #[test]
+    fn test_temporaries() {
+        // Assigned once, read once
+        let b = body(
+            2,
+            &LOCALS,
+            seq(vec![
+                assign(4, add(copy(1), constant(1))),
+                assign(3, Rvalue::Use(move_(4))),
+            ]),
+        );
+        assert!(compute_temporaries(&b).contains(&VarId::Id::new(4)));
+
+        // Assigned twice, never read
+        let b = body(
+            2,
+            &LOCALS,
+            seq(vec![
+                assign(4, Rvalue::Use(copy(1))),
+                assign(4, Rvalue::Use(copy(2))),
+            ]),
+        );
+        assert!(compute_temporaries(&b).is_empty());
+
+        // Assigned once, then dropped
+        let b = body(
+            2,
+            &LOCALS,
+            seq(vec![
+                assign(4, Rvalue::Use(copy(1))),
+                st(RawStatement::Drop(local(4))),
+            ]),
+        );
+        assert!(compute_temporaries(&b).is_empty());
+
+        // Assigned once, then borrowed
+        let b = body(
+            2,
+            &LOCALS,
+            seq(vec![
+                assign(4, Rvalue::Use(copy(1))),
+                assign(5, Rvalue::Ref(local(4), BorrowKind::Shared)),
+            ]),
+        );
+        assert!(!compute_temporaries(&b).contains(&VarId::Id::new(4)));
+
+        // The named locals are not temporaries
+        let b = body(
+            2,
+            &LOCALS,
+            seq(vec![
+                assign(3, Rvalue::Use(copy(1))),
+                assign(4, Rvalue::Use(move_(3))),
+            ]),
+        );
+        assert!(!compute_temporaries(&b).contains(&VarId::Id::new(3)));
+    }
+
+    #[test]
+    fn test_inline() {
+        // tmp4 := copy x + const 1;
+        // tmp5 := move tmp4 + copy y;
+        // z := f(move tmp5)
+        let st = transform(seq(vec![
+            assign(4, add(copy(1), constant(1))),
+            assign(5, add(move_(4), copy(2))),
+            call(vec![move_(5)], 3),
+        ]));
+        match &st.content {
+            RawStatement::Call(call) => {
+                let inlined = expr(add(expr(add(copy(1), constant(1))), copy(2)));
+                assert!(call.args == vec![inlined]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_shared_variable() {
+        // The other operand reads x: we don't inline, to preserve the order of
+        // the reads
+        let st = transform(seq(vec![
+            assign(4, add(copy(1), constant(1))),
+            assign(3, add(move_(4), copy(1))),
+        ]));
+        let sts = flatten_seq(&st);
+        assert!(sts.len() == 2);
+        match &sts[1].content {
+            RawStatement::Assign(_, rv) => assert!(*rv == add(move_(4), copy(1))),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_two_phase_borrow() {
+        let st = transform(seq(vec![
+            assign(4, Rvalue::Ref(local(3), BorrowKind::TwoPhaseMut)),
+            call(vec![move_(4), copy(1)], 5),
+        ]));
+        let sts = flatten_seq(&st);
+        assert!(sts.len() == 2);
+        match &sts[1].content {
+            RawStatement::Call(call) => assert!(call.args == vec![move_(4), copy(1)]),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_discriminant() {
+        let st = transform(seq(vec![
+            assign(4, Rvalue::Discriminant(local(1))),
+            if_(4, st(RawStatement::Return), st(RawStatement::Panic)),
+        ]));
+        let sts = flatten_seq(&st);
+        assert!(sts.len() == 2);
+        match &sts[1].content {
+            RawStatement::Switch(op, _) => assert!(*op == move_(4)),
+            _ => panic!(),
+        }
+    }
+}
@@ -4,53 +4,60 @@
 //! For this reason, when the function has return type unit, we insert
 //! an extra assignment just before returning.
 use crate::expressions::*;
-use crate::llbc_ast::{FunDecl, FunDecls, Statement, SwitchTargets};
+use crate::llbc_ast::{FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
 use crate::values::*;
 use std::iter::FromIterator;
 
 fn transform_st(st: Statement) -> Statement {
-    match st {
-        Statement::Return => {
+    let span = st.span;
+    let content = match st.content {
+        RawStatement::Return => {
             // The interesting case
             let ret_place = Place {
                 var_id: VarId::Id::new(0),
                 projection: Projection::new(),
             };
             let unit_value = Rvalue::Aggregate(AggregateKind::Tuple, Vec::new());
-            let assign_st = Statement::Assign(ret_place, unit_value);
-            let ret_st = Statement::Return;
-            Statement::Sequence(Box::new(assign_st), Box::new(ret_st))
+            let assign_st = Statement::new(span, RawStatement::Assign(ret_place, unit_value));
+            let ret_st = Statement::new(span, RawStatement::Return);
+            return Statement::new_seq(assign_st, ret_st);
         }
-        Statement::Assign(p, rv) => Statement::Assign(p, rv),
-        Statement::FakeRead(p) => Statement::FakeRead(p),
-        Statement::SetDiscriminant(p, vid) => Statement::SetDiscriminant(p, vid),
-        Statement::Drop(p) => Statement::Drop(p),
-        Statement::Assert(assert) => Statement::Assert(assert),
-        Statement::Call(call) => Statement::Call(call),
-        Statement::Panic => Statement::Panic,
-        Statement::Break(i) => Statement::Break(i),
-        Statement::Continue(i) => Statement::Continue(i),
-        Statement::Nop => Statement::Nop,
-        Statement::Switch(op, targets) => match targets {
+        RawStatement::Assign(p, rv) => RawStatement::Assign(p, rv),
+        RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
+        RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
+        RawStatement::Drop(p) => RawStatement::Drop(p),
+        RawStatement::Assert(assert) => RawStatement::Assert(assert),
+        RawStatement::Call(call) => RawStatement::Call(call),
+        RawStatement::Try(t) => RawStatement::Try(t),
+        RawStatement::Panic => RawStatement::Panic,
+        RawStatement::Break(i) => RawStatement::Break(i),
+        RawStatement::Continue(i) => RawStatement::Continue(i),
+        RawStatement::Nop => RawStatement::Nop,
+        RawStatement::Switch(op, targets) => match targets {
             SwitchTargets::If(st1, st2) => {
                 let st1 = Box::new(transform_st(*st1));
                 let st2 = Box::new(transform_st(*st2));
-                Statement::Switch(op, SwitchTargets::If(st1, st2))
+                RawStatement::Switch(op, SwitchTargets::If(st1, st2))
             }
             SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
                 let targets =
                     Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(e))));
                 let otherwise = transform_st(*otherwise);
                 let targets = SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise));
-                Statement::Switch(op, targets)
+                RawStatement::Switch(op, targets)
             }
         },
-        Statement::Loop(loop_body) => Statement::Loop(Box::new(transform_st(*loop_body))),
-        Statement::Sequence(st1, st2) => {
-            Statement::Sequence(Box::new(transform_st(*st1)), Box::new(transform_st(*st2)))
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(*loop_.body)),
+            ..loop_
+        }),
+        RawStatement::Sequence(st1, st2) => {
+            return Statement::new_seq(transform_st(*st1), transform_st(*st2));
         }
-    }
+    };
+    Statement::new(span, content)
 }
+
 fn transform_def(mut def: FunDecl) -> FunDecl {
     trace!("About to update: {}", def.name);
     // If the return type is unit: apply the transformation
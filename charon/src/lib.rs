@@ -0,0 +1,83 @@
+//! Charon: translate Rust crates to LLBC (Low-Level Borrow Calculus).
+//!
+//! The entry point of the library is [driver::translate_crate], which calls
+//! the Rust compiler on a crate and returns the translated declarations.
+#![feature(rustc_private, register_tool)]
+#![feature(box_syntax, box_patterns)]
+#![feature(cell_leak)] // For Ref::leak
+// For rustdoc: prevents overflows
+#![recursion_limit = "256"]
+
+extern crate hashlink;
+extern crate im;
+extern crate linked_hash_set;
+extern crate log;
+extern crate rustc_ast;
+extern crate rustc_ast_pretty;
+extern crate rustc_borrowck;
+extern crate rustc_const_eval;
+extern crate rustc_driver;
+extern crate rustc_errors;
+extern crate rustc_hir;
+extern crate rustc_index;
+extern crate rustc_interface;
+extern crate rustc_middle;
+extern crate rustc_mir_dataflow;
+extern crate rustc_mir_transform;
+extern crate rustc_monomorphize;
+extern crate rustc_resolve;
+extern crate rustc_session;
+extern crate rustc_span;
+extern crate rustc_target;
+
+#[macro_use]
+pub mod common;
+pub mod assumed;
+pub mod cleanup;
+pub mod config;
+pub mod dataflow;
+pub mod divergent;
+pub mod driver;
+pub mod expressions;
+pub mod expressions_utils;
+pub mod formatter;
+pub mod generics;
+pub mod get_mir;
+pub mod graphs;
+pub mod id_vector;
+pub mod im_ast;
+pub mod im_ast_utils;
+pub mod im_to_llbc;
+pub mod inline;
+pub mod inline_temporaries;
+pub mod insert_assign_return_unit;
+pub mod llbc_ast;
+pub mod llbc_ast_utils;
+pub mod llbc_diff;
+pub mod llbc_export;
+pub mod llbc_import;
+pub mod loop_analysis;
+mod manifest;
+pub mod meta;
+pub mod monomorphize;
+pub mod names;
+pub mod names_utils;
+pub mod opacity;
+pub mod passes;
+pub mod reconstruct_asserts;
+pub mod reconstruct_try;
+pub mod regions_hierarchy;
+pub mod register;
+pub mod remove_unused_locals;
+pub mod reorder_decls;
+pub mod rust_to_local_ids;
+pub mod simplify_ops;
+pub mod ssa;
+pub mod stable_ids;
+mod toolchain;
+pub mod translate_functions_to_im;
+pub mod translate_types;
+pub mod types;
+pub mod types_utils;
+pub mod values;
+pub mod values_utils;
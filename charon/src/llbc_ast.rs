@@ -10,18 +10,19 @@
 use crate::expressions::*;
 use crate::im_ast::*;
 pub use crate::llbc_ast_utils::*;
+use crate::meta::Span;
 use crate::types::*;
 use crate::values::*;
 use macros::{EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Assert {
     pub cond: Operand,
     pub expected: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Call {
     pub func: FunId,
     /// Technically this is useless, but we still keep it because we might
@@ -34,14 +35,91 @@ pub struct Call {
     pub dest: Place,
 }
 
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize)]
-pub enum Statement {
+/// The `?` operator, reconstructed from its desugaring (see
+/// [crate::reconstruct_try]): `dest := op?`.
+///
+/// If `op` (a `Result` or an `Option`) evaluates to an error (`Err(e)` or
+/// `None`), the function returns early: the returned value is computed from the
+/// error with `FromResidual::from_residual`, which converts `e` with `From::from`
+/// in the case of `Result`, then the places in `drops` are dropped. Otherwise,
+/// `dest` receives the content of the `Ok` (or the `Some`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Try {
+    pub op: Operand,
+    pub dest: Place,
+    /// The places dropped before returning early, in this order
+    pub drops: Vec<Place>,
+}
+
+/// A loop. The loop-carried variables are computed by the [crate::ssa] pass:
+/// if this pass is not applied, `inputs` and `outputs` are empty.
+///
+/// The loop can be seen as a recursive function, which takes the `inputs` as
+/// parameters and returns the `outputs`: the `inputs` are assigned before
+/// entering the loop and before every `continue` to the loop, and the `outputs`
+/// are assigned on every path leading to a `break` out of the loop.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Loop {
+    pub body: Box<Statement>,
+    /// The variables carried from one iteration to the next
+    pub inputs: Vec<VarId::Id>,
+    /// The variables defined by the loop, and initialized after it
+    pub outputs: Vec<VarId::Id>,
+    /// Computed by the [crate::loop_analysis] pass
+    pub info: Option<LoopInfo>,
+}
+
+/// What a loop body accesses, and what is live after the loop. The locals are
+/// sorted, and the places are listed in the order in which they appear.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoopInfo {
+    /// The locals read in the loop body
+    pub reads: Vec<VarId::Id>,
+    /// The locals assigned (fully or partially, but not through a reference)
+    /// in the loop body
+    pub modifies: Vec<VarId::Id>,
+    /// The places borrowed in the loop body
+    pub borrows: Vec<Place>,
+    /// The places live when exiting the loop, at the target of the `break`s
+    pub live_at_break: Vec<Place>,
+}
+
+/// A statement, together with the span of the source code it comes from.
+/// The span of a sequence covers the spans of the sequenced statements.
+///
+/// We implement the serializer by hand, to omit the missing `dataflow` field
+/// (see the implementation in [crate::llbc_ast_utils]).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Statement {
+    pub span: Span,
+    pub content: RawStatement,
+    /// Computed by the [crate::dataflow] pass. The sequences are not annotated.
+    #[serde(default)]
+    pub dataflow: Option<DataflowInfo>,
+}
+
+/// The results of the dataflow analyses after a statement. The locals are
+/// sorted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataflowInfo {
+    /// The locals which may be read later
+    pub live: Vec<VarId::Id>,
+    /// The locals which may be initialized
+    pub maybe_init: Vec<VarId::Id>,
+    /// The locals which may have been (partially) moved
+    pub maybe_moved: Vec<VarId::Id>,
+}
+
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
+pub enum RawStatement {
     Assign(Place, Rvalue),
     FakeRead(Place),
     SetDiscriminant(Place, VariantId::Id),
     Drop(Place),
     Assert(Assert),
     Call(Call),
+    /// `dest := op?`
+    Try(Try),
     /// Panic also handles "unreachable"
     Panic,
     Return,
@@ -61,10 +139,12 @@ pub enum Statement {
     Nop,
     Sequence(Box<Statement>, Box<Statement>),
     Switch(Operand, SwitchTargets),
-    Loop(Box<Statement>),
+    Loop(Loop),
 }
 
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, VariantIndexArity)]
+/// Note that the serialized form of the switch targets is the same as the one
+/// generated by `derive(Serialize)`, so we can derive the deserializer.
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, VariantIndexArity, Deserialize)]
 pub enum SwitchTargets {
     /// Gives the `if` block and the `else` block
     If(Box<Statement>, Box<Statement>),
@@ -4,10 +4,11 @@
 use crate::common::*;
 use crate::formatter::Formatter;
 use crate::im_ast::{fmt_call, FunDeclId, FunSigFormatter, GAstFormatter, TAB_INCR};
-use crate::llbc_ast::{Call, FunDecl, FunDecls, Statement, SwitchTargets};
+use crate::llbc_ast::{Call, FunDecl, FunDecls, RawStatement, Statement, SwitchTargets};
+use crate::meta::Span;
 use crate::types::*;
 use crate::values::*;
-use serde::ser::SerializeTupleVariant;
+use serde::ser::{SerializeStruct, SerializeTupleVariant};
 use serde::{Serialize, Serializer};
 
 impl SwitchTargets {
@@ -25,6 +26,21 @@ impl SwitchTargets {
             }
         }
     }
+
+    pub fn get_targets_mut(&mut self) -> Vec<&mut Statement> {
+        match self {
+            SwitchTargets::If(exp1, exp2) => {
+                vec![exp1, exp2]
+            }
+            SwitchTargets::SwitchInt(_, targets, otherwise) => {
+                let mut out: Vec<&mut Statement> = vec![otherwise];
+                for (_, tgt) in targets {
+                    out.push(tgt);
+                }
+                out
+            }
+        }
+    }
 }
 
 impl Serialize for SwitchTargets {
@@ -61,7 +77,45 @@ impl Serialize for SwitchTargets {
     }
 }
 
+impl Serialize for Statement {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Most statements are not annotated by the dataflow pass: we omit the
+        // `dataflow` field when it is `None`. Bincode is not self-describing,
+        // and can't deserialize a struct with a missing field: we only omit
+        // it in the human-readable formats (JSON).
+        let skip_dataflow = self.dataflow.is_none() && serializer.is_human_readable();
+        let len = if skip_dataflow { 2 } else { 3 };
+        let mut s = serializer.serialize_struct("Statement", len)?;
+        s.serialize_field("span", &self.span)?;
+        s.serialize_field("content", &self.content)?;
+        if skip_dataflow {
+            s.skip_field("dataflow")?;
+        } else {
+            s.serialize_field("dataflow", &self.dataflow)?;
+        }
+        s.end()
+    }
+}
+
 impl Statement {
+    pub fn new(span: Span, content: RawStatement) -> Self {
+        Statement {
+            span,
+            content,
+            dataflow: Option::None,
+        }
+    }
+
+    /// Sequence two statements. The span of the sequence covers the spans of
+    /// both statements.
+    pub fn new_seq(st1: Statement, st2: Statement) -> Self {
+        let span = st1.span.merge(&st2.span);
+        Statement::new(span, RawStatement::Sequence(Box::new(st1), Box::new(st2)))
+    }
+
     pub fn fmt_with_ctx<'a, 'b, 'c, T>(&'a self, tab: &'b str, ctx: &'c T) -> String
     where
         T: Formatter<VarId::Id>
@@ -72,35 +126,35 @@ impl Statement {
             + Formatter<(TypeDeclId::Id, VariantId::Id)>
             + Formatter<(TypeDeclId::Id, Option<VariantId::Id>, FieldId::Id)>,
     {
-        match self {
-            Statement::Assign(place, rvalue) => format!(
+        match &self.content {
+            RawStatement::Assign(place, rvalue) => format!(
                 "{}{} := {}",
                 tab,
                 place.fmt_with_ctx(ctx),
                 rvalue.fmt_with_ctx(ctx),
             )
             .to_owned(),
-            Statement::FakeRead(place) => {
+            RawStatement::FakeRead(place) => {
                 format!("{}@fake_read({})", tab, place.fmt_with_ctx(ctx),).to_owned()
             }
-            Statement::SetDiscriminant(place, variant_id) => format!(
+            RawStatement::SetDiscriminant(place, variant_id) => format!(
                 "{}@discriminant({}) := {}",
                 tab,
                 place.fmt_with_ctx(ctx),
                 variant_id.to_string()
             )
             .to_owned(),
-            Statement::Drop(place) => {
+            RawStatement::Drop(place) => {
                 format!("{}drop {}", tab, place.fmt_with_ctx(ctx),).to_owned()
             }
-            Statement::Assert(assert) => format!(
+            RawStatement::Assert(assert) => format!(
                 "{}assert({} == {})",
                 tab,
                 assert.cond.fmt_with_ctx(ctx),
                 assert.expected,
             )
             .to_owned(),
-            Statement::Call(call) => {
+            RawStatement::Call(call) => {
                 let Call {
                     func,
                     region_args,
@@ -111,18 +165,34 @@ impl Statement {
                 let call = fmt_call(ctx, func, region_args, type_args, args);
                 format!("{}{} := {}", tab, dest.fmt_with_ctx(ctx), call).to_owned()
             }
-            Statement::Panic => format!("{}panic", tab).to_owned(),
-            Statement::Return => format!("{}return", tab).to_owned(),
-            Statement::Break(index) => format!("{}break {}", tab, index).to_owned(),
-            Statement::Continue(index) => format!("{}continue {}", tab, index).to_owned(),
-            Statement::Nop => format!("{}nop", tab).to_owned(),
-            Statement::Sequence(st1, st2) => format!(
+            RawStatement::Try(t) => {
+                let drops = if t.drops.is_empty() {
+                    "".to_string()
+                } else {
+                    let drops: Vec<String> = t.drops.iter().map(|p| p.fmt_with_ctx(ctx)).collect();
+                    format!(" (drop {})", drops.join(", "))
+                };
+                format!(
+                    "{}{} := {}?{}",
+                    tab,
+                    t.dest.fmt_with_ctx(ctx),
+                    t.op.fmt_with_ctx(ctx),
+                    drops
+                )
+                .to_owned()
+            }
+            RawStatement::Panic => format!("{}panic", tab).to_owned(),
+            RawStatement::Return => format!("{}return", tab).to_owned(),
+            RawStatement::Break(index) => format!("{}break {}", tab, index).to_owned(),
+            RawStatement::Continue(index) => format!("{}continue {}", tab, index).to_owned(),
+            RawStatement::Nop => format!("{}nop", tab).to_owned(),
+            RawStatement::Sequence(st1, st2) => format!(
                 "{}\n{}",
                 st1.fmt_with_ctx(tab, ctx),
                 st2.fmt_with_ctx(tab, ctx)
             )
             .to_owned(),
-            Statement::Switch(discr, targets) => match targets {
+            RawStatement::Switch(discr, targets) => match targets {
                 SwitchTargets::If(true_st, false_st) => {
                     let inner_tab = format!("{}{}", tab, TAB_INCR);
                     format!(
@@ -176,12 +246,28 @@ impl Statement {
                     .to_owned()
                 }
             },
-            Statement::Loop(body) => {
+            RawStatement::Loop(loop_) => {
                 let inner_tab = format!("{}{}", tab, TAB_INCR);
+                // The loop-carried variables, if they were computed
+                let vars = if loop_.inputs.is_empty() && loop_.outputs.is_empty() {
+                    "".to_string()
+                } else {
+                    let fmt_vars = |vars: &Vec<VarId::Id>| {
+                        let vars: Vec<String> =
+                            vars.iter().map(|id| ctx.format_object(*id)).collect();
+                        vars.join(", ")
+                    };
+                    format!(
+                        "({}) -> ({}) ",
+                        fmt_vars(&loop_.inputs),
+                        fmt_vars(&loop_.outputs)
+                    )
+                };
                 format!(
-                    "{}loop {{\n{}\n{}}}",
+                    "{}loop {}{{\n{}\n{}}}",
                     tab,
-                    body.fmt_with_ctx(&inner_tab, ctx),
+                    vars,
+                    loop_.body.fmt_with_ctx(&inner_tab, ctx),
                     tab
                 )
                 .to_owned()
@@ -227,3 +313,117 @@ impl FunDecl {
         self.gfmt_with_ctx("", &fun_sig_ctx, &eval_ctx)
     }
 }
+
+/// Helpers to build LLBC bodies in the unit tests of the micro-passes. All the
+/// locals have type `u32`, and all the statements have the same dummy span.
+#[cfg(test)]
+pub mod test_utils {
+    use crate::expressions::*;
+    use crate::im_ast::Var;
+    use crate::llbc_ast::{FunBody, Loop, RawStatement, Statement, SwitchTargets};
+    use crate::meta::{FileId, Loc, Span};
+    use crate::types::*;
+    use crate::values::*;
+
+    pub fn span() -> Span {
+        let loc = Loc { line: 0, col: 0 };
+        Span {
+            file: FileId::Id::new(0),
+            beg: loc,
+            end: loc,
+        }
+    }
+
+    /// A function body. The locals are given by their names (`None` for the
+    /// temporaries), the first one being the return value.
+    pub fn body(arg_count: usize, names: &[Option<&str>], body: Statement) -> FunBody {
+        let locals = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Var {
+                index: VarId::Id::new(i),
+                name: name.map(|s| s.to_string()),
+                ty: Ty::Integer(IntegerTy::U32),
+                span: span(),
+            })
+            .collect();
+        FunBody {
+            arg_count,
+            locals,
+            body,
+        }
+    }
+
+    pub fn local(i: usize) -> Place {
+        Place {
+            var_id: VarId::Id::new(i),
+            projection: Projection::new(),
+        }
+    }
+
+    pub fn copy(i: usize) -> Operand {
+        Operand::Copy(local(i))
+    }
+
+    pub fn move_(i: usize) -> Operand {
+        Operand::Move(local(i))
+    }
+
+    pub fn constant(n: u32) -> Operand {
+        Operand::Constant(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::ConstantValue(ConstantValue::Scalar(ScalarValue::U32(n))),
+        )
+    }
+
+    pub fn st(content: RawStatement) -> Statement {
+        Statement::new(span(), content)
+    }
+
+    pub fn assign(i: usize, rv: Rvalue) -> Statement {
+        st(RawStatement::Assign(local(i), rv))
+    }
+
+    pub fn add(op1: Operand, op2: Operand) -> Rvalue {
+        Rvalue::BinaryOp(BinOp::Add, op1, op2)
+    }
+
+    /// Sequence statements
+    pub fn seq(sts: Vec<Statement>) -> Statement {
+        let mut sts = sts;
+        let mut st = sts.pop().unwrap();
+        while let Option::Some(prev) = sts.pop() {
+            st = Statement::new_seq(prev, st);
+        }
+        st
+    }
+
+    /// `if move cond { st1 } else { st2 }`
+    pub fn if_(cond: usize, st1: Statement, st2: Statement) -> Statement {
+        st(RawStatement::Switch(
+            move_(cond),
+            SwitchTargets::If(Box::new(st1), Box::new(st2)),
+        ))
+    }
+
+    pub fn loop_(body: Statement) -> Statement {
+        st(RawStatement::Loop(Loop {
+            body: Box::new(body),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            info: Option::None,
+        }))
+    }
+
+    /// Flatten a sequence of statements
+    pub fn flatten_seq(st: &Statement) -> Vec<&Statement> {
+        match &st.content {
+            RawStatement::Sequence(st1, st2) => {
+                let mut sts = flatten_seq(st1);
+                sts.extend(flatten_seq(st2));
+                sts
+            }
+            _ => vec![st],
+        }
+    }
+}
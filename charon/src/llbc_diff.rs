@@ -0,0 +1,339 @@
+//! Compare two extractions of a crate (see the `charon-diff` binary).
+//!
+//! The ids of the declarations change whenever declarations are added or
+//! removed, so we can't compare the files generated by Charon textually. We
+//! rather match the declarations by name, and compare their pretty-printed
+//! versions (in which the ids are replaced with names): for every function, we
+//! compare the signatures and do a line diff of the bodies, where every line is
+//! a statement. Note that we ignore the spans: moving a declaration doesn't
+//! change it.
+use crate::driver::TranslatedCrate;
+use crate::llbc_ast::*;
+use crate::types::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeclKind {
+    Type,
+    Fun,
+}
+
+/// A line of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A group of changed lines, with some unchanged lines around them for the
+/// context. The line numbers start at 1.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DeclChange {
+    Added,
+    Removed,
+    /// The declaration is present in both extractions, but changed.
+    /// For the functions, we give the old and the new signatures (if they
+    /// changed) and the diff of the bodies. For the types, the hunks give the
+    /// diff of the whole declarations.
+    Changed {
+        signature: Option<(String, String)>,
+        hunks: Vec<Hunk>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct DeclDiff {
+    pub kind: DeclKind,
+    pub name: String,
+    pub change: DeclChange,
+}
+
+/// Compute a line diff, by computing the longest common subsequence of the
+/// two sequences of lines.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    // lcs[i][j] is the length of the longest common subsequence of old[i..]
+    // and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Group the changed lines of a diff in hunks, keeping `context` unchanged
+/// lines before and after every change.
+fn compute_hunks(lines: Vec<DiffLine>, context: usize) -> Vec<Hunk> {
+    // Compute the ranges of lines to display, merging the overlapping ones
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let DiffLine::Same(_) = line {
+            continue;
+        }
+        let beg = i.saturating_sub(context);
+        let end = std::cmp::min(lines.len(), i + context + 1);
+        match ranges.last_mut() {
+            Some((_, last_end)) if *last_end >= beg => *last_end = end,
+            _ => ranges.push((beg, end)),
+        }
+    }
+
+    // The line numbers in the old and new sequences, before every diff line
+    let mut old_line = Vec::with_capacity(lines.len());
+    let mut new_line = Vec::with_capacity(lines.len());
+    let (mut old_count, mut new_count) = (0, 0);
+    for line in &lines {
+        old_line.push(old_count);
+        new_line.push(new_count);
+        match line {
+            DiffLine::Same(_) => {
+                old_count += 1;
+                new_count += 1;
+            }
+            DiffLine::Removed(_) => old_count += 1,
+            DiffLine::Added(_) => new_count += 1,
+        }
+    }
+    old_line.push(old_count);
+    new_line.push(new_count);
+
+    ranges
+        .into_iter()
+        .map(|(beg, end)| Hunk {
+            old_start: old_line[beg] + 1,
+            old_len: old_line[end] - old_line[beg],
+            new_start: new_line[beg] + 1,
+            new_len: new_line[end] - new_line[beg],
+            lines: lines[beg..end].to_vec(),
+        })
+        .collect()
+}
+
+fn diff_texts(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    compute_hunks(diff_lines(&old, &new), context)
+}
+
+/// Pretty-print the signature of a function, and its body (if it has one).
+fn fmt_fun_decl(decl: &FunDecl, krate: &TranslatedCrate) -> (String, String) {
+    let sig = FunDecl {
+        def_id: decl.def_id,
+        name: decl.name.clone(),
+        span: decl.span,
+        attributes: Vec::new(),
+        signature: decl.signature.clone(),
+        body: None,
+    };
+    let sig = sig.fmt_with_defs(&krate.type_defs, &krate.fun_defs);
+
+    // The pretty-printed declaration is of the shape: `fn f(...) {\n BODY \n}`.
+    // We only keep the body.
+    let body = match &decl.body {
+        None => "".to_string(),
+        Some(_) => {
+            let decl = decl.fmt_with_defs(&krate.type_defs, &krate.fun_defs);
+            let lines: Vec<&str> = decl.lines().collect();
+            lines[1..lines.len() - 1].join("\n")
+        }
+    };
+    (sig, body)
+}
+
+/// Compare the declarations of two extractions of a crate. The declarations
+/// are matched by name. We return the types first, then the functions, each
+/// sorted by name.
+pub fn diff_crates(old: &TranslatedCrate, new: &TranslatedCrate, context: usize) -> Vec<DeclDiff> {
+    let mut diffs = Vec::new();
+
+    // The types
+    let old_types: BTreeMap<String, &TypeDecl> = old
+        .type_defs
+        .types
+        .iter()
+        .map(|d| (d.name.to_string(), d))
+        .collect();
+    let new_types: BTreeMap<String, &TypeDecl> = new
+        .type_defs
+        .types
+        .iter()
+        .map(|d| (d.name.to_string(), d))
+        .collect();
+    let mut names: Vec<&String> = old_types.keys().chain(new_types.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let change = match (old_types.get(name), new_types.get(name)) {
+            (None, None) => unreachable!(),
+            (None, Some(_)) => DeclChange::Added,
+            (Some(_), None) => DeclChange::Removed,
+            (Some(old_decl), Some(new_decl)) => {
+                let old_decl = old_decl.fmt_with_defs(&old.type_defs);
+                let new_decl = new_decl.fmt_with_defs(&new.type_defs);
+                if old_decl == new_decl {
+                    continue;
+                }
+                DeclChange::Changed {
+                    signature: None,
+                    hunks: diff_texts(&old_decl, &new_decl, context),
+                }
+            }
+        };
+        diffs.push(DeclDiff {
+            kind: DeclKind::Type,
+            name: name.clone(),
+            change,
+        });
+    }
+
+    // The functions
+    let old_funs: BTreeMap<String, &FunDecl> = old
+        .fun_defs
+        .iter()
+        .map(|d| (d.name.to_string(), d))
+        .collect();
+    let new_funs: BTreeMap<String, &FunDecl> = new
+        .fun_defs
+        .iter()
+        .map(|d| (d.name.to_string(), d))
+        .collect();
+    let mut names: Vec<&String> = old_funs.keys().chain(new_funs.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let change = match (old_funs.get(name), new_funs.get(name)) {
+            (None, None) => unreachable!(),
+            (None, Some(_)) => DeclChange::Added,
+            (Some(_), None) => DeclChange::Removed,
+            (Some(old_decl), Some(new_decl)) => {
+                let (old_sig, old_body) = fmt_fun_decl(old_decl, old);
+                let (new_sig, new_body) = fmt_fun_decl(new_decl, new);
+                if old_sig == new_sig && old_body == new_body {
+                    continue;
+                }
+                let signature = if old_sig == new_sig {
+                    None
+                } else {
+                    Some((old_sig, new_sig))
+                };
+                DeclChange::Changed {
+                    signature,
+                    hunks: diff_texts(&old_body, &new_body, context),
+                }
+            }
+        };
+        diffs.push(DeclDiff {
+            kind: DeclKind::Fun,
+            name: name.clone(),
+            change,
+        });
+    }
+
+    diffs
+}
+
+impl std::fmt::Display for DeclKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeclKind::Type => write!(f, "type"),
+            DeclKind::Fun => write!(f, "fn"),
+        }
+    }
+}
+
+impl std::fmt::Display for Hunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )?;
+        for line in &self.lines {
+            match line {
+                DiffLine::Same(l) => writeln!(f, " {}", l)?,
+                DiffLine::Removed(l) => writeln!(f, "-{}", l)?,
+                DiffLine::Added(l) => writeln!(f, "+{}", l)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for DeclDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.change {
+            DeclChange::Added => writeln!(f, "+ {} {}", self.kind, self.name),
+            DeclChange::Removed => writeln!(f, "- {} {}", self.kind, self.name),
+            DeclChange::Changed { signature, hunks } => {
+                writeln!(f, "~ {} {}", self.kind, self.name)?;
+                if let Some((old_sig, new_sig)) = signature {
+                    writeln!(f, "signature:\n-{}\n+{}", old_sig, new_sig)?;
+                }
+                if !hunks.is_empty() && self.kind == DeclKind::Fun {
+                    writeln!(f, "body:")?;
+                }
+                for hunk in hunks {
+                    write!(f, "{}", hunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let new = ["a", "b", "x", "d", "e", "f", "g", "h", "i"];
+        let hunks = compute_hunks(diff_lines(&old, &new), 1);
+        assert!(hunks.len() == 2);
+        assert!((hunks[0].old_start, hunks[0].old_len) == (2, 3));
+        assert!((hunks[0].new_start, hunks[0].new_len) == (2, 3));
+        assert!(
+            hunks[0].lines
+                == vec![
+                    DiffLine::Same("b".to_string()),
+                    DiffLine::Removed("c".to_string()),
+                    DiffLine::Added("x".to_string()),
+                    DiffLine::Same("d".to_string()),
+                ]
+        );
+        assert!((hunks[1].old_start, hunks[1].old_len) == (8, 1));
+        assert!((hunks[1].new_start, hunks[1].new_len) == (8, 2));
+    }
+}
@@ -1,11 +1,20 @@
-use crate::llbc_ast::*;
 use crate::common::*;
+use crate::driver::TransOptions;
+use crate::id_vector::ToUsize;
 use crate::im_ast::FunDeclId;
+use crate::llbc_ast::*;
+use crate::meta::FileId;
 use crate::rust_to_local_ids::*;
+use crate::stable_ids::{relabel_with_stable_ids, IdMap};
+use crate::toolchain;
 use crate::types::*;
-use serde::{Serialize, Serializer};
+use hashlink::linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
 use std::fs::File;
+use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 /// Serialization wrapper for vectors
 pub struct VecSW<'a, T> {
@@ -30,30 +39,210 @@ impl<'a, T: Serialize> Serialize for VecSW<'a, T> {
 /// An auxiliary type used for serialization of declaration groups
 type DeclarationsSerializer<'a> = VecSW<'a, DeclarationGroup>;
 
+/// Serialization wrapper for the type and function declarations: we serialize
+/// them as maps from their ids to the declarations. The positions of the
+/// declarations are not their ids with `--stable-ids`, nor in the module files
+/// of `--split-modules`: this way, the consumers can't rely on them.
+pub struct DeclMapSW<'a, Id, T> {
+    pub decls: Vec<(Id, &'a T)>,
+}
+
+impl<'a, Id: Serialize, T: Serialize> Serialize for DeclMapSW<'a, Id, T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.decls.iter().map(|(id, decl)| (id, decl)))
+    }
+}
+
+impl<'a> DeclMapSW<'a, TypeDeclId::Id, TypeDecl> {
+    pub fn types(types: &'a TypeDeclId::Vector<TypeDecl>) -> Self {
+        DeclMapSW {
+            decls: types.iter().map(|d| (d.def_id, d)).collect(),
+        }
+    }
+}
+
+impl<'a> DeclMapSW<'a, FunDeclId::Id, FunDecl> {
+    pub fn functions(functions: &'a FunDeclId::Vector<FunDecl>) -> Self {
+        DeclMapSW {
+            decls: functions.iter().map(|d| (d.def_id, d)).collect(),
+        }
+    }
+}
+
+/// The version of the format of the generated files.
+///
+/// This must be incremented whenever the serialization of the AST changes,
+/// and the JSON Schema describing the format (`llbc.schema.json`, at the root
+/// of the `charon` directory) must be updated accordingly.
+pub const SCHEMA_VERSION: u32 = 17;
+
+/// The magic number which starts the files generated in a binary format (the
+/// JSON files don't have one). It is followed by a byte identifying the format
+/// (see [OutputFormat::tag]) then by the schema version, as a little-endian u32.
+pub static BINARY_MAGIC: &[u8; 4] = b"LLBC";
+/// The size of the header of the binary files: magic, format tag and version
+pub const BINARY_HEADER_LEN: usize = 9;
+
+/// The format of the generated files. All the formats use the same [Serialize]
+/// implementations, and can be read back with [crate::llbc_import::load_llbc].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    Cbor,
+    Bincode,
+}
+
+impl OutputFormat {
+    /// The extension of the generated files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "llbc",
+            OutputFormat::Cbor => "llbc.cbor",
+            OutputFormat::Bincode => "llbc.bincode",
+        }
+    }
+
+    /// The byte identifying the format in the header of the binary files
+    pub fn tag(&self) -> u8 {
+        match self {
+            OutputFormat::Json => 0,
+            OutputFormat::Cbor => 1,
+            OutputFormat::Bincode => 2,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<OutputFormat> {
+        match tag {
+            0 => Some(OutputFormat::Json),
+            1 => Some(OutputFormat::Cbor),
+            2 => Some(OutputFormat::Bincode),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<OutputFormat, String> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "cbor" => Ok(OutputFormat::Cbor),
+            "bincode" => Ok(OutputFormat::Bincode),
+            _ => Err(format!(
+                "Unknown format: {:?} (expected one of: json, cbor, bincode)",
+                s
+            )),
+        }
+    }
+}
+
+/// The extension of the files generated by `--emit pretty`
+pub static PRETTY_EXTENSION: &str = "llbc.txt";
+
+/// What Charon generates: the .llbc files (in the format given by
+/// [OutputFormat]) and/or a human-readable rendering of the translated
+/// crate (see [export_pretty]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmitKind {
+    Llbc,
+    Pretty,
+}
+
+impl FromStr for EmitKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<EmitKind, String> {
+        match s {
+            "llbc" => Ok(EmitKind::Llbc),
+            "pretty" => Ok(EmitKind::Pretty),
+            _ => Err(format!(
+                "Unknown output: {:?} (expected one of: llbc, pretty)",
+                s
+            )),
+        }
+    }
+}
+
+/// The header of the generated files: it allows the consumers to check that
+/// they can read a file, and records how the file was generated.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Header {
+    pub schema_version: u32,
+    pub charon_version: String,
+    /// The version of the compiler Charon is linked with
+    pub rustc_version: String,
+    /// The toolchain Charon is pinned to
+    pub toolchain: String,
+    /// Note that we don't record the extraction date: two extractions of the
+    /// same crate with the same options give the same file.
+    pub options: TransOptions,
+}
+
+impl Header {
+    fn new(options: &TransOptions) -> Self {
+        Header {
+            schema_version: SCHEMA_VERSION,
+            charon_version: env!("CARGO_PKG_VERSION").to_string(),
+            rustc_version: toolchain::charon_rustc_version(),
+            toolchain: toolchain::pinned_toolchain(),
+            options: options.clone(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename = "Module")]
 struct ModSerializer<'a> {
+    header: Header,
     name: String,
+    /// The files referenced by the spans
+    files: &'a FileId::Vector<String>,
     declarations: DeclarationsSerializer<'a>,
-    types: &'a TypeDeclId::Vector<TypeDecl>,
-    functions: &'a FunDeclId::Vector<FunDecl>,
+    types: DeclMapSW<'a, TypeDeclId::Id, TypeDecl>,
+    functions: DeclMapSW<'a, FunDeclId::Id, FunDecl>,
 }
 
-/// Export the translated definitions to a JSON file.
-pub fn export(
-    name: String,
-    ordered_decls: &OrderedDecls,
-    type_defs: &TypeDecls,
-    fun_defs: &FunDecls,
-    dest_dir: &Option<PathBuf>,
-    sourcefile: &PathBuf,
-) -> Result<()> {
-    // Generate the destination file
-    let target_filename = match dest_dir {
+/// Serialize a module (or an index, see [export_split]) to a writer, in the
+/// given format
+fn write_module<W: Write, T: Serialize>(
+    mut out: W,
+    format: OutputFormat,
+    module: &T,
+) -> std::result::Result<(), String> {
+    if format != OutputFormat::Json {
+        out.write_all(BINARY_MAGIC)
+            .and_then(|_| out.write_all(&[format.tag()]))
+            .and_then(|_| out.write_all(&SCHEMA_VERSION.to_le_bytes()))
+            .map_err(|err| err.to_string())?;
+    }
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, module).map_err(|err| err.to_string())
+        }
+        OutputFormat::Cbor => {
+            serde_cbor::to_writer(&mut out, module).map_err(|err| err.to_string())
+        }
+        OutputFormat::Bincode => {
+            bincode::serialize_into(&mut out, module).map_err(|err| err.to_string())
+        }
+    }?;
+    out.flush().map_err(|err| err.to_string())
+}
+
+/// Compute the path of the generated file, from the input file: we use the
+/// destination directory, if there is one, and change the extension.
+fn target_filename(sourcefile: &PathBuf, dest_dir: &Option<PathBuf>, extension: &str) -> PathBuf {
+    match dest_dir {
         None => {
             // No destination directory: we just need to update the file extension
             let mut tgt = sourcefile.clone();
-            assert!(tgt.set_extension("llbc"));
+            assert!(tgt.set_extension(extension));
             tgt
         }
         Some(dest_dir) => {
@@ -65,47 +254,452 @@ pub fn export(
 
             // Put together, and change the extension
             tgt.push(filename);
-            assert!(tgt.set_extension("llbc"));
+            assert!(tgt.set_extension(extension));
             tgt
         }
-    };
-
-    trace!("Target file: {:?}", target_filename);
-
-    // Serialize
-    let mod_serializer = ModSerializer {
-        name,
-        declarations: VecSW::new(&ordered_decls.decls),
-        types: &type_defs.types,
-        functions: &fun_defs,
-    };
+    }
+}
 
-    // Create the directory, if necessary (note that if the target directory
-    // is not specified, there is no need to create it: otherwise we
-    // couldn't have read the input file in the first place).
+/// Create the destination directory, if necessary (note that if the target
+/// directory is not specified, there is no need to create it: otherwise we
+/// couldn't have read the input file in the first place).
+fn create_dest_dir(dest_dir: &Option<PathBuf>) -> Result<()> {
     match dest_dir {
-        Option::None => (),
+        Option::None => Ok(()),
         Option::Some(dest_dir) => match std::fs::create_dir_all(dest_dir) {
-            std::result::Result::Ok(()) => (),
+            std::result::Result::Ok(()) => Ok(()),
             std::result::Result::Err(_) => {
                 error!("Could not create the directory: {:?}", dest_dir);
-                return Err(());
+                Err(())
             }
         },
+    }
+}
+
+/// Export the translated definitions to a file, in the given format.
+///
+/// The [options] are the ones used for the translation: we record them in the
+/// header of the file.
+pub fn export(
+    name: String,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    files: &FileId::Vector<String>,
+    dest_dir: &Option<PathBuf>,
+    options: &TransOptions,
+    format: OutputFormat,
+) -> Result<()> {
+    // Generate the destination file
+    let target_filename = target_filename(&options.input_file, dest_dir, format.extension());
+    export_to_file(
+        &target_filename,
+        name,
+        ordered_decls,
+        type_defs,
+        fun_defs,
+        files,
+        dest_dir,
+        options,
+        format,
+    )
+}
+
+/// Export the state of the translation after a micro-pass (see
+/// [crate::passes::PassDumpOptions]). The file has the same format as the
+/// file generated by [export], and is named after the pass.
+pub fn export_after_pass(
+    pass: &str,
+    name: String,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    files: &FileId::Vector<String>,
+    dest_dir: &Option<PathBuf>,
+    options: &TransOptions,
+    format: OutputFormat,
+) -> Result<()> {
+    let extension = format!("{}.{}", pass, format.extension());
+    let target_filename = target_filename(&options.input_file, dest_dir, &extension);
+    export_to_file(
+        &target_filename,
+        name,
+        ordered_decls,
+        type_defs,
+        fun_defs,
+        files,
+        dest_dir,
+        options,
+        format,
+    )
+}
+
+fn export_to_file(
+    target_filename: &PathBuf,
+    name: String,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    files: &FileId::Vector<String>,
+    dest_dir: &Option<PathBuf>,
+    options: &TransOptions,
+    format: OutputFormat,
+) -> Result<()> {
+    trace!("Target file: {:?}", target_filename);
+
+    // Relabel the declarations, if the user asked for stable ids
+    let relabeled = if options.stable_ids {
+        let map = IdMap::stable_ids(type_defs, fun_defs)?;
+        Some(relabel_with_stable_ids(
+            &map,
+            &ordered_decls.decls,
+            type_defs,
+            fun_defs,
+        ))
+    } else {
+        None
+    };
+    let (decls, type_defs, fun_defs) = match &relabeled {
+        Some((decls, type_defs, fun_defs)) => (decls, type_defs, fun_defs),
+        None => (&ordered_decls.decls, type_defs, fun_defs),
     };
 
-    // Write to the file
-    match File::create(target_filename.clone()) {
-        std::io::Result::Ok(outfile) => match serde_json::to_writer(&outfile, &mod_serializer) {
-            std::result::Result::Ok(()) => Ok(()),
-            std::result::Result::Err(_) => {
-                error!("Could not write to: {:?}", target_filename);
-                Err(())
+    // Serialize
+    let mod_serializer = ModSerializer {
+        header: Header::new(options),
+        name,
+        files,
+        declarations: VecSW::new(decls),
+        types: DeclMapSW::types(&type_defs.types),
+        functions: DeclMapSW::functions(fun_defs),
+    };
+
+    create_dest_dir(dest_dir)?;
+    write_to_file(target_filename, format, &mod_serializer)
+}
+
+/// Serialize a module (or an index) to a file, in the given format
+fn write_to_file<T: Serialize>(
+    target_filename: &PathBuf,
+    format: OutputFormat,
+    module: &T,
+) -> Result<()> {
+    match File::create(target_filename) {
+        std::io::Result::Ok(outfile) => {
+            let outfile = std::io::BufWriter::new(outfile);
+            match write_module(outfile, format, module) {
+                std::result::Result::Ok(()) => Ok(()),
+                std::result::Result::Err(err) => {
+                    error!("Could not write to {:?}: {}", target_filename, err);
+                    Err(())
+                }
             }
-        },
+        }
         std::io::Result::Err(_) => {
             error!("Could not open: {:?}", target_filename);
             Err(())
         }
     }
 }
+
+/// A module file of the index generated by [export_split]
+#[derive(Serialize)]
+struct IndexModule {
+    /// The module path (the first element is the crate name)
+    name: Vec<String>,
+    /// The path of the module file, relative to the index
+    file: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Index")]
+struct IndexSerializer<'a> {
+    header: Header,
+    name: String,
+    files: &'a FileId::Vector<String>,
+    /// The module files, in the order in which they first appear in the
+    /// declaration groups
+    modules: Vec<IndexModule>,
+    /// All the declaration groups, in dependency order
+    declarations: DeclarationsSerializer<'a>,
+    /// For every type id, the index of the module (in [modules]) which
+    /// contains the type declaration
+    type_modules: Vec<(TypeDeclId::Id, usize)>,
+    /// For every function id, the index of the module which contains the
+    /// function declaration
+    function_modules: Vec<(FunDeclId::Id, usize)>,
+}
+
+/// The declarations of a module, for [export_split]
+struct ModuleDecls {
+    /// The declaration groups, restricted to the declarations of the module
+    decls: Vec<DeclarationGroup>,
+    types: TypeDeclId::Vector<TypeDecl>,
+    functions: FunDeclId::Vector<FunDecl>,
+}
+
+impl ModuleDecls {
+    fn new() -> Self {
+        ModuleDecls {
+            decls: Vec::new(),
+            types: TypeDeclId::Vector::new(),
+            functions: FunDeclId::Vector::new(),
+        }
+    }
+}
+
+/// Restrict a declaration group to the declarations which satisfy a predicate.
+/// Note that we preserve the kind of the group: a recursive group stays
+/// recursive, even if it contains only one declaration once restricted.
+fn filter_decl_group<Id: Copy>(
+    group: &GDeclarationGroup<Id>,
+    keep: &dyn Fn(Id) -> bool,
+) -> Option<GDeclarationGroup<Id>> {
+    match group {
+        GDeclarationGroup::NonRec(id) => {
+            if keep(*id) {
+                Some(GDeclarationGroup::NonRec(*id))
+            } else {
+                None
+            }
+        }
+        GDeclarationGroup::Rec(ids) => {
+            let ids: Vec<Id> = ids.iter().copied().filter(|id| keep(*id)).collect();
+            if ids.is_empty() {
+                None
+            } else {
+                Some(GDeclarationGroup::Rec(ids))
+            }
+        }
+    }
+}
+
+/// Export the translated definitions to a directory tree mirroring the module
+/// hierarchy (`--split-modules`): the declarations of module `krate::a::b`
+/// are written in `krate/a/b/mod.llbc`, with the same format as the files
+/// generated by [export]. Note that the `types` and `functions` maps of a
+/// module file only contain the declarations of the module.
+///
+/// We also generate an index (`index.llbc`) which maps the ids of the
+/// declarations to the module files, and gives all the declaration groups in
+/// dependency order. A recursive group may be split between several modules.
+///
+/// The root of the tree is the target file of [export], with a `.d` suffix
+/// (for instance: `lib.llbc.d`).
+pub fn export_split(
+    name: String,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    files: &FileId::Vector<String>,
+    dest_dir: &Option<PathBuf>,
+    options: &TransOptions,
+    format: OutputFormat,
+) -> Result<()> {
+    let extension = format.extension();
+    let root = target_filename(&options.input_file, dest_dir, &format!("{}.d", extension));
+    trace!("Target directory: {:?}", root);
+
+    // Compute the module of every declaration
+    let type_modules: Vec<Vec<String>> = type_defs
+        .types
+        .iter()
+        .map(|def| def.name.module_path())
+        .collect();
+    let fun_modules: Vec<Vec<String>> = fun_defs.iter().map(|def| def.name.module_path()).collect();
+
+    // Dispatch the declaration groups between the modules, in order
+    let mut modules: LinkedHashMap<Vec<String>, ModuleDecls> = LinkedHashMap::new();
+    for group in &ordered_decls.decls {
+        match group {
+            DeclarationGroup::Type(group) => {
+                for module_name in group_modules(group, &type_modules) {
+                    let keep = |id: TypeDeclId::Id| type_modules[id.to_usize()] == module_name;
+                    let group = filter_decl_group(group, &keep).unwrap();
+                    let module = modules.entry(module_name).or_insert_with(ModuleDecls::new);
+                    for id in group.get_ids() {
+                        let def = type_defs.get_type_def(id).unwrap();
+                        module.types.push_back(def.clone());
+                    }
+                    module.decls.push(DeclarationGroup::Type(group));
+                }
+            }
+            DeclarationGroup::Fun(group) => {
+                for module_name in group_modules(group, &fun_modules) {
+                    let keep = |id: FunDeclId::Id| fun_modules[id.to_usize()] == module_name;
+                    let group = filter_decl_group(group, &keep).unwrap();
+                    let module = modules.entry(module_name).or_insert_with(ModuleDecls::new);
+                    for id in group.get_ids() {
+                        module
+                            .functions
+                            .push_back(fun_defs.get(id).unwrap().clone());
+                    }
+                    module.decls.push(DeclarationGroup::Fun(group));
+                }
+            }
+        }
+    }
+
+    // If the user asked for stable ids, we relabel the declarations of every
+    // module with the ids computed over the whole crate
+    let id_map = if options.stable_ids {
+        IdMap::stable_ids(type_defs, fun_defs)?
+    } else {
+        IdMap::dense_ids(type_defs, fun_defs)
+    };
+
+    // Write the module files
+    let mut index_modules = Vec::new();
+    let mut module_ids: HashMap<Vec<String>, usize> = HashMap::new();
+    for (module_name, module) in modules.iter() {
+        let mut dir = root.clone();
+        for elem in module_name {
+            dir.push(elem);
+        }
+        create_dest_dir(&Some(dir.clone()))?;
+        let filename = dir.join(format!("mod.{}", extension));
+        trace!("Module file: {:?}", filename);
+
+        let module_types = TypeDecls {
+            types: module.types.clone(),
+        };
+        let (decls, module_types, module_funs) = if options.stable_ids {
+            relabel_with_stable_ids(&id_map, &module.decls, &module_types, &module.functions)
+        } else {
+            (module.decls.clone(), module_types, module.functions.clone())
+        };
+        let mod_serializer = ModSerializer {
+            header: Header::new(options),
+            name: module_name.join("::"),
+            files,
+            declarations: VecSW::new(&decls),
+            types: DeclMapSW::types(&module_types.types),
+            functions: DeclMapSW::functions(&module_funs),
+        };
+        write_to_file(&filename, format, &mod_serializer)?;
+
+        let relative: Vec<&str> = module_name.iter().map(|s| s.as_str()).collect();
+        module_ids.insert(module_name.clone(), index_modules.len());
+        index_modules.push(IndexModule {
+            name: module_name.clone(),
+            file: format!("{}/mod.{}", relative.join("/"), extension),
+        });
+    }
+
+    // Write the index
+    let decls = id_map.relabel_decl_groups(&ordered_decls.decls);
+    let mut type_modules: Vec<(TypeDeclId::Id, usize)> = type_defs
+        .types
+        .iter()
+        .map(|d| {
+            (
+                id_map.type_id(d.def_id),
+                module_ids[&type_modules[d.def_id.to_usize()]],
+            )
+        })
+        .collect();
+    type_modules.sort_by_key(|(id, _)| *id);
+    let mut function_modules: Vec<(FunDeclId::Id, usize)> = fun_defs
+        .iter()
+        .map(|d| {
+            (
+                id_map.fun_id(d.def_id),
+                module_ids[&fun_modules[d.def_id.to_usize()]],
+            )
+        })
+        .collect();
+    function_modules.sort_by_key(|(id, _)| *id);
+    let index = IndexSerializer {
+        header: Header::new(options),
+        name,
+        files,
+        modules: index_modules,
+        declarations: VecSW::new(&decls),
+        type_modules,
+        function_modules,
+    };
+    write_to_file(&root.join(format!("index.{}", extension)), format, &index)
+}
+
+/// The modules of the declarations of a group, in order of first occurrence
+fn group_modules<Id: Copy + ToUsize>(
+    group: &GDeclarationGroup<Id>,
+    modules: &Vec<Vec<String>>,
+) -> Vec<Vec<String>> {
+    let mut group_modules: Vec<Vec<String>> = Vec::new();
+    for id in group.get_ids() {
+        let module = &modules[id.to_usize()];
+        if !group_modules.contains(module) {
+            group_modules.push(module.clone());
+        }
+    }
+    group_modules
+}
+
+/// Print the translated definitions in a human-readable syntax (the one used
+/// in the traces). The declarations are printed in the order of the
+/// declaration groups, with the type declarations followed by their regions
+/// hierarchies, and the functions with their signatures and bodies.
+pub fn fmt_translated_crate(
+    name: &str,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+) -> String {
+    let mut decls: Vec<String> = Vec::new();
+    for group in &ordered_decls.decls {
+        match group {
+            DeclarationGroup::Type(group) => {
+                for id in group.get_ids() {
+                    let def = type_defs.get_type_def(id).unwrap();
+                    decls.push(def.fmt_with_defs(type_defs));
+                }
+            }
+            DeclarationGroup::Fun(group) => {
+                for id in group.get_ids() {
+                    let def = fun_defs.get(id).unwrap();
+                    decls.push(def.fmt_with_defs(type_defs, fun_defs));
+                }
+            }
+        }
+    }
+    format!("// Crate: {}\n\n{}\n", name, decls.join("\n\n"))
+}
+
+/// Export the translated definitions to a text file (`.llbc.txt`), in the
+/// syntax of [fmt_translated_crate]. This file is meant to be read by
+/// humans, for instance to review what Charon generated: it can't be read
+/// back.
+pub fn export_pretty(
+    name: &str,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    dest_dir: &Option<PathBuf>,
+    options: &TransOptions,
+) -> Result<()> {
+    let target_filename = target_filename(&options.input_file, dest_dir, PRETTY_EXTENSION);
+    trace!("Target file: {:?}", target_filename);
+
+    let content = fmt_translated_crate(name, ordered_decls, type_defs, fun_defs);
+
+    create_dest_dir(dest_dir)?;
+    match std::fs::write(&target_filename, content) {
+        std::result::Result::Ok(()) => Ok(()),
+        std::result::Result::Err(err) => {
+            error!("Could not write to {:?}: {}", target_filename, err);
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_schema_version() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../llbc.schema.json")).unwrap();
+        let version = &schema["definitions"]["Header"]["properties"]["schema_version"]["const"];
+        assert!(*version == serde_json::json!(crate::llbc_export::SCHEMA_VERSION));
+    }
+}
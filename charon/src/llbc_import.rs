@@ -0,0 +1,402 @@
+//! Load the files generated by [crate::llbc_export] back into the AST types.
+//!
+//! This is useful for the tools which want to analyze or transform LLBC
+//! without calling the Rust compiler again.
+
+use crate::common::*;
+use crate::driver::TranslatedCrate;
+use crate::id_vector::ToUsize;
+use crate::im_ast::FunDeclId;
+use crate::llbc_ast::*;
+use crate::llbc_export::{Header, OutputFormat, BINARY_HEADER_LEN, BINARY_MAGIC, SCHEMA_VERSION};
+use crate::meta::FileId;
+use crate::rust_to_local_ids::*;
+use crate::stable_ids::IdMap;
+use crate::types::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// The counterpart of the `ModSerializer` of [crate::llbc_export]
+#[derive(Deserialize)]
+#[serde(rename = "Module")]
+struct ModDeserializer {
+    header: Header,
+    name: String,
+    files: FileId::Vector<String>,
+    declarations: Vec<DeclarationGroup>,
+    /// See [crate::llbc_export::DeclMapSW]
+    types: BTreeMap<TypeDeclId::Id, TypeDecl>,
+    functions: BTreeMap<FunDeclId::Id, FunDecl>,
+}
+
+/// Convert a map of declarations (see [crate::llbc_export::DeclMapSW]) to a
+/// vector, checking that the keys are the ids of the declarations. Without
+/// stable ids, the ids must also be the positions of the declarations.
+fn decls_to_vector<Id: Copy + Eq + ToUsize + std::fmt::Display, T>(
+    path: &Path,
+    decls: BTreeMap<Id, T>,
+    stable_ids: bool,
+    def_id: &dyn Fn(&T) -> Id,
+) -> Result<Vec<T>> {
+    let mut vector = Vec::new();
+    for (i, (id, decl)) in decls.into_iter().enumerate() {
+        if id != def_id(&decl) || (!stable_ids && id.to_usize() != i) {
+            error!("Could not load {:?}: invalid declaration id: {}", path, id);
+            return Err(());
+        }
+        vector.push(decl);
+    }
+    Ok(vector)
+}
+
+/// Decode the content of a file generated by [crate::llbc_export::export].
+/// We detect the format by looking for the magic number of the binary formats.
+fn decode(data: &[u8]) -> std::result::Result<ModDeserializer, String> {
+    if !data.starts_with(BINARY_MAGIC) {
+        return serde_json::from_slice(data).map_err(|err| err.to_string());
+    }
+
+    if data.len() < BINARY_HEADER_LEN {
+        return Err("Truncated header".to_string());
+    }
+    let format = match OutputFormat::from_tag(data[BINARY_MAGIC.len()]) {
+        Some(format) if format != OutputFormat::Json => format,
+        _ => return Err(format!("Unknown format tag: {}", data[BINARY_MAGIC.len()])),
+    };
+    let mut version = [0; 4];
+    version.copy_from_slice(&data[BINARY_MAGIC.len() + 1..BINARY_HEADER_LEN]);
+    let version = u32::from_le_bytes(version);
+    if version != SCHEMA_VERSION {
+        return Err(format!(
+            "The file was generated with version {} of the LLBC format, but we can only read version {}",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    let data = &data[BINARY_HEADER_LEN..];
+    match format {
+        OutputFormat::Json => unreachable!(),
+        OutputFormat::Cbor => serde_cbor::from_slice(data).map_err(|err| err.to_string()),
+        OutputFormat::Bincode => bincode::deserialize(data).map_err(|err| err.to_string()),
+    }
+}
+
+/// Load a file generated by [crate::llbc_export::export], in any of the
+/// supported formats (see [OutputFormat]).
+///
+/// Note that the maps between the rustc identifiers and our identifiers in the
+/// [OrderedDecls] are left empty: the rustc identifiers are not serialized.
+pub fn load_llbc(path: &Path) -> Result<TranslatedCrate> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(_) => {
+            error!("Could not read: {:?}", path);
+            return Err(());
+        }
+    };
+    let module = match decode(&data) {
+        Ok(module) => module,
+        Err(err) => {
+            error!("Could not load {:?}:\n{}", path, err);
+            return Err(());
+        }
+    };
+
+    if module.header.schema_version != SCHEMA_VERSION {
+        error!(
+            "{:?} was generated with version {} of the LLBC format, but we can only read version {}",
+            path, module.header.schema_version, SCHEMA_VERSION
+        );
+        return Err(());
+    }
+
+    let stable_ids = module.header.options.stable_ids;
+    let types = decls_to_vector(path, module.types, stable_ids, &|d: &TypeDecl| d.def_id)?;
+    let types: TypeDeclId::Vector<TypeDecl> = TypeDeclId::Vector::from(types);
+    let functions = decls_to_vector(path, module.functions, stable_ids, &|d: &FunDecl| d.def_id)?;
+    let functions: FunDecls = FunDeclId::Vector::from(functions);
+
+    // If the file uses stable ids, relabel the declarations with dense ids, so
+    // that the ids index the vectors of declarations
+    let (declarations, types, functions) = if stable_ids {
+        let types = TypeDecls { types };
+        let map = IdMap::dense_ids(&types, &functions);
+        let (decls, types, funs) = map.relabel(&module.declarations, &types, &functions);
+        (decls, types.types, funs)
+    } else {
+        (module.declarations, types, functions)
+    };
+
+    // Recompute the sets of opaque declarations
+    let opaque_types: HashSet<TypeDeclId::Id> = types
+        .iter()
+        .filter(|decl| decl.kind.is_opaque())
+        .map(|decl| decl.def_id)
+        .collect();
+    let opaque_funs: HashSet<FunDeclId::Id> = functions
+        .iter()
+        .filter(|decl| decl.body.is_none())
+        .map(|decl| decl.def_id)
+        .collect();
+
+    let ordered_decls = OrderedDecls {
+        decls: declarations,
+        opaque_types,
+        opaque_funs,
+        type_rid_to_id: HashMap::new(),
+        type_id_to_rid: HashMap::new(),
+        fun_rid_to_id: HashMap::new(),
+        fun_id_to_rid: HashMap::new(),
+    };
+
+    Ok(TranslatedCrate {
+        crate_name: module.name,
+        ordered_decls,
+        type_defs: TypeDecls { types },
+        fun_defs: functions,
+        files: module.files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::types::*;
+    use crate::values::*;
+
+    /// Check that deserializing a serialized value gives back the same value,
+    /// in all the formats
+    fn round_trip<T>(v: &T) -> T
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_string(v).unwrap();
+        let from_json: T = serde_json::from_str(&json).unwrap();
+        let cbor = serde_cbor::to_vec(v).unwrap();
+        let from_cbor: T = serde_cbor::from_slice(&cbor).unwrap();
+        let bincode = bincode::serialize(v).unwrap();
+        let from_bincode: T = bincode::deserialize(&bincode).unwrap();
+        assert_eq!(from_json, from_cbor);
+        assert_eq!(from_json, from_bincode);
+        from_json
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ty: RTy = Ty::Ref(
+            Region::Var(RegionVarId::Id::new(0)),
+            Box::new(Ty::Adt(
+                TypeId::Adt(TypeDeclId::Id::new(1)),
+                im::vector![Region::Static],
+                im::vector![
+                    Ty::Integer(IntegerTy::U32),
+                    Ty::TypeVar(TypeVarId::Id::new(0)),
+                    Ty::Bool
+                ],
+            )),
+            RefKind::Mut,
+        );
+        assert!(round_trip(&ty) == ty);
+
+        let values = vec![
+            ScalarValue::I128(i128::MIN),
+            ScalarValue::U128(u128::MAX),
+            ScalarValue::Isize(-1),
+        ];
+        assert!(round_trip(&values) == values);
+
+        let place = Place {
+            var_id: VarId::Id::new(3),
+            projection: im::vector![
+                ProjectionElem::Deref,
+                ProjectionElem::Field(FieldProjKind::Tuple(2), FieldId::Id::new(1))
+            ],
+        };
+        assert!(round_trip(&place) == place);
+
+        let cv = OperandConstantValue::Adt(
+            Some(VariantId::Id::new(1)),
+            im::vector![OperandConstantValue::ConstantValue(ConstantValue::Bool(
+                true
+            ))],
+        );
+        assert!(round_trip(&cv) == cv);
+    }
+
+    #[test]
+    fn test_statement_dataflow() {
+        use crate::llbc_ast::{DataflowInfo, RawStatement, Statement};
+        use crate::llbc_ast_utils::test_utils::st;
+
+        // The missing annotation is omitted in JSON, but not in the binary
+        // formats
+        let nop = st(RawStatement::Nop);
+        let json: serde_json::Value = serde_json::to_value(&nop).unwrap();
+        assert!(json.get("dataflow").is_none());
+        let from_json: Statement = serde_json::from_value(json).unwrap();
+        assert!(from_json.dataflow.is_none());
+        let cbor = serde_cbor::to_vec(&nop).unwrap();
+        let from_cbor: Statement = serde_cbor::from_slice(&cbor).unwrap();
+        assert!(from_cbor.dataflow.is_none());
+        let bincode = bincode::serialize(&nop).unwrap();
+        let from_bincode: Statement = bincode::deserialize(&bincode).unwrap();
+        assert!(from_bincode.dataflow.is_none());
+
+        let mut nop = nop;
+        nop.dataflow = Some(DataflowInfo {
+            live: vec![VarId::Id::new(1)],
+            maybe_init: Vec::new(),
+            maybe_moved: Vec::new(),
+        });
+        let json = serde_json::to_string(&nop).unwrap();
+        let from_json: Statement = serde_json::from_str(&json).unwrap();
+        assert!(from_json.dataflow.unwrap().live == vec![VarId::Id::new(1)]);
+        let bincode = bincode::serialize(&nop).unwrap();
+        let from_bincode: Statement = bincode::deserialize(&bincode).unwrap();
+        assert!(from_bincode.dataflow.unwrap().live == vec![VarId::Id::new(1)]);
+    }
+
+    fn name(path: &str) -> crate::names::Name {
+        crate::names::Name {
+            name: path
+                .split("::")
+                .map(|s| crate::names::PathElem::Ident(s.to_string()))
+                .collect(),
+        }
+    }
+
+    /// A small crate: a structure, a function with a body and an opaque
+    /// function
+    fn test_crate() -> crate::driver::TranslatedCrate {
+        use crate::im_ast::{FunDeclId, FunSig};
+        use crate::llbc_ast::{FunDecl, FunDecls, RawStatement};
+        use crate::llbc_ast_utils::test_utils::*;
+        use crate::regions_hierarchy::RegionGroups;
+        use crate::reorder_decls::GDeclarationGroup;
+        use crate::rust_to_local_ids::{DeclarationGroup, OrderedDecls};
+        use std::collections::{HashMap, HashSet};
+
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let field = |name: &str| Field {
+            name: Some(name.to_string()),
+            span: span(),
+            attributes: Vec::new(),
+            ty: u32_ty.clone(),
+        };
+        let pair = TypeDecl {
+            def_id: TypeDeclId::Id::new(0),
+            name: name("mycrate::Pair"),
+            span: span(),
+            attributes: Vec::new(),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Struct(FieldId::Vector::from(vec![field("x"), field("y")])),
+        };
+
+        let signature = FunSig {
+            region_params: RegionVarId::Vector::new(),
+            num_early_bound_regions: 0,
+            regions_hierarchy: RegionGroups::new(),
+            type_params: TypeVarId::Vector::new(),
+            inputs: vec![u32_ty.clone()],
+            output: u32_ty,
+        };
+        let fun = |id: usize, path: &str, body| FunDecl {
+            def_id: FunDeclId::Id::new(id),
+            name: name(path),
+            span: span(),
+            attributes: Vec::new(),
+            signature: signature.clone(),
+            body,
+        };
+        // @return := copy x; return
+        let id_body = body(
+            1,
+            &[None, Some("x")],
+            seq(vec![
+                assign(0, Rvalue::Use(copy(1))),
+                st(RawStatement::Return),
+            ]),
+        );
+        let fun_defs: FunDecls = FunDeclId::Vector::from(vec![
+            fun(0, "mycrate::id", Some(id_body)),
+            fun(1, "mycrate::ext", None),
+        ]);
+
+        let ordered_decls = OrderedDecls {
+            decls: vec![
+                DeclarationGroup::Type(GDeclarationGroup::NonRec(TypeDeclId::Id::new(0))),
+                DeclarationGroup::Fun(GDeclarationGroup::NonRec(FunDeclId::Id::new(0))),
+                DeclarationGroup::Fun(GDeclarationGroup::NonRec(FunDeclId::Id::new(1))),
+            ],
+            opaque_types: HashSet::new(),
+            opaque_funs: vec![FunDeclId::Id::new(1)].into_iter().collect(),
+            type_rid_to_id: HashMap::new(),
+            type_id_to_rid: HashMap::new(),
+            fun_rid_to_id: HashMap::new(),
+            fun_id_to_rid: HashMap::new(),
+        };
+
+        crate::driver::TranslatedCrate {
+            crate_name: "mycrate".to_string(),
+            ordered_decls,
+            type_defs: TypeDecls {
+                types: TypeDeclId::Vector::from(vec![pair]),
+            },
+            fun_defs,
+            files: crate::meta::FileId::Vector::from(vec!["src/lib.rs".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_export_load() {
+        use crate::driver::TransOptions;
+        use crate::llbc_export::{export, OutputFormat};
+        use crate::llbc_import::load_llbc;
+        use std::path::PathBuf;
+
+        // Compare the declarations through their JSON representation: the
+        // declarations don't implement [PartialEq]
+        fn json<T: serde::Serialize>(v: &T) -> serde_json::Value {
+            serde_json::to_value(v).unwrap()
+        }
+
+        let krate = test_crate();
+        let options = TransOptions::new(PathBuf::from("lib.rs"));
+        let dest_dir = std::env::temp_dir().join(format!("charon-test-{}", std::process::id()));
+        let dest_dir = Option::Some(dest_dir);
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::Cbor,
+            OutputFormat::Bincode,
+        ] {
+            export(
+                krate.crate_name.clone(),
+                &krate.ordered_decls,
+                &krate.type_defs,
+                &krate.fun_defs,
+                &krate.files,
+                &dest_dir,
+                &options,
+                format,
+            )
+            .unwrap();
+            let path = dest_dir
+                .as_ref()
+                .unwrap()
+                .join(format!("lib.{}", format.extension()));
+            let loaded = load_llbc(&path).unwrap();
+
+            assert!(loaded.crate_name == krate.crate_name);
+            assert!(json(&loaded.files) == json(&krate.files));
+            assert!(json(&loaded.ordered_decls.decls) == json(&krate.ordered_decls.decls));
+            assert!(loaded.ordered_decls.opaque_types == krate.ordered_decls.opaque_types);
+            assert!(loaded.ordered_decls.opaque_funs == krate.ordered_decls.opaque_funs);
+            assert!(json(&loaded.type_defs.types) == json(&krate.type_defs.types));
+            assert!(json(&loaded.fun_defs) == json(&krate.fun_defs));
+        }
+        std::fs::remove_dir_all(dest_dir.unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,419 @@
+//! Annotate every loop with the locals its body reads and modifies, the places
+//! it borrows, and the places which are live when exiting the loop (see
+//! [crate::llbc_ast::LoopInfo]). The backends translating the loops to
+//! recursive functions, or generating loop invariants, need this information:
+//! this way, they don't have to redo the analysis themselves.
+//!
+//! The places live after a loop are computed with a backward liveness
+//! analysis (see [crate::dataflow]). A place is live if it may be read before
+//! being overwritten: reading a place (or borrowing it) makes it live, while
+//! writing to a place kills the places it is a prefix of (writing to `x.f`
+//! kills `x.f` and `x.f.g`, but not `x`). Writing through a dereference reads
+//! the dereferenced pointer.
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. The
+//! passes which transform the statements don't update the annotations: it
+//! must be applied after them, and after [crate::ssa] (see
+//! [crate::passes::check_pass_order]).
+
+use crate::dataflow::{analyze, Analysis, Direction};
+use crate::expressions::*;
+use crate::llbc_ast::{FunBody, FunDecl, FunDecls, LoopInfo, RawStatement, Statement};
+use std::collections::BTreeSet;
+use std::iter::FromIterator;
+
+/// A set of places. [Place] is not hashable: we use a vector, and don't
+/// insert duplicates.
+type Places = Vec<Place>;
+
+fn insert(places: &mut Places, p: &Place) {
+    if !places.contains(p) {
+        places.push(p.clone());
+    }
+}
+
+/// Return true if `p1` is a prefix of `p2`
+fn is_prefix(p1: &Place, p2: &Place) -> bool {
+    p1.var_id == p2.var_id
+        && p1.projection.len() <= p2.projection.len()
+        && p1
+            .projection
+            .iter()
+            .zip(p2.projection.iter())
+            .all(|(pe1, pe2)| pe1 == pe2)
+}
+
+/// If the place is written through a dereference, return the dereferenced
+/// pointer, which is read
+fn dereferenced(p: &Place) -> Option<Place> {
+    let i = p
+        .projection
+        .iter()
+        .rposition(|pe| matches!(pe, ProjectionElem::Deref | ProjectionElem::DerefBox))?;
+    Option::Some(Place {
+        var_id: p.var_id,
+        projection: p.projection.take(i),
+    })
+}
+
+/// The places read or borrowed by an operand
+fn operand_reads(places: &mut Places, op: &Operand) {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => insert(places, p),
+        Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => rvalue_reads(places, rv),
+    }
+}
+
+/// The places read or borrowed by an rvalue
+fn rvalue_reads(places: &mut Places, rv: &Rvalue) {
+    match rv {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand_reads(places, op),
+        Rvalue::Ref(p, _) | Rvalue::Discriminant(p) => insert(places, p),
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
+            operand_reads(places, op1);
+            operand_reads(places, op2);
+        }
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                operand_reads(places, op);
+            }
+        }
+    }
+}
+
+/// The places borrowed by an operand
+fn operand_borrows(places: &mut Places, op: &Operand) {
+    match op {
+        Operand::Copy(_) | Operand::Move(_) | Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => rvalue_borrows(places, rv),
+    }
+}
+
+/// The places borrowed by an rvalue
+fn rvalue_borrows(places: &mut Places, rv: &Rvalue) {
+    match rv {
+        Rvalue::Ref(p, _) => insert(places, p),
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand_borrows(places, op),
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
+            operand_borrows(places, op1);
+            operand_borrows(places, op2);
+        }
+        Rvalue::Discriminant(_) => (),
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                operand_borrows(places, op);
+            }
+        }
+    }
+}
+
+/// What a loop body accesses
+struct Accesses {
+    reads: BTreeSet<VarId::Id>,
+    modifies: BTreeSet<VarId::Id>,
+    borrows: Places,
+}
+
+impl Accesses {
+    fn read_places(&mut self, places: &Places) {
+        self.reads.extend(places.iter().map(|p| p.var_id));
+    }
+
+    fn read_operand(&mut self, op: &Operand) {
+        let mut places = Vec::new();
+        operand_reads(&mut places, op);
+        self.read_places(&places);
+        operand_borrows(&mut self.borrows, op);
+    }
+
+    fn read_rvalue(&mut self, rv: &Rvalue) {
+        let mut places = Vec::new();
+        rvalue_reads(&mut places, rv);
+        self.read_places(&places);
+        rvalue_borrows(&mut self.borrows, rv);
+    }
+
+    fn write(&mut self, p: &Place) {
+        match dereferenced(p) {
+            Option::Some(ptr) => {
+                self.reads.insert(ptr.var_id);
+            }
+            Option::None => {
+                self.modifies.insert(p.var_id);
+            }
+        }
+    }
+
+    fn statement(&mut self, st: &Statement) {
+        match &st.content {
+            RawStatement::Assign(p, rv) => {
+                self.read_rvalue(rv);
+                self.write(p);
+            }
+            RawStatement::FakeRead(p) | RawStatement::Drop(p) => {
+                self.reads.insert(p.var_id);
+            }
+            RawStatement::SetDiscriminant(p, _) => self.write(p),
+            RawStatement::Assert(assert) => self.read_operand(&assert.cond),
+            RawStatement::Call(call) => {
+                for op in &call.args {
+                    self.read_operand(op);
+                }
+                self.write(&call.dest);
+            }
+            RawStatement::Try(t) => {
+                self.read_operand(&t.op);
+                self.write(&t.dest);
+                self.reads.extend(t.drops.iter().map(|p| p.var_id));
+            }
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop => (),
+            RawStatement::Sequence(st1, st2) => {
+                self.statement(st1);
+                self.statement(st2);
+            }
+            RawStatement::Switch(op, targets) => {
+                self.read_operand(op);
+                for st in targets.get_targets() {
+                    self.statement(st);
+                }
+            }
+            RawStatement::Loop(loop_) => self.statement(&loop_.body),
+        }
+    }
+}
+
+/// The places which may be read later (see [crate::dataflow::Liveness] for
+/// the liveness of the locals)
+struct PlaceLiveness;
+
+impl PlaceLiveness {
+    /// Update the live places before a write to a place
+    fn write(live: &mut Places, p: &Place) {
+        live.retain(|q| !is_prefix(p, q));
+        if let Option::Some(ptr) = dereferenced(p) {
+            insert(live, &ptr);
+        }
+    }
+}
+
+impl Analysis for PlaceLiveness {
+    type Domain = Places;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn bottom(&self) -> Places {
+        Vec::new()
+    }
+
+    fn boundary(&self) -> Places {
+        // The return value is read when returning
+        vec![Place {
+            var_id: VarId::ZERO,
+            projection: Projection::new(),
+        }]
+    }
+
+    fn join(&self, d: &mut Places, other: &Places) {
+        for p in other {
+            insert(d, p);
+        }
+    }
+
+    fn transfer(&self, st: &RawStatement, live: &mut Places) {
+        match st {
+            RawStatement::Assign(p, rv) => {
+                PlaceLiveness::write(live, p);
+                rvalue_reads(live, rv);
+            }
+            RawStatement::FakeRead(p) | RawStatement::Drop(p) => insert(live, p),
+            RawStatement::SetDiscriminant(p, _) => {
+                if let Option::Some(ptr) = dereferenced(p) {
+                    insert(live, &ptr);
+                }
+            }
+            RawStatement::Assert(assert) => operand_reads(live, &assert.cond),
+            RawStatement::Call(call) => {
+                PlaceLiveness::write(live, &call.dest);
+                for op in &call.args {
+                    operand_reads(live, op);
+                }
+            }
+            RawStatement::Try(t) => {
+                // If the operand is an error, we return: the return value is
+                // computed from the operand, and the places in `drops` are
+                // dropped
+                PlaceLiveness::write(live, &t.dest);
+                operand_reads(live, &t.op);
+                for p in &t.drops {
+                    insert(live, p);
+                }
+            }
+            RawStatement::Switch(op, _) => operand_reads(live, op),
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop
+            | RawStatement::Sequence(_, _)
+            | RawStatement::Loop(_) => (),
+        }
+    }
+}
+
+/// Annotate a loop, given the places live after it
+fn annotate_loop(st: &mut Statement, live: &Places) {
+    if let RawStatement::Loop(loop_) = &mut st.content {
+        let mut accesses = Accesses {
+            reads: BTreeSet::new(),
+            modifies: BTreeSet::new(),
+            borrows: Vec::new(),
+        };
+        accesses.statement(&loop_.body);
+        loop_.info = Option::Some(LoopInfo {
+            reads: Vec::from_iter(accesses.reads),
+            modifies: Vec::from_iter(accesses.modifies),
+            borrows: accesses.borrows,
+            live_at_break: live.clone(),
+        });
+    }
+}
+
+fn transform_body(body: &mut FunBody) {
+    let _ = analyze(&PlaceLiveness, &mut body.body, &mut |st, _, live| {
+        annotate_loop(st, live)
+    });
+}
+
+fn transform_def(mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    if let Option::Some(body) = &mut def.body {
+        transform_body(body);
+    }
+    def
+}
+
+pub fn transform(defs: FunDecls) -> FunDecls {
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::llbc_ast::{LoopInfo, RawStatement, Statement};
+    use crate::llbc_ast_utils::test_utils::*;
+    use crate::loop_analysis::transform_body;
+
+    /// The locals of the tests: `b` is the input, `p` and `q` are pointers
+    static LOCALS: [Option<&str>; 6] =
+        [None, Some("b"), Some("p"), Some("x"), Some("y"), Some("q")];
+
+    fn info(st: &Statement) -> &LoopInfo {
+        match &st.content {
+            RawStatement::Loop(loop_) => loop_.info.as_ref().unwrap(),
+            _ => panic!("Expected a loop"),
+        }
+    }
+
+    fn locals(ids: &[usize]) -> Vec<VarId::Id> {
+        ids.iter().map(|i| VarId::Id::new(*i)).collect()
+    }
+
+    fn deref(i: usize) -> Place {
+        let mut p = local(i);
+        p.projection.push_back(ProjectionElem::Deref);
+        p
+    }
+
+    #[test]
+    fn test_accesses() {
+        // p := &mut x;
+        // loop {
+        //   *p := copy y;
+        //   q := &y;
+        //   if move b { continue 0 } else { break 0 }
+        // };
+        // @return := copy x;
+        // return
+        let mut b = body(
+            1,
+            &LOCALS,
+            seq(vec![
+                assign(2, Rvalue::Ref(local(3), BorrowKind::Mut)),
+                loop_(seq(vec![
+                    st(RawStatement::Assign(deref(2), Rvalue::Use(copy(4)))),
+                    assign(5, Rvalue::Ref(local(4), BorrowKind::Shared)),
+                    if_(1, st(RawStatement::Continue(0)), st(RawStatement::Break(0))),
+                ])),
+                assign(0, Rvalue::Use(copy(3))),
+                st(RawStatement::Return),
+            ]),
+        );
+        transform_body(&mut b);
+        let sts = flatten_seq(&b.body);
+        let info = info(sts[1]);
+
+        // Writing through `p` reads `p`, and modifies neither `p` nor `x`
+        assert!(info.reads == locals(&[1, 2, 4]));
+        assert!(info.modifies == locals(&[5]));
+        assert!(info.borrows == vec![local(4)]);
+        assert!(info.live_at_break == vec![local(3)]);
+    }
+
+    #[test]
+    fn test_nested_break() {
+        // loop {
+        //   loop {
+        //     if move b { break 1 } else { x := copy y; break 0 }
+        //   };
+        //   continue 0
+        // };
+        // @return := copy x;
+        // return
+        let mut b = body(
+            1,
+            &LOCALS,
+            seq(vec![
+                loop_(seq(vec![
+                    loop_(if_(
+                        1,
+                        st(RawStatement::Break(1)),
+                        seq(vec![
+                            assign(3, Rvalue::Use(copy(4))),
+                            st(RawStatement::Break(0)),
+                        ]),
+                    )),
+                    st(RawStatement::Continue(0)),
+                ])),
+                assign(0, Rvalue::Use(copy(3))),
+                st(RawStatement::Return),
+            ]),
+        );
+        transform_body(&mut b);
+        let sts = flatten_seq(&b.body);
+        let outer = info(sts[0]);
+        let inner = match &sts[0].content {
+            RawStatement::Loop(loop_) => info(flatten_seq(&loop_.body)[0]),
+            _ => unreachable!(),
+        };
+
+        // The `break 1` exits the outer loop
+        assert!(outer.live_at_break == vec![local(3)]);
+        assert!(outer.modifies == locals(&[3]));
+
+        // After the inner loop, we go back to the entry of the outer loop:
+        // `x` is live because the next iteration may exit with the `break 1`
+        assert!(inner.reads == locals(&[1, 4]));
+        assert!(inner.modifies == locals(&[3]));
+        for i in &[1, 3, 4] {
+            assert!(inner.live_at_break.contains(&local(*i)));
+        }
+        assert!(inner.live_at_break.len() == 3);
+    }
+}
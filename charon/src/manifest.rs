@@ -0,0 +1,664 @@
+//! The helpers used to read crate manifests (the `Cargo.toml` files) and to
+//! compute the arguments with which to call the Rust compiler, so that it finds
+//! the compiled external dependencies.
+
+use crate::common::*;
+use crate::driver::TargetKind;
+use crate::toolchain;
+use log::info;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Error, Formatter};
+use std::path::PathBuf;
+
+// The following helpers are used to read crate manifests (the `Cargo.toml` files),
+// and were adapated from [hacspec](https://github.com/hacspec/).
+
+/// We ignore some fields:
+/// - source: String
+/// - req: String
+/// - rename: Option<String>
+/// - optional: bool
+/// - uses_default_features: bool
+/// - features: Vec<?>
+/// - target: Option<?>
+/// - registry: Opion<?>
+#[derive(Debug, Deserialize, Clone)]
+struct Dependency {
+    name: String,
+    kind: Option<String>,
+}
+
+/// We ignore some fields:
+/// - edition: string
+/// - doctest: bool
+/// - test: bool
+/// TODO: remove?
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct Target {
+    pub(crate) name: String,
+    kind: Vec<String>,
+    #[allow(dead_code)]
+    crate_types: Vec<String>,
+    src_path: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// We ignore some fields:
+/// - version: string
+/// - license: Option<String>
+/// - license_file: Option<String>
+/// - description: Option<String>
+/// - source: Option<String>
+/// - features: ?
+/// - metadata: Option<?>
+/// - publish: Option<?>
+/// - authors: Vec<String>
+/// - categories: Vec<?>
+/// - keywords: Vec<?>
+/// - readme: Option<?>
+/// - repository: Option<?>
+/// - homepage: Option<?>
+/// - documentation: Option<?>
+/// - links: String
+pub(crate) struct Package {
+    #[allow(dead_code)]
+    name: String,
+    #[allow(dead_code)]
+    id: String,
+    targets: Vec<Target>,
+    dependencies: Vec<Dependency>,
+    manifest_path: String,
+    pub(crate) edition: String,
+}
+
+#[derive(Debug, Deserialize)]
+/// We ignore some fields:
+/// - resolve: Option<?>
+/// - version: int
+/// - workspace_root: String
+pub(crate) struct Manifest {
+    packages: Vec<Package>,
+    #[allow(dead_code)]
+    /// The workspace members are packages identified by their [id]
+    workspace_members: Vec<String>,
+    target_directory: String,
+    metadata: Option<String>,
+}
+
+impl Display for Dependency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), Error> {
+        write!(f, "      {}: kind={:?}", self.name, self.kind)
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), Error> {
+        write!(
+            f,
+            "      {}: kind={:?}, crate_types={:?}, src_path={}",
+            self.name, self.kind, self.crate_types, self.src_path
+        )
+    }
+}
+
+impl Display for Package {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), Error> {
+        write!(f, "  {}: {{\n", self.name)?;
+
+        // id
+        write!(f, "    id={};\n", self.id)?;
+
+        // manifest_path
+        write!(f, "    manifest_path={};\n", self.manifest_path)?;
+
+        // targets
+        write!(f, "    targets=[\n")?;
+        for target in &self.targets {
+            write!(f, "{},\n", target)?
+        }
+        write!(f, "    ];\n")?;
+
+        // dependencies
+        write!(f, "    dependencies=[\n")?;
+        for dep in &self.dependencies {
+            write!(f, "{},\n", dep)?;
+        }
+        write!(f, "    ];\n")?;
+
+        write!(f, "  }}")
+    }
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), Error> {
+        // workspace_members
+        write!(f, "workspace_members=[\n")?;
+        for wm in &self.workspace_members {
+            write!(f, "  {},\n", wm)?;
+        }
+        write!(f, "];\n")?;
+
+        write!(f, "target_directory={};\n", self.target_directory)?;
+        write!(f, "metadata={:?};\n", self.metadata)?;
+
+        write!(f, "packages=[\n")?;
+        for p in &self.packages {
+            write!(f, "{},\n", p)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+/// Small helper. See [compute_external_deps]
+fn compiled_to_lib_name(remove_pre: bool, no_ext_filename: String) -> String {
+    // We need to convert the filename to a vector of chars - slices of strings
+    // operate over bytes, not characters
+    let filename: Vec<char> = no_ext_filename.chars().collect();
+
+    // Remove the "lib" prefix, if necessary.
+    // We have to clone because borrows can't outlive the blocks in which
+    // they are created, which is slightly annoying...
+    let filename: Vec<char> = if remove_pre {
+        let pre: Vec<char> = "lib".to_string().chars().collect();
+        assert!(filename.len() > pre.len());
+        assert!(&filename[0..pre.len()] == pre);
+        filename[pre.len()..].to_vec()
+    } else {
+        filename
+    };
+
+    // Remove the hash suffix
+    assert!(filename.len() > 0);
+    let mut i = filename.len() - 1;
+    while i > 0 {
+        if filename[i] == '-' {
+            return filename[0..i].iter().collect::<String>();
+        }
+        i -= 1;
+    }
+    // If we got there, it means we couldn't spot the '-' character delimiting
+    // the hash suffix
+    unreachable!("Invalid compiled file name: {:?}", no_ext_filename);
+}
+
+/// Small utility. See [compute_external_deps].
+/// Insert [filename] in the string vector for [lib_name]. Create a new entry if
+/// [lib_name] is not an entry.
+///
+/// There may be several compiled files for the same library (if we used different
+/// versions of the compiler for instance). This is why we use a map from `String`
+/// to `Vec<String>`.
+fn insert_in_vec_map(map: &mut HashMap<String, Vec<String>>, lib_name: String, filename: String) {
+    // There may not be an entry, in which case we initialize it - there must
+    // be a better way of doing this?...
+    if !(map.contains_key(&lib_name)) {
+        map.insert(lib_name.clone(), Vec::new());
+    }
+
+    // Insert the new filename
+    trace!("lib to compiled: {:?} -> {:?}", &lib_name, filename);
+    let filenames = map.get_mut(&lib_name).unwrap();
+    filenames.push(filename);
+}
+
+/// Compute the path to the crate (i.e., the directory containing the manifest)
+/// from the path to the entry point of the crate.
+///
+/// We use the source file as a starting point, and look for the closest parent
+/// directory containing a `Cargo.toml` file. Note that the source file is not
+/// necessarily in the `src` directory: the entry points of the binaries can be in
+/// `src/bin`, the entry points of the tests in `tests`, etc.
+pub(crate) fn compute_crate_path(source_file: &PathBuf) -> Result<PathBuf> {
+    let source_file = match std::fs::canonicalize(&source_file) {
+        Ok(source_file) => source_file,
+        Err(err) => {
+            error!("Could not find the input file {:?}: {}", source_file, err);
+            return Err(());
+        }
+    };
+    let mut crate_path = source_file.as_path().parent().unwrap();
+    while !crate_path.join("Cargo.toml").exists() {
+        match crate_path.parent() {
+            Some(parent) => crate_path = parent,
+            None => {
+                error!("Could not find a `Cargo.toml` file for: {:?}", source_file);
+                return Err(());
+            }
+        }
+    }
+    Ok(crate_path.to_path_buf())
+}
+
+/// Find the target whose entry point is [source_file], if there is one
+pub(crate) fn find_target<'a>(package: &'a Package, source_file: &PathBuf) -> Option<&'a Target> {
+    let source_file = std::fs::canonicalize(&source_file).unwrap();
+    package
+        .targets
+        .iter()
+        .find(|target| PathBuf::from(&target.src_path) == source_file)
+}
+
+/// Read the manifest of a crate, find the target package and compute the external
+/// dependencies.
+///
+/// We face the issue that we directly call the rust compiler, rather than
+/// `cargo`, and thus have to give very precise arguments to our invocation
+/// of rustc (more specifically: we need to provide the list of external
+/// dependencies).
+///
+/// This is slightly annoying to do, and we place ourselves in the situation
+/// where the project is built through `cargo`, and the user built the
+/// (debug version) of the project *before* calling Charon. In this situation,
+/// we can leverage the fact that the external dependencies have already been
+/// compiled, and can be found in the target directory (`/target/debug/deps/`,
+/// usually).
+/// We thus don't have to build them (and don't want anyway! Charon is not a
+/// build system), and just need to:
+/// - use the manifest (the `Cargo.toml` file) to retrieve the list of external
+///   dependencies
+/// - explore the target `/target/debug/deps` directory to retrieve the names of
+///   the compiled libraries, to compute the arguments with which to invoke the
+///   Rust compiler
+///
+/// Finally, the code used in this function to read the manifest and compute
+/// the list of external dependencies is greatly inspired by the code used in
+/// [hacspec](https://github.com/hacspec/), so all credits to them.
+///
+/// If [rebuild_deps] is true, we don't use the dependencies compiled by the
+/// user but build them ourselves, with the proper toolchain, in a private
+/// target directory.
+///
+/// The [target_kind] is used to know which dependencies we need: for instance,
+/// the tests can use the dev-dependencies, and the binaries and the tests can
+/// use the library of their own package.
+pub(crate) fn read_manifest_compute_external_deps(
+    source_file: &PathBuf,
+    target_kind: TargetKind,
+    rebuild_deps: bool,
+) -> Result<(Manifest, Package, Vec<String>)> {
+    use std::str::FromStr;
+
+    // Compute the path to the crate
+    let crate_path = compute_crate_path(source_file)?;
+    let crate_path = crate_path.as_path();
+    let mut manifest_path = crate_path.to_path_buf();
+    manifest_path.push(PathBuf::from_str("Cargo.toml").unwrap());
+    let manifest_path = manifest_path.to_str().unwrap().to_string();
+
+    // First, read the manifest (comes from hacspec)
+    info!("Reading manifest: {:?}", manifest_path);
+
+    // Compute the command to apply
+    let output_args = vec![
+        // We want to read the metadata
+        "metadata".to_string(),
+        // We need the verbose version of the manifest
+        "-v".to_string(),
+        // Focus on the workspace members
+        "--no-deps".to_string(),
+        // For stability (and to prevent cargo from printing an annoying warning
+        // message), select a format version
+        "--format-version".to_string(),
+        "1".to_string(),
+        // We need to provide the path to the manifest
+        "--manifest-path".to_string(),
+        manifest_path.clone(),
+    ];
+
+    trace!("cargo metadata command args: {:?}", output_args);
+
+    // Apply the command
+    let output = match std::process::Command::new("cargo")
+        .args(output_args)
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => {
+            error!("Could not call cargo to read the manifest: {}", err);
+            return Err(());
+        }
+    };
+    if !output.status.success() {
+        error!(
+            "Error running cargo metadata:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(());
+    }
+    let manifest: Manifest = match serde_json::from_slice(&output.stdout) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            error!(
+                "Error reading the manifest (Cargo.toml file) processed by cargo: {}",
+                err
+            );
+            return Err(());
+        }
+    };
+
+    trace!("manifest: {}", manifest);
+
+    // The manifest lists all the packages we need to build (including the
+    // dependencies' dependencies). We only want to retrieve the information
+    // about the package for the local crate (assuming there is only one).
+    // We thought of using the [workspace_members] field, but it actually
+    // contains a lot of unrelated packages. We thus do this in a slightly
+    // hacky way: we find the package whose [manifest_path] field matches
+    // the current manifest.
+    // Rmk: in theory, it would be cleaner if we didn't give a source file
+    // as input argument to Charon, but rather a directory (from where we
+    // would find the manifest, then lookup the proper target, which would
+    // contain the source path indicating the entry point of the crate).
+    // For now we don't do this because we want to be able to extract
+    // sub-parts of a crate (by using the proper entry points): we might
+    // change that in the future.
+
+    // Find all the packages whose [manifest_path] matches the current manifest
+    let mut tgt_packages: Vec<Package> = Vec::new();
+    for package in &manifest.packages {
+        if package.manifest_path == manifest_path {
+            tgt_packages.push(package.clone());
+        }
+    }
+    // Check that we found exactly one package
+    if tgt_packages.len() != 1 {
+        error!(
+            "Expected exactly one package with the manifest {:?}, found {}",
+            manifest_path,
+            tgt_packages.len()
+        );
+        return Err(());
+    }
+
+    let tgt_package = tgt_packages.pop().unwrap();
+
+    // Build systems can be annoying, especially if we use different versions
+    // of the compiler (Charon relies on a nightly version, which may be
+    // different from the one used by the user to compile his project! - this
+    // can result in rustc considering the compiled libraries as invalid,
+    // because of a version mismatch).
+    // We don't want to take the user by surprise if something goes wrong,
+    // so we print as much information as we can: we read the version of
+    // the compiler used to build every dependency we select, and check it
+    // against our own version (see [toolchain]).
+    // Rk.: we don't want to force the user to compile his project with a
+    // specific version of the compiler. If the versions don't match, the user
+    // can ask us to build the dependencies ourselves, with the proper
+    // toolchain, in a private target directory (`--rebuild-deps`).
+
+    // List the dependencies.
+    // We do something simple: we list the dependencies for the target package,
+    // as having useless dependencies shouldn't be a problem.
+    // We make sure we don't have duplicates while doing so.
+    let mut deps: HashSet<String> = HashSet::new();
+    for dep in &tgt_package.dependencies {
+        // We keep the "regular" dependencies (with kind=None), and the
+        // dev-dependencies if the target can use them.
+        // We ignore the build dependencies, which are only used by the build
+        // scripts.
+        match dep.kind.as_deref() {
+            Option::None => (),
+            Option::Some("dev") => {
+                if !target_kind.uses_dev_dependencies() {
+                    continue;
+                }
+            }
+            Option::Some("build") => {
+                trace!("Ignoring the build dependency: {}", dep.name);
+                continue;
+            }
+            Option::Some(kind) => {
+                log::warn!(
+                    "Ignoring the dependency {} which has an unknown kind: {}",
+                    dep.name,
+                    kind
+                );
+                continue;
+            }
+        }
+
+        // A crate name may use the "-" symbol, however this symbol gets
+        // replaced with "_" at compilation: we thus need to convert it
+        // before registering it.
+        // Note that we need to do the conversion now, because when looking
+        // in the directory file containing all the compiled dependencies,
+        // we filter the useless dependencies.
+        let dep = str::replace(&dep.name, "-", "_");
+        deps.insert(dep);
+    }
+
+    // If the package has a library and we don't extract the library itself,
+    // the target may use it (for instance, the integration tests or the
+    // binaries use the library of their package).
+    let source_file_path = std::fs::canonicalize(&source_file).unwrap();
+    for target in &tgt_package.targets {
+        if target.kind.iter().any(|k| k == "lib" || k == "rlib")
+            && PathBuf::from(&target.src_path) != source_file_path
+        {
+            deps.insert(str::replace(&target.name, "-", "_"));
+        }
+    }
+    trace!("List of external dependencies: {:?}", deps);
+
+    // Compute the path to the compiled dependencies - if necessary, we build
+    // them first
+    let target_directory = if rebuild_deps {
+        let target_directory = toolchain::private_target_directory(&manifest.target_directory);
+        toolchain::rebuild_dependencies(
+            &manifest_path,
+            &target_directory,
+            target_kind.uses_dev_dependencies(),
+        )?;
+        target_directory.to_str().unwrap().to_string()
+    } else {
+        manifest.target_directory.clone()
+    };
+    let target_dir = format!("{}/debug/deps/", &target_directory);
+    let deps_dir = PathBuf::from_str(&target_dir).unwrap();
+    let deps_dir = crate_path.join(deps_dir);
+    info!(
+        "Looking for the compiled external dependencies in: {:?}",
+        deps_dir
+    );
+
+    // List the files in the dependencies
+    // There are .rmeta, .rlib, .d and .so files.
+    // All the files have a hash suffix.
+    // The .rmeta, .rlib and .so files have a "lib" prefix.
+    // Ex.:
+    // - External "remote" crates:
+    //   "libserde_json-25bfd2343c819291.rlib"
+    // - Local crates:
+    //   "attributes-b73eebf157017326.d"
+    //   "libattributes-b73eebf157017326.so"
+    //
+    // We list all the compiled files in the target directory and retrieve the
+    // original library name (i.e., "serde_json" or "attributes" in the above
+    // examples), then compute a map from library name to compiled files.
+    //
+    // It happens that there are several compiled files for the same dependency:
+    // we store them all in a vector.
+    let files = match std::fs::read_dir(deps_dir.clone()) {
+        Ok(files) => files,
+        Err(err) => {
+            error!(
+                "Could not read the directory of the compiled dependencies {:?}: {}. You may need to build the crate: `{}`.",
+                deps_dir, err, target_kind.build_command()
+            );
+            return Err(());
+        }
+    };
+    let mut lib_to_rmeta: HashMap<String, Vec<String>> = HashMap::new();
+    let mut lib_to_rlib: HashMap<String, Vec<String>> = HashMap::new();
+    let mut lib_to_so: HashMap<String, Vec<String>> = HashMap::new();
+    let mut lib_to_d: HashMap<String, Vec<String>> = HashMap::new();
+    for file in files {
+        trace!("File: {:?}", file);
+        match file {
+            std::io::Result::Ok(entry) => {
+                let entry = entry.path();
+
+                // We only keep the files with .rlib or .d extension
+                let extension = entry.extension();
+                if extension.is_none() {
+                    continue;
+                }
+                let extension = extension.unwrap().to_str().unwrap();
+                if extension != "rmeta"
+                    && extension != "rlib"
+                    && extension != "so"
+                    && extension != "d"
+                {
+                    continue;
+                }
+                // The file has a "lib" prefix if and only if its extension is
+                // ".rmeta", ".rlib" or ".so"
+                let is_rmeta = extension == "rmeta";
+                let is_rlib = extension == "rlib";
+                let is_so = extension == "so";
+                let has_prefix = is_rmeta || is_rlib || is_so;
+
+                // Retrieve the file name
+                let filename = PathBuf::from(entry.file_name().unwrap());
+
+                // Remove the extension
+                let no_ext_filename = filename.file_stem().unwrap().to_str().unwrap().to_string();
+
+                // Compute the library name (remove the "lib" prefix for .rlib files,
+                // remove the hash suffix)
+                let lib_name = compiled_to_lib_name(has_prefix, no_ext_filename);
+
+                // Only keep the libraries for the dependencies we need
+                if !(deps.contains(&lib_name)) {
+                    continue;
+                }
+
+                // Insert in the proper map - note that we need the full path
+                let full_path = deps_dir.join(entry).to_str().unwrap().to_string();
+                if is_rmeta {
+                    insert_in_vec_map(&mut lib_to_rmeta, lib_name, full_path);
+                } else if is_rlib {
+                    insert_in_vec_map(&mut lib_to_rlib, lib_name, full_path);
+                } else if is_so {
+                    insert_in_vec_map(&mut lib_to_so, lib_name, full_path);
+                } else {
+                    insert_in_vec_map(&mut lib_to_d, lib_name, full_path);
+                }
+            }
+            std::io::Result::Err(err) => {
+                error!(
+                    "Unexpected error while reading files in {:?}: {}",
+                    deps_dir, err
+                );
+                return Err(());
+            }
+        }
+    }
+
+    // Generate the additional arguments
+    let mut args: Vec<String> = Vec::new();
+
+    // Add the "-L" dependency
+    args.push("-L".to_string());
+    args.push(format!("dependency={}", deps_dir.to_str().unwrap().to_string()).to_string());
+
+    // The version of the compiler we use: we check that the dependencies were
+    // compiled with the same version.
+    let rustc_version = toolchain::charon_rustc_version();
+    // The list of dependencies compiled with a different version of the compiler:
+    // (library name, compiled file, version)
+    let mut mismatches: Vec<(String, String, String)> = Vec::new();
+
+    // Add the "--extern" arguments
+    for dep in deps {
+        // Retrieve the path to the compiled library.
+        // We look in the following order:
+        // - .rmeta
+        // - .rlib files
+        // - .so files
+        let libs = [&lib_to_rmeta, &lib_to_rlib, &lib_to_so];
+        let mut compiled_path = None;
+        for lib in libs {
+            compiled_path = lib.get(&dep);
+            if compiled_path.is_some() {
+                break;
+            }
+        }
+        if compiled_path.is_none() {
+            error!(
+                "Could not find a compiled file for the external dependency {:?} in {:?}. You may need to build the crate: `{}`.",
+                dep, deps_dir, target_kind.build_command()
+            );
+            return Err(());
+        }
+
+        // Check that there is exactly one compiled library.
+        // If there are several compiled files because the crate was built
+        // with different versions of the compiler, we select the one which
+        // was built with our version.
+        let candidates = compiled_path.unwrap();
+        assert!(candidates.len() > 0);
+        let compiled_path: Vec<&String> = if candidates.len() > 1 {
+            candidates
+                .iter()
+                .filter(|path| {
+                    toolchain::read_compiled_rustc_version(&PathBuf::from(path))
+                        == Some(rustc_version.clone())
+                })
+                .collect()
+        } else {
+            candidates.iter().collect()
+        };
+        if compiled_path.is_empty() {
+            // None of the compiled files was built with our version of the
+            // compiler: we report all of them
+            for path in candidates {
+                let version = toolchain::read_compiled_rustc_version(&PathBuf::from(path))
+                    .unwrap_or_else(|| "an unknown version".to_string());
+                mismatches.push((dep.clone(), path.clone(), version));
+            }
+            continue;
+        }
+        if compiled_path.len() != 1 {
+            error!("Found several compiled library files for the same external dependency ({:?}). You may want to clean the target directory (`rm \"{:?}/*\"`) then rebuild the project with `{}`",
+                    dep, deps_dir, target_kind.build_command());
+            return Err(());
+        }
+        let compiled_path = compiled_path[0];
+
+        // Check the version of the compiler used to build the library.
+        // Note that we can't read the version for some files (.so files):
+        // we ignore those.
+        match toolchain::read_compiled_rustc_version(&PathBuf::from(compiled_path)) {
+            Some(version) if version != rustc_version => {
+                mismatches.push((dep.clone(), compiled_path.clone(), version));
+            }
+            _ => (),
+        }
+
+        args.push("--extern".to_string());
+        args.push(format!("{}={}", dep, compiled_path).to_string());
+    }
+
+    // Report the version mismatches, if there are
+    if !mismatches.is_empty() {
+        let mismatches: Vec<String> = mismatches
+            .iter()
+            .map(|(dep, path, version)| format!("  - {} ({}): built with {}", dep, path, version))
+            .collect();
+        error!(
+            "Some external dependencies were built with a different version of the compiler than the one used by Charon ({}, toolchain {}):\n{}\n\nYou can either rebuild the project with the proper toolchain (`cargo +{} build`), or call Charon with `--rebuild-deps` to build the dependencies in a private target directory.",
+            rustc_version,
+            toolchain::pinned_toolchain(),
+            mismatches.join("\n"),
+            toolchain::pinned_toolchain()
+        );
+        return Err(());
+    }
+
+    // Return
+    trace!("Args vec: {:?}", args);
+    Ok((manifest, tgt_package, args))
+}
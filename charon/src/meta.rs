@@ -0,0 +1,187 @@
+//! Meta-information about the translated programs: the source locations of
+//! the declarations and statements, and the attributes of the declarations.
+#![allow(dead_code)]
+
+use macros::generate_index_type;
+use rustc_ast::ast::{AttrKind, MacArgs};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::source_map::SourceMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+generate_index_type!(FileId);
+
+/// A location in a source file.
+/// Lines start at 1, columns start at 0 (this is the convention used by rustc).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Loc {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// A span of source code. The file is an index in the file table of the
+/// exported crate (see [FileTable]).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub file: FileId::Id,
+    pub beg: Loc,
+    pub end: Loc,
+}
+
+impl Span {
+    /// Return the smallest span containing both spans.
+    /// If the spans are not in the same file, we return the first one.
+    pub fn merge(&self, other: &Span) -> Span {
+        if self.file != other.file {
+            return *self;
+        }
+        Span {
+            file: self.file,
+            beg: std::cmp::min(self.beg, other.beg),
+            end: std::cmp::max(self.end, other.end),
+        }
+    }
+}
+
+/// The table of the files referenced by the spans.
+///
+/// The files are registered the first time a span refers to them: we don't
+/// export the whole content of the source map, which contains all the files
+/// of the external crates. We use a `RefCell` so that the translation contexts
+/// can share the table.
+pub struct FileTable {
+    files: RefCell<FileId::Vector<String>>,
+    ids: RefCell<HashMap<String, FileId::Id>>,
+}
+
+impl FileTable {
+    pub fn new() -> FileTable {
+        FileTable {
+            files: RefCell::new(FileId::Vector::new()),
+            ids: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Return the id of a file, registering it if necessary
+    pub fn get_id(&self, file: String) -> FileId::Id {
+        if let Some(id) = self.ids.borrow().get(&file) {
+            return *id;
+        }
+        let mut files = self.files.borrow_mut();
+        let id = FileId::Id::new(files.len());
+        files.push_back(file.clone());
+        self.ids.borrow_mut().insert(file, id);
+        id
+    }
+
+    /// Return a copy of the files registered so far
+    pub fn files(&self) -> FileId::Vector<String> {
+        self.files.borrow().clone()
+    }
+
+    pub fn into_files(self) -> FileId::Vector<String> {
+        self.files.into_inner()
+    }
+}
+
+/// Convert a file name to a string. See [crate::common::span_to_string]:
+/// for the remapped paths (for instance, paths into libstd), we use the
+/// local path if there is one.
+fn file_name_to_string(name: &rustc_span::FileName) -> String {
+    match name {
+        rustc_span::FileName::Real(rustc_span::RealFileName::LocalPath(path)) => {
+            path.to_string_lossy().to_string()
+        }
+        rustc_span::FileName::Real(rustc_span::RealFileName::Remapped {
+            local_path,
+            virtual_name,
+        }) => match local_path {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => virtual_name.to_string_lossy().to_string(),
+        },
+        // The other file names don't refer to files on the user's file system
+        // (expanded macros, command line, etc.)
+        _ => format!("{}", name.prefer_local()),
+    }
+}
+
+/// Translate a rustc span, and register its file in the file table
+pub fn translate_span(source_map: &SourceMap, files: &FileTable, span: rustc_span::Span) -> Span {
+    let beg = source_map.lookup_char_pos(span.lo());
+    let end = source_map.lookup_char_pos(span.hi());
+    let file = files.get_id(file_name_to_string(&beg.file.name));
+    Span {
+        file,
+        beg: Loc {
+            line: beg.line,
+            col: beg.col.0,
+        },
+        end: Loc {
+            line: end.line,
+            col: end.col.0,
+        },
+    }
+}
+
+/// The namespaces of the tool attributes we export (`#[charon::opaque]`,
+/// `#[aeneas::rename("foo")]`, etc.). The other attributes are ignored.
+///
+/// Note that rustc rejects the attributes in unknown namespaces: the crate
+/// must register them with `#![feature(register_tool)]` and
+/// `#![register_tool(charon, aeneas)]`.
+pub const TOOL_ATTRIBUTE_NAMESPACES: [&str; 2] = ["charon", "aeneas"];
+
+/// An attribute of a declaration, which we forward to the backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Attribute {
+    /// A doc comment (`/// ...`, `/** ... */` or `#[doc = "..."]`).
+    /// We keep the text as it is, including the leading spaces.
+    DocComment(String),
+    /// A tool attribute in one of the [TOOL_ATTRIBUTE_NAMESPACES]: `path`
+    /// contains the segments of the attribute name (`["charon", "opaque"]`)
+    /// and `args` the arguments, as they appear in the source (`"(\"foo\")"`,
+    /// `"= 3"`), if there are some.
+    Tool {
+        path: Vec<String>,
+        args: Option<String>,
+    },
+}
+
+/// Translate the attributes of a definition. We only keep the doc comments
+/// and the tool attributes in the [TOOL_ATTRIBUTE_NAMESPACES].
+pub fn translate_attributes(tcx: TyCtxt, def_id: DefId) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    for attr in tcx.get_attrs(def_id).iter() {
+        if let Some(doc) = attr.doc_str() {
+            attributes.push(Attribute::DocComment(doc.to_string()));
+            continue;
+        }
+        match &attr.kind {
+            AttrKind::Normal(item, _) => {
+                let path: Vec<String> = item
+                    .path
+                    .segments
+                    .iter()
+                    .map(|seg| seg.ident.to_string())
+                    .collect();
+                if path.len() < 2 || !TOOL_ATTRIBUTE_NAMESPACES.contains(&path[0].as_str()) {
+                    continue;
+                }
+                let args = match &item.args {
+                    MacArgs::Empty => None,
+                    MacArgs::Delimited(..) | MacArgs::Eq(..) => Some(
+                        rustc_ast_pretty::pprust::tts_to_string(&item.args.outer_tokens()),
+                    ),
+                };
+                attributes.push(Attribute::Tool { path, args });
+            }
+            AttrKind::DocComment(..) => {
+                // Already handled by [doc_str]
+                unreachable!()
+            }
+        }
+    }
+    attributes
+}
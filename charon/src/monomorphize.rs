@@ -0,0 +1,759 @@
+//! Monomorphize the crate, for the backends which don't support polymorphism.
+//!
+//! We start from a set of roots: the non-generic functions selected by the
+//! user with name patterns (see [crate::opacity] for the syntax of the
+//! patterns), or, by default, all the non-generic functions and types. We then
+//! collect the instantiations of the generic declarations used by the roots,
+//! through the type arguments of the calls (`Call.type_args`) and of the ADTs
+//! (`Ty::Adt`), and generate one declaration per instantiation, without type
+//! parameters. For instance, if `foo<T>` is called with `T = u32`, we generate
+//! a function `foo_u32`, and we replace the calls to `foo::<u32>` with calls to
+//! `foo_u32`. The instantiations are collected transitively, and the
+//! declarations which are not reachable from the roots are dropped.
+//!
+//! The ids of the declarations change: we recompute the declaration groups on
+//! the monomorphized declarations (see [crate::reorder_decls]).
+//!
+//! We don't support instantiating type parameters with types containing
+//! borrows (for instance, `Wrapper<&'a u32>`): the instances would need new
+//! region parameters.
+//!
+//! See the `test-monomorphize` target of the Makefile for the tests.
+
+use crate::common::*;
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::im_ast::{FunDeclId, FunId, FunSig};
+use crate::llbc_ast::{
+    Assert, Call, FunDecl, FunDecls, Loop, LoopInfo, RawStatement, Statement, SwitchTargets, Try,
+};
+use crate::names::{Name, PathElem};
+use crate::opacity::NamePattern;
+use crate::reorder_decls::{group_declarations, GDeclarationGroup};
+use crate::rust_to_local_ids::{DeclarationGroup, OrderedDecls};
+use crate::types::*;
+use crate::types_utils::integer_ty_to_string;
+use crate::values::*;
+use im::Vector;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A declaration of the monomorphized crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum AnyDeclId {
+    Type(TypeDeclId::Id),
+    Fun(FunDeclId::Id),
+}
+
+/// Convert a type without regions nor type variables (an instantiation of a
+/// type parameter)
+fn lift_ty<R: Clone + Eq>(ty: &ETy) -> Ty<R> {
+    match ty {
+        Ty::Adt(id, regions, tys) => {
+            assert!(regions.is_empty());
+            Ty::Adt(*id, Vector::new(), tys.iter().map(lift_ty).collect())
+        }
+        Ty::Bool => Ty::Bool,
+        Ty::Char => Ty::Char,
+        Ty::Never => Ty::Never,
+        Ty::Integer(k) => Ty::Integer(*k),
+        Ty::Str => Ty::Str,
+        Ty::Array(ty) => Ty::Array(Box::new(lift_ty(ty))),
+        Ty::Slice(ty) => Ty::Slice(Box::new(lift_ty(ty))),
+        Ty::TypeVar(_) | Ty::Ref(_, _, _) => unreachable!(),
+    }
+}
+
+/// Substitute the type parameters of a declaration with their instantiation
+fn subst_ty<R: Copy + Eq>(args: &Vector<ETy>, ty: &Ty<R>) -> Ty<R> {
+    ty.substitute(&|r| *r, &|id| lift_ty(&args[id.to_usize()]))
+}
+
+struct Ctx<'a> {
+    type_defs: &'a TypeDecls,
+    fun_defs: &'a FunDecls,
+    /// For every generic type declaration, its instances: the type arguments
+    /// and the id of the monomorphized declaration
+    type_instances: HashMap<TypeDeclId::Id, Vec<(Vector<ETy>, TypeDeclId::Id)>>,
+    fun_instances: HashMap<FunDeclId::Id, Vec<(Vector<ETy>, FunDeclId::Id)>>,
+    /// The instances we still have to generate, in the order of their ids
+    type_queue: VecDeque<(TypeDeclId::Id, Vector<ETy>, TypeDeclId::Id)>,
+    fun_queue: VecDeque<(FunDeclId::Id, Vector<ETy>, FunDeclId::Id)>,
+    type_counter: TypeDeclId::Generator,
+    fun_counter: FunDeclId::Generator,
+    /// The dependencies of the instance we are currently generating
+    deps: Vec<AnyDeclId>,
+}
+
+impl<'a> Ctx<'a> {
+    fn add_dep(&mut self, id: AnyDeclId) {
+        if !self.deps.contains(&id) {
+            self.deps.push(id);
+        }
+    }
+
+    fn check_no_regions(&self, name: &Name, args: &Vector<ETy>) -> Result<()> {
+        if args.iter().any(|ty| ty.contains_regions()) {
+            let args: Vec<String> = args.iter().map(|ty| self.mangle_ty(ty)).collect();
+            error!(
+                "Can't monomorphize {} with the type arguments [{}]: instantiating type parameters with types containing borrows is not supported",
+                name,
+                args.join(", ")
+            );
+            return Err(());
+        }
+        Ok(())
+    }
+
+    /// Lookup or register an instance of a type declaration
+    fn type_instance(&mut self, id: TypeDeclId::Id, args: Vector<ETy>) -> Result<TypeDeclId::Id> {
+        self.check_no_regions(&self.type_defs.get_type_def(id).unwrap().name, &args)?;
+        let instances = self.type_instances.entry(id).or_insert_with(Vec::new);
+        let nid = match instances.iter().find(|(iargs, _)| *iargs == args) {
+            Option::Some((_, nid)) => *nid,
+            Option::None => {
+                let nid = self.type_counter.fresh_id();
+                instances.push((args.clone(), nid));
+                self.type_queue.push_back((id, args, nid));
+                nid
+            }
+        };
+        self.add_dep(AnyDeclId::Type(nid));
+        Ok(nid)
+    }
+
+    /// Lookup or register an instance of a function declaration
+    fn fun_instance(&mut self, id: FunDeclId::Id, args: Vector<ETy>) -> Result<FunDeclId::Id> {
+        self.check_no_regions(&self.fun_defs.get(id).unwrap().name, &args)?;
+        let instances = self.fun_instances.entry(id).or_insert_with(Vec::new);
+        let nid = match instances.iter().find(|(iargs, _)| *iargs == args) {
+            Option::Some((_, nid)) => *nid,
+            Option::None => {
+                let nid = self.fun_counter.fresh_id();
+                instances.push((args.clone(), nid));
+                self.fun_queue.push_back((id, args, nid));
+                nid
+            }
+        };
+        self.add_dep(AnyDeclId::Fun(nid));
+        Ok(nid)
+    }
+
+    /// The string we use to identify a type argument in the mangled names. The
+    /// ADTs are identified by their full paths (see [mangle_path]), and the
+    /// tuples by their arity, so that different types give different strings.
+    fn mangle_ty(&self, ty: &ETy) -> String {
+        let mangle_args = |ident: String, tys: &Vector<ETy>| -> String {
+            let mut elems = vec![ident];
+            elems.extend(tys.iter().map(|ty| self.mangle_ty(ty)));
+            elems.join("_")
+        };
+        match ty {
+            Ty::Adt(TypeId::Adt(id), _, tys) => {
+                let name = &self.type_defs.get_type_def(*id).unwrap().name;
+                mangle_args(mangle_path(name), tys)
+            }
+            Ty::Adt(TypeId::Tuple, _, tys) => {
+                if tys.is_empty() {
+                    "unit".to_string()
+                } else {
+                    mangle_args(format!("Tuple{}", tys.len()), tys)
+                }
+            }
+            Ty::Adt(TypeId::Assumed(aty), _, tys) => mangle_args(format!("{:?}", aty), tys),
+            Ty::TypeVar(id) => format!("T{}", id.to_usize()),
+            Ty::Bool => "bool".to_string(),
+            Ty::Char => "char".to_string(),
+            Ty::Never => "never".to_string(),
+            Ty::Integer(k) => integer_ty_to_string(*k),
+            Ty::Str => "str".to_string(),
+            Ty::Array(ty) => format!("Array_{}", self.mangle_ty(ty)),
+            Ty::Slice(ty) => format!("Slice_{}", self.mangle_ty(ty)),
+            Ty::Ref(_, ty, RefKind::Shared) => format!("Ref_{}", self.mangle_ty(ty)),
+            Ty::Ref(_, ty, RefKind::Mut) => format!("RefMut_{}", self.mangle_ty(ty)),
+        }
+    }
+
+    /// The name of an instance: we append the type arguments to the last
+    /// identifier of the name (`foo` instantiated with `u32` and `bool` gives
+    /// `foo_u32_bool`, and instantiated with `mycrate::a::Foo` gives
+    /// `foo_mycrate__a__Foo`). We check that the names of the instances are
+    /// unique once all of them have been generated (see [check_unique_names]).
+    fn mangle_name(&self, name: &Name, args: &Vector<ETy>) -> Name {
+        if args.is_empty() {
+            return name.clone();
+        }
+        let mut name = name.clone();
+        let ident = name
+            .name
+            .iter_mut()
+            .rev()
+            .find_map(|elem| match elem {
+                PathElem::Ident(ident) => Some(ident),
+                PathElem::Disambiguator(_) => None,
+            })
+            .unwrap();
+        let mut elems = vec![ident.clone()];
+        elems.extend(args.iter().map(|ty| self.mangle_ty(ty)));
+        *ident = elems.join("_");
+        name
+    }
+
+    /// Replace the references to generic ADTs with references to their
+    /// instances. The type must not contain type variables.
+    fn ty<R: Copy + Eq>(&mut self, ty: &Ty<R>) -> Result<Ty<R>> {
+        Ok(match ty {
+            Ty::Adt(TypeId::Adt(id), regions, tys) => {
+                let args = tys.iter().map(|ty| ty.erase_regions()).collect();
+                let nid = self.type_instance(*id, args)?;
+                Ty::Adt(TypeId::Adt(nid), regions.clone(), Vector::new())
+            }
+            Ty::Adt(id, regions, tys) => {
+                let tys = tys
+                    .iter()
+                    .map(|ty| self.ty(ty))
+                    .collect::<Result<Vector<Ty<R>>>>()?;
+                Ty::Adt(*id, regions.clone(), tys)
+            }
+            Ty::TypeVar(_) => unreachable!(),
+            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => ty.clone(),
+            Ty::Array(ty) => Ty::Array(Box::new(self.ty(ty)?)),
+            Ty::Slice(ty) => Ty::Slice(Box::new(self.ty(ty)?)),
+            Ty::Ref(r, ty, kind) => Ty::Ref(*r, Box::new(self.ty(ty)?), *kind),
+        })
+    }
+
+    fn places(&mut self, locals: &[ETy], places: &[Place]) -> Result<Vec<Place>> {
+        places.iter().map(|p| self.place(locals, p)).collect()
+    }
+
+    /// [locals] gives the types of the local variables, once instantiated
+    /// (but before replacing the generic ADTs with their instances): we need
+    /// them to retrieve the instances of the ADTs we project from.
+    fn place(&mut self, locals: &[ETy], p: &Place) -> Result<Place> {
+        let mut ty = locals[p.var_id.to_usize()].clone();
+        let mut projection = Projection::new();
+        for pe in &p.projection {
+            let (pe, pty) = match (pe, &ty) {
+                (ProjectionElem::Deref, Ty::Ref(_, ty, _)) => (pe.clone(), (**ty).clone()),
+                (ProjectionElem::DerefBox, Ty::Adt(TypeId::Assumed(AssumedTy::Box), _, tys)) => {
+                    (pe.clone(), tys[0].clone())
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::Adt(id, variant_id), field_id),
+                    Ty::Adt(TypeId::Adt(_), _, tys),
+                ) => {
+                    let nid = self.type_instance(*id, tys.clone())?;
+                    let def = self.type_defs.get_type_def(*id).unwrap();
+                    let field_ty =
+                        def.get_erased_regions_instantiated_field_type(*variant_id, tys, *field_id);
+                    let pe = ProjectionElem::Field(FieldProjKind::Adt(nid, *variant_id), *field_id);
+                    (pe, field_ty)
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::Option(_), _),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Option), _, tys),
+                ) => (pe.clone(), tys[0].clone()),
+                (
+                    ProjectionElem::Field(FieldProjKind::Tuple(_), field_id),
+                    Ty::Adt(TypeId::Tuple, _, tys),
+                ) => (pe.clone(), tys[field_id.to_usize()].clone()),
+                _ => unreachable!(),
+            };
+            projection.push_back(pe);
+            ty = pty;
+        }
+        Ok(Place {
+            var_id: p.var_id,
+            projection,
+        })
+    }
+
+    fn operand(&mut self, args: &Vector<ETy>, locals: &[ETy], op: &Operand) -> Result<Operand> {
+        Ok(match op {
+            Operand::Copy(p) => Operand::Copy(self.place(locals, p)?),
+            Operand::Move(p) => Operand::Move(self.place(locals, p)?),
+            Operand::Constant(ty, cv) => {
+                Operand::Constant(self.ty(&subst_ty(args, ty))?, cv.clone())
+            }
+            Operand::Expr(ty, rv) => Operand::Expr(
+                self.ty(&subst_ty(args, ty))?,
+                Box::new(self.rvalue(args, locals, rv)?),
+            ),
+        })
+    }
+
+    fn operands(
+        &mut self,
+        args: &Vector<ETy>,
+        locals: &[ETy],
+        ops: &[Operand],
+    ) -> Result<Vec<Operand>> {
+        ops.iter()
+            .map(|op| self.operand(args, locals, op))
+            .collect()
+    }
+
+    fn rvalue(&mut self, args: &Vector<ETy>, locals: &[ETy], rv: &Rvalue) -> Result<Rvalue> {
+        Ok(match rv {
+            Rvalue::Use(op) => Rvalue::Use(self.operand(args, locals, op)?),
+            Rvalue::Ref(p, kind) => Rvalue::Ref(self.place(locals, p)?, *kind),
+            Rvalue::UnaryOp(unop, op) => Rvalue::UnaryOp(*unop, self.operand(args, locals, op)?),
+            Rvalue::BinaryOp(binop, op1, op2) => Rvalue::BinaryOp(
+                *binop,
+                self.operand(args, locals, op1)?,
+                self.operand(args, locals, op2)?,
+            ),
+            Rvalue::CheckedBinaryOp(binop, op1, op2) => Rvalue::CheckedBinaryOp(
+                *binop,
+                self.operand(args, locals, op1)?,
+                self.operand(args, locals, op2)?,
+            ),
+            Rvalue::Discriminant(p) => Rvalue::Discriminant(self.place(locals, p)?),
+            Rvalue::Aggregate(kind, ops) => {
+                let kind = match kind {
+                    AggregateKind::Tuple => AggregateKind::Tuple,
+                    AggregateKind::Option(variant_id, ty) => {
+                        AggregateKind::Option(*variant_id, self.ty(&subst_ty(args, ty))?)
+                    }
+                    AggregateKind::Adt(id, variant_id, regions, tys) => {
+                        let tys = tys.iter().map(|ty| subst_ty(args, ty)).collect();
+                        let nid = self.type_instance(*id, tys)?;
+                        AggregateKind::Adt(nid, *variant_id, regions.clone(), Vec::new())
+                    }
+                };
+                Rvalue::Aggregate(kind, self.operands(args, locals, ops)?)
+            }
+        })
+    }
+
+    fn statement(
+        &mut self,
+        args: &Vector<ETy>,
+        locals: &[ETy],
+        st: &Statement,
+    ) -> Result<Statement> {
+        let content = match &st.content {
+            RawStatement::Assign(p, rv) => {
+                RawStatement::Assign(self.place(locals, p)?, self.rvalue(args, locals, rv)?)
+            }
+            RawStatement::FakeRead(p) => RawStatement::FakeRead(self.place(locals, p)?),
+            RawStatement::SetDiscriminant(p, variant_id) => {
+                RawStatement::SetDiscriminant(self.place(locals, p)?, *variant_id)
+            }
+            RawStatement::Drop(p) => RawStatement::Drop(self.place(locals, p)?),
+            RawStatement::Assert(assert) => RawStatement::Assert(Assert {
+                cond: self.operand(args, locals, &assert.cond)?,
+                expected: assert.expected,
+            }),
+            RawStatement::Call(call) => {
+                let type_args: Vec<ETy> =
+                    call.type_args.iter().map(|ty| subst_ty(args, ty)).collect();
+                let (func, type_args) = match &call.func {
+                    FunId::Regular(id) => {
+                        let nid = self.fun_instance(*id, Vector::from(type_args))?;
+                        (FunId::Regular(nid), Vec::new())
+                    }
+                    FunId::Assumed(_) => {
+                        let type_args = type_args
+                            .iter()
+                            .map(|ty| self.ty(ty))
+                            .collect::<Result<Vec<ETy>>>()?;
+                        (call.func.clone(), type_args)
+                    }
+                };
+                RawStatement::Call(Call {
+                    func,
+                    region_args: call.region_args.clone(),
+                    type_args,
+                    args: self.operands(args, locals, &call.args)?,
+                    dest: self.place(locals, &call.dest)?,
+                })
+            }
+            RawStatement::Try(t) => RawStatement::Try(Try {
+                op: self.operand(args, locals, &t.op)?,
+                dest: self.place(locals, &t.dest)?,
+                drops: t
+                    .drops
+                    .iter()
+                    .map(|p| self.place(locals, p))
+                    .collect::<Result<Vec<Place>>>()?,
+            }),
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop => st.content.clone(),
+            RawStatement::Sequence(st1, st2) => RawStatement::Sequence(
+                Box::new(self.statement(args, locals, st1)?),
+                Box::new(self.statement(args, locals, st2)?),
+            ),
+            RawStatement::Switch(op, targets) => {
+                let op = self.operand(args, locals, op)?;
+                let targets = match targets {
+                    SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                        Box::new(self.statement(args, locals, st1)?),
+                        Box::new(self.statement(args, locals, st2)?),
+                    ),
+                    SwitchTargets::SwitchInt(int_ty, branches, otherwise) => {
+                        let branches = branches
+                            .iter()
+                            .map(|(values, st)| -> Result<(Vec<ScalarValue>, Statement)> {
+                                Ok((values.clone(), self.statement(args, locals, st)?))
+                            })
+                            .collect::<Result<Vec<(Vec<ScalarValue>, Statement)>>>()?;
+                        let otherwise = self.statement(args, locals, otherwise)?;
+                        SwitchTargets::SwitchInt(*int_ty, branches, Box::new(otherwise))
+                    }
+                };
+                RawStatement::Switch(op, targets)
+            }
+            RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+                body: Box::new(self.statement(args, locals, &loop_.body)?),
+                inputs: loop_.inputs.clone(),
+                outputs: loop_.outputs.clone(),
+                info: match &loop_.info {
+                    Option::None => Option::None,
+                    Option::Some(info) => Option::Some(LoopInfo {
+                        borrows: self.places(locals, &info.borrows)?,
+                        live_at_break: self.places(locals, &info.live_at_break)?,
+                        ..info.clone()
+                    }),
+                },
+            }),
+        };
+        Ok(Statement {
+            dataflow: st.dataflow.clone(),
+            ..Statement::new(st.span, content)
+        })
+    }
+
+    fn fields(
+        &mut self,
+        args: &Vector<ETy>,
+        fields: &FieldId::Vector<Field>,
+    ) -> Result<FieldId::Vector<Field>> {
+        let mut nfields = FieldId::Vector::new();
+        for field in fields.iter() {
+            nfields.push_back(Field {
+                ty: self.ty(&subst_ty(args, &field.ty))?,
+                ..field.clone()
+            });
+        }
+        Ok(nfields)
+    }
+
+    fn type_decl(
+        &mut self,
+        id: TypeDeclId::Id,
+        args: &Vector<ETy>,
+        nid: TypeDeclId::Id,
+    ) -> Result<TypeDecl> {
+        let type_defs = self.type_defs;
+        let def = type_defs.get_type_def(id).unwrap();
+        let kind = match &def.kind {
+            TypeDeclKind::Struct(fields) => TypeDeclKind::Struct(self.fields(args, fields)?),
+            TypeDeclKind::Enum(variants) => {
+                let mut nvariants = VariantId::Vector::new();
+                for variant in variants.iter() {
+                    nvariants.push_back(Variant {
+                        fields: self.fields(args, &variant.fields)?,
+                        ..variant.clone()
+                    });
+                }
+                TypeDeclKind::Enum(nvariants)
+            }
+            TypeDeclKind::Opaque => TypeDeclKind::Opaque,
+        };
+        Ok(TypeDecl {
+            def_id: nid,
+            name: self.mangle_name(&def.name, args),
+            type_params: TypeVarId::Vector::new(),
+            kind,
+            ..def.clone()
+        })
+    }
+
+    fn fun_decl(
+        &mut self,
+        id: FunDeclId::Id,
+        args: &Vector<ETy>,
+        nid: FunDeclId::Id,
+    ) -> Result<FunDecl> {
+        let fun_defs = self.fun_defs;
+        let def = fun_defs.get(id).unwrap();
+        let inputs = def
+            .signature
+            .inputs
+            .iter()
+            .map(|ty| self.ty(&subst_ty(args, ty)))
+            .collect::<Result<Vec<RTy>>>()?;
+        let output = self.ty(&subst_ty(args, &def.signature.output))?;
+        let body = match &def.body {
+            Option::None => Option::None,
+            Option::Some(body) => {
+                let locals: Vec<ETy> = body
+                    .locals
+                    .iter()
+                    .map(|var| subst_ty(args, &var.ty))
+                    .collect();
+                let mut nlocals = body.locals.clone();
+                for (var, ty) in nlocals.iter_mut().zip(locals.iter()) {
+                    var.ty = self.ty(ty)?;
+                }
+                let nbody = self.statement(args, &locals, &body.body)?;
+                let mut body = body.clone();
+                body.locals = nlocals;
+                body.body = nbody;
+                Option::Some(body)
+            }
+        };
+        Ok(FunDecl {
+            def_id: nid,
+            name: self.mangle_name(&def.name, args),
+            signature: FunSig {
+                type_params: TypeVarId::Vector::new(),
+                inputs,
+                output,
+                ..def.signature.clone()
+            },
+            body,
+            ..def.clone()
+        })
+    }
+}
+
+/// Split a group of declarations between the types and the functions. Note
+/// that a group can't mix types and functions: the types don't depend on the
+/// functions.
+fn to_declaration_group(group: GDeclarationGroup<AnyDeclId>) -> DeclarationGroup {
+    let type_id = |id| match id {
+        AnyDeclId::Type(id) => id,
+        AnyDeclId::Fun(_) => unreachable!(),
+    };
+    let fun_id = |id| match id {
+        AnyDeclId::Fun(id) => id,
+        AnyDeclId::Type(_) => unreachable!(),
+    };
+    match group {
+        GDeclarationGroup::NonRec(AnyDeclId::Type(id)) => {
+            DeclarationGroup::Type(GDeclarationGroup::NonRec(id))
+        }
+        GDeclarationGroup::NonRec(AnyDeclId::Fun(id)) => {
+            DeclarationGroup::Fun(GDeclarationGroup::NonRec(id))
+        }
+        GDeclarationGroup::Rec(ids) => match ids[0] {
+            AnyDeclId::Type(_) => DeclarationGroup::Type(GDeclarationGroup::Rec(
+                ids.into_iter().map(type_id).collect(),
+            )),
+            AnyDeclId::Fun(_) => DeclarationGroup::Fun(GDeclarationGroup::Rec(
+                ids.into_iter().map(fun_id).collect(),
+            )),
+        },
+    }
+}
+
+/// The full path of a declaration, as used in the mangled names: the elements
+/// of the path are separated by `__` (`mycrate::a::Foo` gives
+/// `mycrate__a__Foo`).
+fn mangle_path(name: &Name) -> String {
+    let elems: Vec<String> = name.name.iter().map(|elem| elem.to_string()).collect();
+    elems.join("__")
+}
+
+/// Check that the declarations have different names: the mangling could in
+/// theory make two instances collide (if the identifiers contain `__`, for
+/// instance), or make an instance collide with a non-generic declaration.
+fn check_unique_names<'b>(kind: &str, names: impl Iterator<Item = &'b Name>) -> Result<()> {
+    let mut seen: HashSet<String> = HashSet::new();
+    for name in names {
+        let name = name.to_string();
+        if !seen.insert(name.clone()) {
+            error!(
+                "Could not monomorphize the crate: several {} declarations are named {}",
+                kind, name
+            );
+            return Err(());
+        }
+    }
+    Ok(())
+}
+
+/// Monomorphize the declarations. [roots] contains the patterns selecting the
+/// roots: if it is empty, all the non-generic declarations are roots.
+pub fn monomorphize(
+    crate_name: &str,
+    roots: &Vec<String>,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+) -> Result<(OrderedDecls, TypeDecls, FunDecls)> {
+    let mut ctx = Ctx {
+        type_defs,
+        fun_defs,
+        type_instances: HashMap::new(),
+        fun_instances: HashMap::new(),
+        type_queue: VecDeque::new(),
+        fun_queue: VecDeque::new(),
+        type_counter: TypeDeclId::Generator::new(),
+        fun_counter: FunDeclId::Generator::new(),
+        deps: Vec::new(),
+    };
+
+    // Register the roots, in the order of the declarations
+    let patterns = roots
+        .iter()
+        .map(|root| NamePattern::parse(crate_name, root))
+        .collect::<Result<Vec<NamePattern>>>()?;
+    let mut used_patterns: HashSet<usize> = HashSet::new();
+    for group in &ordered_decls.decls {
+        match group {
+            DeclarationGroup::Type(group) => {
+                for id in group.get_ids() {
+                    let def = type_defs.get_type_def(id).unwrap();
+                    if patterns.is_empty() && def.type_params.is_empty() {
+                        ctx.type_instance(id, Vector::new())?;
+                    }
+                }
+            }
+            DeclarationGroup::Fun(group) => {
+                for id in group.get_ids() {
+                    let def = fun_defs.get(id).unwrap();
+                    if !def.signature.type_params.is_empty() {
+                        continue;
+                    }
+                    let mut is_root = patterns.is_empty();
+                    for (i, pattern) in patterns.iter().enumerate() {
+                        if pattern.matches_name(&def.name) {
+                            used_patterns.insert(i);
+                            is_root = true;
+                        }
+                    }
+                    if is_root {
+                        ctx.fun_instance(id, Vector::new())?;
+                    }
+                }
+            }
+        }
+    }
+    for (i, pattern) in patterns.iter().enumerate() {
+        if !used_patterns.contains(&i) {
+            error!(
+                "The monomorphization root {:?} doesn't select any non-generic function",
+                pattern.source
+            );
+            return Err(());
+        }
+    }
+
+    // Generate the instances, collecting the new instances as we go. We
+    // generate the instances in the order of their ids.
+    let mut types: TypeDeclId::Vector<TypeDecl> = TypeDeclId::Vector::new();
+    let mut funs: FunDecls = FunDeclId::Vector::new();
+    let mut origins: HashMap<AnyDeclId, (AnyDeclId, bool)> = HashMap::new();
+    let mut deps: HashMap<AnyDeclId, Vec<AnyDeclId>> = HashMap::new();
+    loop {
+        ctx.deps = Vec::new();
+        let (nid, origin) = if let Some((id, args, nid)) = ctx.type_queue.pop_front() {
+            let def = ctx.type_decl(id, &args, nid)?;
+            assert!(nid.to_usize() == types.len());
+            types.push_back(def);
+            (AnyDeclId::Type(nid), (AnyDeclId::Type(id), args.is_empty()))
+        } else if let Some((id, args, nid)) = ctx.fun_queue.pop_front() {
+            let def = ctx.fun_decl(id, &args, nid)?;
+            assert!(nid.to_usize() == funs.len());
+            funs.push_back(def);
+            (AnyDeclId::Fun(nid), (AnyDeclId::Fun(id), args.is_empty()))
+        } else {
+            break;
+        };
+        origins.insert(nid, origin);
+        deps.insert(nid, std::mem::take(&mut ctx.deps));
+    }
+    check_unique_names("type", types.iter().map(|def| &def.name))?;
+    check_unique_names("function", funs.iter().map(|def| &def.name))?;
+
+    // Recompute the declaration groups. We try to preserve the order of the
+    // original declarations.
+    let mut positions: HashMap<AnyDeclId, usize> = HashMap::new();
+    for group in &ordered_decls.decls {
+        let ids: Vec<AnyDeclId> = match group {
+            DeclarationGroup::Type(group) => {
+                group.get_ids().into_iter().map(AnyDeclId::Type).collect()
+            }
+            DeclarationGroup::Fun(group) => {
+                group.get_ids().into_iter().map(AnyDeclId::Fun).collect()
+            }
+        };
+        for id in ids {
+            let pos = positions.len();
+            positions.insert(id, pos);
+        }
+    }
+    let mut ids: Vec<AnyDeclId> = origins.keys().copied().collect();
+    ids.sort_by_key(|id| (*positions.get(&origins.get(id).unwrap().0).unwrap(), *id));
+    let groups = group_declarations(&ids, &|id| deps.get(&id).unwrap().clone());
+    let decls = groups.into_iter().map(to_declaration_group).collect();
+
+    // Compute the other tables. The instances of generic declarations share
+    // the rustc identifier of their origin, but only the non-generic
+    // declarations are in the maps from rustc identifiers.
+    let mut new_decls = OrderedDecls {
+        decls,
+        opaque_types: HashSet::new(),
+        opaque_funs: HashSet::new(),
+        type_rid_to_id: HashMap::new(),
+        type_id_to_rid: HashMap::new(),
+        fun_rid_to_id: HashMap::new(),
+        fun_id_to_rid: HashMap::new(),
+    };
+    for (nid, (id, non_generic)) in &origins {
+        match (nid, id) {
+            (AnyDeclId::Type(nid), AnyDeclId::Type(id)) => {
+                if ordered_decls.opaque_types.contains(id) {
+                    new_decls.opaque_types.insert(*nid);
+                }
+                let rid = *ordered_decls.type_id_to_rid.get(id).unwrap();
+                new_decls.type_id_to_rid.insert(*nid, rid);
+                if *non_generic {
+                    new_decls.type_rid_to_id.insert(rid, *nid);
+                }
+            }
+            (AnyDeclId::Fun(nid), AnyDeclId::Fun(id)) => {
+                if ordered_decls.opaque_funs.contains(id) {
+                    new_decls.opaque_funs.insert(*nid);
+                }
+                let rid = *ordered_decls.fun_id_to_rid.get(id).unwrap();
+                new_decls.fun_id_to_rid.insert(*nid, rid);
+                if *non_generic {
+                    new_decls.fun_rid_to_id.insert(rid, *nid);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok((new_decls, TypeDecls { types }, funs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monomorphize::*;
+
+    fn name(path: &str) -> Name {
+        Name::from(path.split("::").map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_mangle_path() {
+        assert!(mangle_path(&name("mycrate::a::Foo")) == "mycrate__a__Foo");
+        assert!(mangle_path(&name("mycrate::a::Foo")) != mangle_path(&name("mycrate::b::Foo")));
+    }
+
+    #[test]
+    fn test_check_unique_names() {
+        let names = vec![name("mycrate::f_u32"), name("mycrate::g_u32")];
+        assert!(check_unique_names("function", names.iter()).is_ok());
+        let names = vec![name("mycrate::f_u32"), name("mycrate::f_u32")];
+        assert!(check_unique_names("function", names.iter()).is_err());
+    }
+}
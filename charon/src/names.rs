@@ -4,12 +4,12 @@
 pub use crate::names_utils::*;
 use macros::generate_index_type;
 use macros::EnumIsA;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 generate_index_type!(Disambiguator);
 
 /// See the comments for [Name]
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, EnumIsA)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, EnumIsA)]
 pub enum PathElem {
     Ident(String),
     Disambiguator(Disambiguator::Id),
@@ -9,7 +9,7 @@ use rustc_hir::def_id::DefId;
 use rustc_hir::definitions::DefPathData;
 use rustc_hir::{Item, ItemKind};
 use rustc_middle::ty::TyCtxt;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashSet;
 
 impl PathElem {
@@ -88,6 +88,22 @@ impl Name {
         self.prefix_is_same(&[krate, module])
     }
 
+    /// The path of the module in which the item is defined (the first element
+    /// is the crate name). Note that the items of the "impl" blocks belong to
+    /// the module of the type they implement.
+    pub fn module_path(&self) -> Vec<String> {
+        // Stop at the first disambiguator, which identifies an "impl" block,
+        // and remove the last identifier: the name of the item or, in case of
+        // an impl block, the name of the type.
+        let end = self
+            .name
+            .iter()
+            .position(|e| e.is_disambiguator())
+            .unwrap_or(self.name.len());
+        let end = std::cmp::max(end - 1, 1);
+        self.name[0..end].iter().map(|e| e.to_string()).collect()
+    }
+
     /// Similar to [is_in_module]
     pub fn is_in_modules(&self, krate: &String, modules: &HashSet<String>) -> bool {
         if self.len() >= 2 {
@@ -119,6 +135,16 @@ impl Serialize for Name {
     }
 }
 
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name: Vec<PathElem> = Vec::deserialize(deserializer)?;
+        Ok(Name { name })
+    }
+}
+
 /// Retrieve an item name from a `DefId`.
 pub fn item_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> ItemName {
     trace!("{:?}", def_id);
@@ -0,0 +1,401 @@
+//! Select the items of the extracted crate that we translate as opaque (i.e.,
+//! for which we only translate the signature) with patterns over their names.
+//! See the `--opaque` and `--transparent` options.
+//!
+//! A pattern is a path whose elements are separated by `::`, for instance:
+//! `mycrate::net::*::parse` or `mycrate::Foo{impl}::new`. Every element of the
+//! pattern is matched against one element of the item name:
+//! - `*` matches any element
+//! - the other elements may contain `*` wildcards (`parse_*` matches `parse_u32`)
+//! - `Foo` matches the item `Foo` as well as the impl blocks of `Foo`: the
+//!   pattern `mycrate::Foo` selects the type `Foo` and its methods
+//! - `Foo{impl}` matches any impl block of `Foo` (but not `Foo` itself), and
+//!   `Foo{impl#N}` the impl block with disambiguator `N` (see [crate::names::Name])
+//!
+//! A pattern matches an item if it matches a prefix of the item name: the pattern
+//! `mycrate::net` selects the module `net` as well as all the items it contains.
+//! A pattern which doesn't contain `::` is interpreted as a top-level module of
+//! the crate: `net` is the same as `mycrate::net`.
+//!
+//! If several patterns match the same item, the longest pattern wins, and a
+//! transparent pattern wins over an opaque pattern of the same length. The
+//! local items which are not matched by any pattern are transparent, while the
+//! external items are always opaque.
+
+use crate::common::*;
+use crate::id_vector::ToUsize;
+use crate::names::{Name, PathElem};
+use std::cell::Cell;
+
+/// The impl blocks an element of a pattern matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ImplSelector {
+    /// `Foo`: the item `Foo` and its impl blocks
+    None,
+    /// `Foo{impl}`: any impl block
+    Any,
+    /// `Foo{impl#N}`: the impl block with disambiguator `N`
+    Index(usize),
+}
+
+#[derive(Debug, Clone)]
+enum PatternElem {
+    /// `*`
+    Wildcard,
+    /// An identifier, which may contain `*` wildcards, and which may identify
+    /// impl blocks
+    Glob(Vec<char>, ImplSelector),
+}
+
+/// A pattern over item names.
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    /// The pattern, as given by the user (for the error messages)
+    pub source: String,
+    elems: Vec<PatternElem>,
+}
+
+/// An element of an item name, in the format used by the patterns: an identifier,
+/// and the disambiguator of the impl block if the element identifies one.
+type NameElem<'a> = (&'a str, Option<usize>);
+
+/// Convert a name to the format used for the pattern matching. Note that the
+/// disambiguators follow the identifier of the type of the impl blocks (see
+/// [crate::names_utils::item_def_id_to_name]).
+fn name_to_elems(name: &Name) -> Vec<NameElem> {
+    let mut elems: Vec<NameElem> = Vec::new();
+    for elem in &name.name {
+        match elem {
+            PathElem::Ident(s) => elems.push((s, None)),
+            PathElem::Disambiguator(d) => match elems.last_mut() {
+                Option::Some((_, disambiguator @ None)) => *disambiguator = Some(d.to_usize()),
+                _ => elems.push(("", Some(d.to_usize()))),
+            },
+        }
+    }
+    elems
+}
+
+/// Match a string against a pattern which may contain `*` wildcards
+fn glob_match(pattern: &[char], s: &[char]) -> bool {
+    match pattern.split_first() {
+        Option::None => s.is_empty(),
+        Option::Some(('*', pattern)) => (0..=s.len()).any(|i| glob_match(pattern, &s[i..])),
+        Option::Some((c, pattern)) => match s.split_first() {
+            Option::Some((c1, s)) => c == c1 && glob_match(pattern, s),
+            Option::None => false,
+        },
+    }
+}
+
+impl PatternElem {
+    fn parse(source: &str, elem: &str) -> Result<PatternElem> {
+        if elem == "*" {
+            return Ok(PatternElem::Wildcard);
+        }
+
+        let (ident, impl_selector) = match elem.find('{') {
+            Option::None => (elem, ImplSelector::None),
+            Option::Some(i) => {
+                let selector = &elem[i..];
+                let impl_selector = if selector == "{impl}" {
+                    ImplSelector::Any
+                } else {
+                    match selector
+                        .strip_prefix("{impl#")
+                        .and_then(|s| s.strip_suffix('}'))
+                        .and_then(|s| s.parse::<usize>().ok())
+                    {
+                        Option::Some(index) => ImplSelector::Index(index),
+                        Option::None => {
+                            error!(
+                                "Invalid pattern {:?}: expected `{{impl}}` or `{{impl#N}}`, found: {:?}",
+                                source, selector
+                            );
+                            return Err(());
+                        }
+                    }
+                };
+                (&elem[..i], impl_selector)
+            }
+        };
+
+        if ident.is_empty() {
+            error!("Invalid pattern {:?}: found an empty path element", source);
+            return Err(());
+        }
+        Ok(PatternElem::Glob(ident.chars().collect(), impl_selector))
+    }
+
+    fn matches(&self, elem: &NameElem) -> bool {
+        match self {
+            PatternElem::Wildcard => true,
+            PatternElem::Glob(ident, impl_selector) => {
+                let (elem_ident, disambiguator) = elem;
+                let elem_ident: Vec<char> = elem_ident.chars().collect();
+                glob_match(ident, &elem_ident)
+                    && match impl_selector {
+                        ImplSelector::None => true,
+                        ImplSelector::Any => disambiguator.is_some(),
+                        ImplSelector::Index(i) => *disambiguator == Some(*i),
+                    }
+            }
+        }
+    }
+}
+
+impl NamePattern {
+    /// Parse a pattern. A pattern which doesn't contain `::` is a top-level
+    /// module of the crate.
+    pub fn parse(crate_name: &str, source: &str) -> Result<NamePattern> {
+        let full_source = if source.contains("::") {
+            source.to_string()
+        } else {
+            format!("{}::{}", crate_name, source)
+        };
+        let elems = full_source
+            .split("::")
+            .map(|elem| PatternElem::parse(source, elem))
+            .collect::<Result<Vec<PatternElem>>>()?;
+
+        // We can only select items of the extracted crate
+        if !elems[0].matches(&(crate_name, None)) {
+            error!(
+                "Invalid pattern {:?}: only the items of the extracted crate ({}) can be selected",
+                source, crate_name
+            );
+            return Err(());
+        }
+
+        Ok(NamePattern {
+            source: source.to_string(),
+            elems,
+        })
+    }
+
+    /// Return true if the pattern matches a prefix of the name
+    fn matches(&self, name: &[NameElem]) -> bool {
+        self.elems.len() <= name.len()
+            && self
+                .elems
+                .iter()
+                .zip(name.iter())
+                .all(|(p, e)| p.matches(e))
+    }
+
+    /// Return true if the pattern matches the name (or a prefix of it)
+    pub fn matches_name(&self, name: &Name) -> bool {
+        self.matches(&name_to_elems(name))
+    }
+
+    /// Return true if the pattern may match items defined inside the item
+    /// with the given name (this is a conservative check)
+    fn may_match_inside(&self, name: &[NameElem]) -> bool {
+        self.elems.len() > name.len()
+            && self
+                .elems
+                .iter()
+                .zip(name.iter())
+                .all(|(p, e)| p.matches(e))
+    }
+}
+
+/// The opacity selection, built from the `--opaque` and `--transparent` patterns.
+pub struct OpacityFilter {
+    opaque: Vec<NamePattern>,
+    transparent: Vec<NamePattern>,
+    /// For every opaque pattern, did it match an item?
+    used_opaque: Vec<Cell<bool>>,
+    /// For every transparent pattern, did it match an item?
+    used_transparent: Vec<Cell<bool>>,
+}
+
+impl OpacityFilter {
+    pub fn new(
+        crate_name: &str,
+        opaque: &[String],
+        transparent: &[String],
+    ) -> Result<OpacityFilter> {
+        let opaque = opaque
+            .iter()
+            .map(|p| NamePattern::parse(crate_name, p))
+            .collect::<Result<Vec<NamePattern>>>()?;
+        let transparent = transparent
+            .iter()
+            .map(|p| NamePattern::parse(crate_name, p))
+            .collect::<Result<Vec<NamePattern>>>()?;
+        Ok(OpacityFilter {
+            used_opaque: opaque.iter().map(|_| Cell::new(false)).collect(),
+            used_transparent: transparent.iter().map(|_| Cell::new(false)).collect(),
+            opaque,
+            transparent,
+        })
+    }
+
+    /// Return the length of the longest pattern matching the name (0 if no
+    /// pattern matches), and remember the patterns which matched.
+    fn longest_match(patterns: &[NamePattern], used: &[Cell<bool>], name: &[NameElem]) -> usize {
+        let mut longest = 0;
+        for (pattern, used) in patterns.iter().zip(used.iter()) {
+            if pattern.matches(name) {
+                used.set(true);
+                longest = std::cmp::max(longest, pattern.elems.len());
+            }
+        }
+        longest
+    }
+
+    /// Return true if the local item with the given name is opaque
+    pub fn is_opaque(&self, name: &Name) -> bool {
+        let name = name_to_elems(name);
+        let opaque = OpacityFilter::longest_match(&self.opaque, &self.used_opaque, &name);
+        let transparent =
+            OpacityFilter::longest_match(&self.transparent, &self.used_transparent, &name);
+        opaque > transparent
+    }
+
+    /// Return true if some items defined inside the item with the given name
+    /// (for instance, a module or an impl block) may be transparent because of
+    /// a transparent pattern.
+    pub fn may_contain_transparent(&self, name: &Name) -> bool {
+        let name = name_to_elems(name);
+        self.transparent.iter().any(|p| p.may_match_inside(&name))
+    }
+
+    /// Check that all the patterns matched at least one item
+    pub fn check_all_patterns_used(&self) -> Result<()> {
+        let unused: Vec<&String> = self
+            .opaque
+            .iter()
+            .zip(self.used_opaque.iter())
+            .chain(self.transparent.iter().zip(self.used_transparent.iter()))
+            .filter(|(_, used)| !used.get())
+            .map(|(p, _)| &p.source)
+            .collect();
+        if unused.is_empty() {
+            Ok(())
+        } else {
+            for pattern in unused {
+                error!(
+                    "The pattern {:?} doesn't match any item of the crate",
+                    pattern
+                );
+            }
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::names::{Disambiguator, Name, PathElem};
+    use crate::opacity::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// Build a name from a path: `Foo#0` is the impl block of `Foo` with
+    /// disambiguator 0
+    fn name(path: &str) -> Name {
+        let mut name = Vec::new();
+        for elem in path.split("::") {
+            match elem.split_once('#') {
+                Option::Some((ident, d)) => {
+                    name.push(PathElem::Ident(ident.to_string()));
+                    name.push(PathElem::Disambiguator(Disambiguator::Id::new(
+                        d.parse().unwrap(),
+                    )));
+                }
+                Option::None => name.push(PathElem::Ident(elem.to_string())),
+            }
+        }
+        Name { name }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(&chars("parse"), &chars("parse")));
+        assert!(!glob_match(&chars("parse"), &chars("parse_u32")));
+        assert!(glob_match(&chars("parse_*"), &chars("parse_u32")));
+        assert!(glob_match(&chars("parse_*"), &chars("parse_")));
+        assert!(glob_match(&chars("*_u32"), &chars("parse_u32")));
+        assert!(glob_match(&chars("p*_*2"), &chars("parse_u32")));
+        assert!(!glob_match(&chars("p*_*3"), &chars("parse_u32")));
+        assert!(glob_match(&chars("*"), &chars("")));
+        assert!(!glob_match(&chars(""), &chars("a")));
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        // A pattern without `::` is a top-level module of the crate
+        let p = NamePattern::parse("mycrate", "net").unwrap();
+        assert!(p.matches_name(&name("mycrate::net::parse")));
+        assert!(!p.matches_name(&name("mycrate::io")));
+
+        assert!(NamePattern::parse("mycrate", "mycrate::Foo{impl}::new").is_ok());
+        assert!(NamePattern::parse("mycrate", "mycrate::Foo{impl#2}::new").is_ok());
+        assert!(NamePattern::parse("mycrate", "*::net").is_ok());
+        // Invalid impl selectors, empty elements, other crates
+        assert!(NamePattern::parse("mycrate", "mycrate::Foo{impl#}").is_err());
+        assert!(NamePattern::parse("mycrate", "mycrate::Foo{trait}").is_err());
+        assert!(NamePattern::parse("mycrate", "mycrate::::Foo").is_err());
+        assert!(NamePattern::parse("mycrate", "mycrate::{impl}").is_err());
+        assert!(NamePattern::parse("mycrate", "core::mem::swap").is_err());
+    }
+
+    #[test]
+    fn test_impl_selectors() {
+        let ty = name("mycrate::Foo");
+        let method0 = name("mycrate::Foo#0::new");
+        let method1 = name("mycrate::Foo#1::new");
+
+        // `Foo` selects the type and its impl blocks
+        let p = NamePattern::parse("mycrate", "mycrate::Foo").unwrap();
+        assert!(p.matches_name(&ty));
+        assert!(p.matches_name(&method0));
+        assert!(p.matches_name(&method1));
+
+        let p = NamePattern::parse("mycrate", "mycrate::Foo{impl}").unwrap();
+        assert!(!p.matches_name(&ty));
+        assert!(p.matches_name(&method0));
+        assert!(p.matches_name(&method1));
+
+        let p = NamePattern::parse("mycrate", "mycrate::Foo{impl#1}::new").unwrap();
+        assert!(!p.matches_name(&method0));
+        assert!(p.matches_name(&method1));
+
+        let p = NamePattern::parse("mycrate", "mycrate::*::new").unwrap();
+        assert!(p.matches_name(&method0));
+        assert!(!p.matches_name(&ty));
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let opaque = vec!["net".to_string(), "mycrate::Foo::get_*".to_string()];
+        let transparent = vec![
+            "mycrate::net::parse".to_string(),
+            "mycrate::Foo{impl}::get_*".to_string(),
+        ];
+        let filter = OpacityFilter::new("mycrate", &opaque, &transparent).unwrap();
+
+        assert!(filter.is_opaque(&name("mycrate::net")));
+        assert!(filter.is_opaque(&name("mycrate::net::print")));
+        // The longest pattern wins
+        assert!(!filter.is_opaque(&name("mycrate::net::parse")));
+        assert!(!filter.is_opaque(&name("mycrate::net::parse::aux")));
+        // A transparent pattern wins over an opaque pattern of the same length
+        assert!(!filter.is_opaque(&name("mycrate::Foo#0::get_x")));
+        assert!(!filter.is_opaque(&name("mycrate::Foo#0::set_x")));
+        // The items which are not matched are transparent
+        assert!(!filter.is_opaque(&name("mycrate::io::read")));
+
+        assert!(filter.may_contain_transparent(&name("mycrate::net")));
+        assert!(!filter.may_contain_transparent(&name("mycrate::io")));
+        assert!(filter.check_all_patterns_used().is_ok());
+
+        let filter = OpacityFilter::new("mycrate", &opaque, &[]).unwrap();
+        assert!(filter.is_opaque(&name("mycrate::net::parse")));
+        assert!(filter.check_all_patterns_used().is_err());
+    }
+}
@@ -0,0 +1,443 @@
+//! The micro-passes we apply on the LLBC, once the control-flow has been
+//! reconstructed, to make the code cleaner.
+//!
+//! Every pass implements [LlbcPass], and is registered in [all_passes]: the
+//! user selects the passes to apply, and their order, with their names (see
+//! [crate::driver::TransOptions]). Different backends need different subsets
+//! of the passes.
+use crate::cleanup;
+use crate::common::*;
+use crate::dataflow;
+use crate::driver::TransOptions;
+use crate::inline;
+use crate::inline_temporaries;
+use crate::insert_assign_return_unit;
+use crate::llbc_ast::FunDecls;
+use crate::llbc_export::OutputFormat;
+use crate::loop_analysis;
+use crate::reconstruct_asserts;
+use crate::reconstruct_try;
+use crate::remove_unused_locals;
+use crate::simplify_ops;
+use crate::ssa;
+use crate::types::TypeDecls;
+use std::path::PathBuf;
+
+/// A micro-pass over the LLBC function declarations
+pub trait LlbcPass {
+    /// The name of the pass, used to select it
+    fn name(&self) -> &'static str;
+
+    /// Apply the pass on the function declarations. Some passes are
+    /// parameterized by the translation options.
+    fn transform(&self, defs: FunDecls, type_defs: &TypeDecls, options: &TransOptions) -> FunDecls;
+
+    /// Return true if the pass removes the arithmetic checks introduced by
+    /// rustc (overflows, divisions by zero...): we don't apply those passes if
+    /// the user asked to keep the checks.
+    fn removes_arith_checks(&self) -> bool {
+        false
+    }
+
+    /// The passes which, if they are applied, must be applied before this one
+    /// (see [check_pass_order])
+    fn must_run_after(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Return true if the results of the pass (annotations, or invariants of
+    /// the statements) are lost when another pass transforms the statements:
+    /// the pass must then be applied after all the passes which don't return
+    /// true here (see [check_pass_order])
+    fn must_run_last(&self) -> bool {
+        false
+    }
+}
+
+/// Simplify the calls to unops and binops.
+/// Note that we assume that the sequences have been flattened.
+struct SimplifyOps;
+
+impl LlbcPass for SimplifyOps {
+    fn name(&self) -> &'static str {
+        "simplify_ops"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        simplify_ops::simplify(defs)
+    }
+
+    fn removes_arith_checks(&self) -> bool {
+        true
+    }
+}
+
+/// Reconstruct the asserts
+struct ReconstructAsserts;
+
+impl LlbcPass for ReconstructAsserts {
+    fn name(&self) -> &'static str {
+        "reconstruct_asserts"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        reconstruct_asserts::simplify(defs)
+    }
+}
+
+/// Reconstruct the `?` operator from its desugaring. This pass is not applied
+/// by default.
+struct ReconstructTry;
+
+impl LlbcPass for ReconstructTry {
+    fn name(&self) -> &'static str {
+        "reconstruct_try"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        reconstruct_try::transform(defs)
+    }
+}
+
+/// Add the missing assignments to the return value.
+/// When the function return type is unit, the generated MIR doesn't
+/// set the return value to `()`. This can be a concern: in the case
+/// of Aeneas, it means the return variable contains ⊥ upon returning.
+/// For this reason, when the function has return type unit, we insert
+/// an extra assignment just before returning.
+struct InsertAssignReturnUnit;
+
+impl LlbcPass for InsertAssignReturnUnit {
+    fn name(&self) -> &'static str {
+        "insert_assign_return_unit"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        insert_assign_return_unit::transform(defs)
+    }
+}
+
+/// Inline the temporaries introduced by the desugaring, to rebuild nested
+/// expressions. This pass is not applied by default.
+struct InlineTemporaries;
+
+impl LlbcPass for InlineTemporaries {
+    fn name(&self) -> &'static str {
+        "inline_temporaries"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        inline_temporaries::transform(defs)
+    }
+
+    /// [crate::simplify_ops] and [crate::reconstruct_try] expect the flat MIR
+    /// style
+    fn must_run_after(&self) -> &'static [&'static str] {
+        &["simplify_ops", "reconstruct_try"]
+    }
+}
+
+/// Inline the calls to small non-recursive functions, and to the functions
+/// with the `#[charon::inline]` attribute. This pass is not applied by default.
+struct Inline;
+
+impl LlbcPass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        options: &TransOptions,
+    ) -> FunDecls {
+        inline::transform(defs, options.inline_threshold)
+    }
+}
+
+/// Remove the `FakeRead`s, the drops which don't do anything and the `Nop`s.
+/// This pass is not applied by default, because some consumers model the
+/// `FakeRead`s (for the two-phase borrows, for instance).
+struct Cleanup;
+
+impl LlbcPass for Cleanup {
+    fn name(&self) -> &'static str {
+        "cleanup"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        cleanup::transform(defs, type_defs)
+    }
+}
+
+/// Remove the locals which are never used. After doing so, we
+/// check that there are no remaining locals with type `Never`.
+struct RemoveUnusedLocals;
+
+impl LlbcPass for RemoveUnusedLocals {
+    fn name(&self) -> &'static str {
+        "remove_unused_locals"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        remove_unused_locals::transform(defs)
+    }
+
+    /// Those passes leave unused locals behind them
+    fn must_run_after(&self) -> &'static [&'static str] {
+        &["reconstruct_try", "inline_temporaries", "inline"]
+    }
+}
+
+/// Rename the locals so that every local is assigned at most once on every
+/// control-flow path, and compute the loop-carried variables. This pass is not
+/// applied by default.
+struct Ssa;
+
+impl LlbcPass for Ssa {
+    fn name(&self) -> &'static str {
+        "ssa"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        ssa::transform(defs)
+    }
+
+    fn must_run_last(&self) -> bool {
+        true
+    }
+}
+
+/// Annotate the loops with the locals they read and modify, the places they
+/// borrow and the places live after them. This pass is not applied by default.
+struct LoopAnalysis;
+
+impl LlbcPass for LoopAnalysis {
+    fn name(&self) -> &'static str {
+        "loop_analysis"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        loop_analysis::transform(defs)
+    }
+
+    fn must_run_last(&self) -> bool {
+        true
+    }
+
+    /// [crate::ssa] doesn't preserve the annotations
+    fn must_run_after(&self) -> &'static [&'static str] {
+        &["ssa"]
+    }
+}
+
+/// Annotate the statements with the results of the liveness, maybe-initialized
+/// and maybe-moved analyses. This pass is not applied by default.
+struct Dataflow;
+
+impl LlbcPass for Dataflow {
+    fn name(&self) -> &'static str {
+        "dataflow"
+    }
+
+    fn transform(
+        &self,
+        defs: FunDecls,
+        _type_defs: &TypeDecls,
+        _options: &TransOptions,
+    ) -> FunDecls {
+        dataflow::transform(defs)
+    }
+
+    fn must_run_last(&self) -> bool {
+        true
+    }
+
+    /// [crate::ssa] doesn't preserve the annotations
+    fn must_run_after(&self) -> &'static [&'static str] {
+        &["ssa"]
+    }
+}
+
+/// The micro-passes we apply by default, in this order.
+pub static DEFAULT_PASSES: [&str; 4] = [
+    "simplify_ops",
+    "reconstruct_asserts",
+    "insert_assign_return_unit",
+    "remove_unused_locals",
+];
+
+/// The registry of the micro-passes
+pub fn all_passes() -> Vec<Box<dyn LlbcPass>> {
+    vec![
+        Box::new(SimplifyOps),
+        Box::new(ReconstructAsserts),
+        Box::new(ReconstructTry),
+        Box::new(InsertAssignReturnUnit),
+        Box::new(InlineTemporaries),
+        Box::new(Inline),
+        Box::new(Cleanup),
+        Box::new(RemoveUnusedLocals),
+        Box::new(Ssa),
+        Box::new(LoopAnalysis),
+        Box::new(Dataflow),
+    ]
+}
+
+/// Lookup a micro-pass in the registry
+pub fn get_pass(name: &str) -> Option<Box<dyn LlbcPass>> {
+    all_passes().into_iter().find(|pass| pass.name() == name)
+}
+
+/// Check that the names refer to registered passes
+pub fn check_pass_names(names: &Vec<String>) -> Result<()> {
+    for name in names {
+        if get_pass(name).is_none() {
+            let available: Vec<&str> = all_passes().iter().map(|pass| pass.name()).collect();
+            error!(
+                "Unknown micro-pass: {:?}. The available passes are: {:?}",
+                name, available
+            );
+            return Err(());
+        }
+    }
+    Ok(())
+}
+
+/// Check that the passes, given in the order in which they are applied, satisfy
+/// the ordering constraints (see [LlbcPass::must_run_after] and
+/// [LlbcPass::must_run_last]). The names must have been checked with
+/// [check_pass_names].
+pub fn check_pass_order(names: &Vec<String>) -> Result<()> {
+    for (i, name) in names.iter().enumerate() {
+        let pass = get_pass(name).unwrap();
+        if pass.must_run_last() {
+            let after = names[i + 1..]
+                .iter()
+                .find(|n| !get_pass(n).unwrap().must_run_last());
+            if let Option::Some(after) = after {
+                error!(
+                    "Invalid order of the micro-passes: {:?} must be applied after {:?}",
+                    name, after
+                );
+                return Err(());
+            }
+        }
+        for before in pass.must_run_after() {
+            if names[i + 1..].iter().any(|n| n == before) {
+                error!(
+                    "Invalid order of the micro-passes: {:?} must be applied before {:?}",
+                    before, name
+                );
+                return Err(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The options to dump the state of the translation after some passes
+/// (for debugging purposes). The dumps have the same format as the generated
+/// .llbc files: we write them in the destination directory, in files named
+/// after the passes (`lib.simplify_ops.llbc`, etc.).
+#[derive(Debug, Clone)]
+pub struct PassDumpOptions {
+    /// The passes after which to dump the declarations
+    pub passes: Vec<String>,
+    pub dest_dir: Option<PathBuf>,
+    pub format: OutputFormat,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::passes::*;
+
+    fn names(passes: &[&str]) -> Vec<String> {
+        passes.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pass_order() {
+        // The ordering constraints refer to registered passes
+        for pass in all_passes() {
+            assert!(check_pass_names(&names(pass.must_run_after())).is_ok());
+        }
+
+        assert!(check_pass_order(&names(&DEFAULT_PASSES)).is_ok());
+        assert!(check_pass_order(&names(&[
+            "simplify_ops",
+            "reconstruct_try",
+            "inline_temporaries",
+            "remove_unused_locals"
+        ]))
+        .is_ok());
+        assert!(check_pass_order(&names(&["inline_temporaries", "simplify_ops"])).is_err());
+        assert!(check_pass_order(&names(&["remove_unused_locals", "reconstruct_try"])).is_err());
+        assert!(check_pass_order(&names(&[
+            "simplify_ops",
+            "inline_temporaries",
+            "simplify_ops"
+        ]))
+        .is_err());
+
+        // The analyses come after the passes which transform the statements
+        assert!(check_pass_order(&names(&[
+            "simplify_ops",
+            "remove_unused_locals",
+            "ssa",
+            "loop_analysis",
+            "dataflow"
+        ]))
+        .is_ok());
+        assert!(check_pass_order(&names(&["dataflow", "remove_unused_locals"])).is_err());
+        assert!(check_pass_order(&names(&["loop_analysis", "cleanup"])).is_err());
+        assert!(check_pass_order(&names(&["ssa", "inline"])).is_err());
+        assert!(check_pass_order(&names(&["dataflow", "ssa"])).is_err());
+    }
+}
@@ -3,40 +3,42 @@
 //! introduce `if ... then { panic!(...) } else { ...}`.
 //! This pass introduces `assert` instead in order to make the code shorter.
 
-use crate::llbc_ast::{Assert, FunDecl, FunDecls, Statement, SwitchTargets};
+use crate::llbc_ast::{Assert, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
 use std::iter::FromIterator;
 
 fn simplify_st(st: Statement) -> Statement {
-    match st {
-        Statement::Assign(p, rv) => Statement::Assign(p, rv),
-        Statement::FakeRead(p) => Statement::FakeRead(p),
-        Statement::SetDiscriminant(p, vid) => Statement::SetDiscriminant(p, vid),
-        Statement::Drop(p) => Statement::Drop(p),
-        Statement::Assert(assert) => Statement::Assert(assert),
-        Statement::Call(call) => Statement::Call(call),
-        Statement::Panic => Statement::Panic,
-        Statement::Return => Statement::Return,
-        Statement::Break(i) => Statement::Break(i),
-        Statement::Continue(i) => Statement::Continue(i),
-        Statement::Nop => Statement::Nop,
-        Statement::Switch(op, targets) => {
+    let span = st.span;
+    let content = match st.content {
+        RawStatement::Assign(p, rv) => RawStatement::Assign(p, rv),
+        RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
+        RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
+        RawStatement::Drop(p) => RawStatement::Drop(p),
+        RawStatement::Assert(assert) => RawStatement::Assert(assert),
+        RawStatement::Call(call) => RawStatement::Call(call),
+        RawStatement::Try(t) => RawStatement::Try(t),
+        RawStatement::Panic => RawStatement::Panic,
+        RawStatement::Return => RawStatement::Return,
+        RawStatement::Break(i) => RawStatement::Break(i),
+        RawStatement::Continue(i) => RawStatement::Continue(i),
+        RawStatement::Nop => RawStatement::Nop,
+        RawStatement::Switch(op, targets) => {
             match targets {
                 SwitchTargets::If(st1, st2) => {
                     let st2 = Box::new(simplify_st(*st2));
 
                     // Check if the first statement is a panic: if yes, replace
                     // the if .. then ... else ... by an assertion.
-                    if st1.is_panic() {
-                        let st1 = Statement::Assert(Assert {
+                    if st1.content.is_panic() {
+                        let st1 = RawStatement::Assert(Assert {
                             cond: op,
                             expected: false,
                         });
-                        let st1 = Box::new(st1);
+                        let st1 = Statement::new(span, st1);
 
-                        Statement::Sequence(st1, st2)
+                        return Statement::new_seq(st1, *st2);
                     } else {
                         let targets = SwitchTargets::If(Box::new(simplify_st(*st1)), st2);
-                        Statement::Switch(op, targets)
+                        RawStatement::Switch(op, targets)
                     }
                 }
                 SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
@@ -44,16 +46,21 @@ fn simplify_st(st: Statement) -> Statement {
                         Vec::from_iter(targets.into_iter().map(|(v, e)| (v, simplify_st(e))));
                     let otherwise = simplify_st(*otherwise);
                     let targets = SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise));
-                    Statement::Switch(op, targets)
+                    RawStatement::Switch(op, targets)
                 }
             }
         }
-        Statement::Loop(loop_body) => Statement::Loop(Box::new(simplify_st(*loop_body))),
-        Statement::Sequence(st1, st2) => {
-            Statement::Sequence(Box::new(simplify_st(*st1)), Box::new(simplify_st(*st2)))
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(simplify_st(*loop_.body)),
+            ..loop_
+        }),
+        RawStatement::Sequence(st1, st2) => {
+            return Statement::new_seq(simplify_st(*st1), simplify_st(*st2));
         }
-    }
+    };
+    Statement::new(span, content)
 }
+
 fn simplify_def(mut def: FunDecl) -> FunDecl {
     trace!("About to update: {}", def.name);
     def.body = match def.body {
@@ -0,0 +1,534 @@
+//! Reconstruct the `?` operator from its desugaring. In MIR, `let y = x?;` is
+//! compiled to a call to `Try::branch`, followed by a match on the resulting
+//! `ControlFlow`, which, in the `Break` case, converts the residual with
+//! `FromResidual::from_residual` and returns. Once the control-flow has been
+//! reconstructed, this gives:
+//!   ```
+//!   cf := core::ops::try_trait::Try::branch(move x);
+//!   d := discriminant(cf);
+//!   switch move d {
+//!     0 => {
+//!       v := move (cf as Continue).0;
+//!       ...
+//!     },
+//!     1 => {
+//!       r := move (cf as Break).0;
+//!       @return := core::ops::try_trait::FromResidual::from_residual(move r);
+//!       drop a;
+//!       ...
+//!       drop b;
+//!       return
+//!     },
+//!     _ => { panic }
+//!   }
+//!   ```
+//! where the drops, if any, are the drops of the locals live at this point.
+//! We replace this pattern with the dedicated statement
+//! [crate::llbc_ast::RawStatement::Try], which records the drops, followed by
+//! the rest of the `Continue` branch:
+//!   ```
+//!   v := move x? (drop a, ..., b);
+//!   ...
+//!   ```
+//! so that the consumers doing error-monad translations don't have to
+//! pattern-match the desugaring themselves. We don't record the drops of `cf`
+//! and `r`, which don't do anything: their content has been moved.
+//!
+//! We only rewrite the exact pattern above: if the `Break` branch contains any
+//! other statement, or if the branches don't move the fields of the right
+//! variants, we leave the desugaring as it is, so that the rewrite never
+//! changes the meaning of the code. The pass is tested by the
+//! `test-question_mark` target of the Makefile at the root of the repository.
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. It
+//! must be applied before [crate::remove_unused_locals], which removes the
+//! locals introduced by the desugaring.
+
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::im_ast::{FunDeclId, FunId};
+use crate::llbc_ast::{Call, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets, Try};
+use crate::values::*;
+use std::collections::{HashSet, VecDeque};
+use std::iter::FromIterator;
+
+pub static TRY_BRANCH_NAME: [&str; 5] = ["core", "ops", "try_trait", "Try", "branch"];
+pub static FROM_RESIDUAL_NAME: [&str; 5] =
+    ["core", "ops", "try_trait", "FromResidual", "from_residual"];
+
+/// The discriminant of `ControlFlow::Continue`
+static CONTINUE_DISCRIMINANT: i128 = 0;
+/// The discriminant of `ControlFlow::Break`
+static BREAK_DISCRIMINANT: i128 = 1;
+
+struct Ctx {
+    /// The identifiers of `Try::branch` (there should be at most one)
+    branch: HashSet<FunDeclId::Id>,
+    /// The identifiers of `FromResidual::from_residual`
+    from_residual: HashSet<FunDeclId::Id>,
+}
+
+fn is_var(p: &Place, var_id: VarId::Id) -> bool {
+    p.var_id == var_id && p.projection.is_empty()
+}
+
+fn operand_is_var(op: &Operand, var_id: VarId::Id) -> bool {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => is_var(p, var_id),
+        Operand::Constant(_, _) | Operand::Expr(_, _) => false,
+    }
+}
+
+fn scalar_as_int(v: &ScalarValue) -> Option<i128> {
+    if v.is_int() {
+        v.as_int().ok()
+    } else {
+        v.as_uint().ok().map(|v| v as i128)
+    }
+}
+
+/// Find the branch of a switch over a discriminant
+fn find_target(
+    targets: &[(Vec<ScalarValue>, Statement)],
+    discriminant: i128,
+) -> Option<&Statement> {
+    targets
+        .iter()
+        .find(|(values, _)| values.len() == 1 && scalar_as_int(&values[0]) == Some(discriminant))
+        .map(|(_, st)| st)
+}
+
+fn first_statement(st: &Statement) -> &Statement {
+    match &st.content {
+        RawStatement::Sequence(st1, _) => first_statement(st1),
+        _ => st,
+    }
+}
+
+fn flatten_ref<'a>(st: &'a Statement, out: &mut Vec<&'a Statement>) {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => {
+            flatten_ref(st1, out);
+            flatten_ref(st2, out);
+        }
+        _ => out.push(st),
+    }
+}
+
+/// Check that a place is the field of the variant with the given discriminant
+/// of the control-flow value: `(cf as Continue).0` or `(cf as Break).0`
+fn is_variant_field(p: &Place, cf: VarId::Id, discriminant: i128) -> bool {
+    if p.var_id != cf || p.projection.len() != 1 {
+        return false;
+    }
+    match p.projection.get(0).unwrap() {
+        ProjectionElem::Field(FieldProjKind::Adt(_, Some(variant_id)), field_id) => {
+            variant_id.to_usize() as i128 == discriminant && field_id.to_usize() == 0
+        }
+        _ => false,
+    }
+}
+
+/// Check that the `Continue` branch starts by moving the value out of the
+/// control-flow value
+fn is_continue_branch(st: &Statement, cf: VarId::Id) -> bool {
+    match &first_statement(st).content {
+        RawStatement::Assign(_, Rvalue::Use(Operand::Move(p))) => {
+            is_variant_field(p, cf, CONTINUE_DISCRIMINANT)
+        }
+        _ => false,
+    }
+}
+
+/// Check that the `Break` branch moves the residual out of the control-flow
+/// value, converts it with `FromResidual::from_residual` to set the return
+/// value, drops some places and returns, and does nothing else. Return the
+/// dropped places (but `cf` and the residual).
+fn break_branch_drops(ctx: &Ctx, st: &Statement, cf: VarId::Id) -> Option<Vec<Place>> {
+    let mut sts = Vec::new();
+    flatten_ref(st, &mut sts);
+    if sts.len() < 3 || !sts[sts.len() - 1].content.is_return() {
+        return None;
+    }
+
+    // `r := move (cf as Break).0`
+    let r = match &sts[0].content {
+        RawStatement::Assign(r, Rvalue::Use(Operand::Move(p)))
+            if r.projection.is_empty() && is_variant_field(p, cf, BREAK_DISCRIMINANT) =>
+        {
+            r.var_id
+        }
+        _ => return None,
+    };
+
+    // `@return := FromResidual::from_residual(move r)`
+    match &sts[1].content {
+        RawStatement::Call(Call {
+            func: FunId::Regular(id),
+            args,
+            dest,
+            ..
+        }) if ctx.from_residual.contains(id)
+            && is_var(dest, VarId::ZERO)
+            && args.len() == 1
+            && matches!(&args[0], Operand::Move(p) if is_var(p, r)) => {}
+        _ => return None,
+    }
+
+    // The drops
+    let mut drops = Vec::new();
+    for st in &sts[2..sts.len() - 1] {
+        match &st.content {
+            RawStatement::Drop(p) => {
+                if p.var_id != cf && p.var_id != r {
+                    drops.push(p.clone());
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(drops)
+}
+
+/// Check if three consecutive statements are the desugaring of `?`. If so,
+/// return the places dropped when returning early.
+fn is_try(
+    ctx: &Ctx,
+    call: &Statement,
+    discr: &Statement,
+    switch: &Statement,
+) -> Option<Vec<Place>> {
+    // `cf := Try::branch(move x)`
+    let cf = match &call.content {
+        RawStatement::Call(Call {
+            func: FunId::Regular(id),
+            args,
+            dest,
+            ..
+        }) if ctx.branch.contains(id) && args.len() == 1 && dest.projection.is_empty() => {
+            dest.var_id
+        }
+        _ => return None,
+    };
+
+    // `d := discriminant(cf)`
+    let d = match &discr.content {
+        RawStatement::Assign(d, Rvalue::Discriminant(p))
+            if d.projection.is_empty() && is_var(p, cf) =>
+        {
+            d.var_id
+        }
+        _ => return None,
+    };
+
+    // The switch over the discriminant
+    match &switch.content {
+        RawStatement::Switch(op, SwitchTargets::SwitchInt(_, targets, otherwise))
+            if operand_is_var(op, d) && targets.len() == 2 && otherwise.content.is_panic() =>
+        {
+            match (
+                find_target(targets, CONTINUE_DISCRIMINANT),
+                find_target(targets, BREAK_DISCRIMINANT),
+            ) {
+                (Some(cont), Some(brk)) if is_continue_branch(cont, cf) => {
+                    break_branch_drops(ctx, brk, cf)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn flatten(st: Statement, out: &mut Vec<Statement>) {
+    match st.content {
+        RawStatement::Sequence(st1, st2) => {
+            flatten(*st1, out);
+            flatten(*st2, out);
+        }
+        _ => out.push(st),
+    }
+}
+
+fn transform_sub_statements(ctx: &Ctx, st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                    Box::new(transform_st(ctx, *st1)),
+                    Box::new(transform_st(ctx, *st2)),
+                ),
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets =
+                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(ctx, e))));
+                    let otherwise = transform_st(ctx, *otherwise);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(ctx, *loop_.body)),
+            ..loop_
+        }),
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+fn transform_st(ctx: &Ctx, st: Statement) -> Statement {
+    // We work on the flattened sequence of statements
+    let mut sts = Vec::new();
+    flatten(st, &mut sts);
+    let mut sts: VecDeque<Statement> = sts
+        .into_iter()
+        .map(|st| transform_sub_statements(ctx, st))
+        .collect();
+    let mut out: Vec<Statement> = Vec::new();
+    while let Some(st) = sts.pop_front() {
+        let drops = if sts.len() < 2 {
+            None
+        } else {
+            is_try(ctx, &st, &sts[0], &sts[1])
+        };
+        let drops = match drops {
+            Some(drops) => drops,
+            None => {
+                out.push(st);
+                continue;
+            }
+        };
+
+        // Retrieve the operand of `?`
+        let op = match st.content {
+            RawStatement::Call(mut call) => call.args.pop().unwrap(),
+            _ => unreachable!(),
+        };
+        let _discr = sts.pop_front().unwrap();
+        let switch = sts.pop_front().unwrap();
+
+        // Retrieve the `Continue` branch: its first statement gives the
+        // destination of `?`, and the remaining statements follow the `?`
+        let cont = match switch.content {
+            RawStatement::Switch(_, SwitchTargets::SwitchInt(_, targets, _)) => {
+                targets
+                    .into_iter()
+                    .find(|(values, _)| scalar_as_int(&values[0]) == Some(CONTINUE_DISCRIMINANT))
+                    .unwrap()
+                    .1
+            }
+            _ => unreachable!(),
+        };
+        let mut cont_sts = Vec::new();
+        flatten(cont, &mut cont_sts);
+        let mut cont_sts = cont_sts.into_iter();
+        let dest = match cont_sts.next().unwrap().content {
+            RawStatement::Assign(dest, _) => dest,
+            _ => unreachable!(),
+        };
+        out.push(Statement::new(
+            st.span,
+            RawStatement::Try(Try { op, dest, drops }),
+        ));
+
+        // The statements of the `Continue` branch may themselves contain `?`
+        for st in cont_sts.rev() {
+            sts.push_front(st);
+        }
+    }
+
+    // Rebuild the sequence
+    let mut st = out.pop().unwrap();
+    while let Some(prev) = out.pop() {
+        st = Statement::new_seq(prev, st);
+    }
+    st
+}
+
+fn transform_def(ctx: &Ctx, mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    def.body = match def.body {
+        Option::Some(mut body) => {
+            body.body = transform_st(ctx, body.body);
+            Option::Some(body)
+        }
+        Option::None => Option::None,
+    };
+    def
+}
+
+pub fn transform(defs: FunDecls) -> FunDecls {
+    // Lookup the functions used by the desugaring: as they are external, they
+    // are in the declarations (without bodies)
+    let branch = defs
+        .iter()
+        .filter(|def| def.name.equals_ref_name(&TRY_BRANCH_NAME))
+        .map(|def| def.def_id)
+        .collect();
+    let from_residual = defs
+        .iter()
+        .filter(|def| def.name.equals_ref_name(&FROM_RESIDUAL_NAME))
+        .map(|def| def.def_id)
+        .collect();
+    let ctx = Ctx {
+        branch,
+        from_residual,
+    };
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(&ctx, def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::im_ast::{FunDeclId, FunId};
+    use crate::llbc_ast::{Call, RawStatement, Statement, SwitchTargets, Try};
+    use crate::llbc_ast_utils::test_utils::*;
+    use crate::reconstruct_try::*;
+    use crate::types::*;
+    use crate::values::*;
+
+    /// The locals of the tests: `x` is the operand of `?`, `cf`, `d` and `r`
+    /// are introduced by the desugaring, `v` receives the content of `x`, and
+    /// `y` is a local dropped when returning early
+    static LOCALS: [Option<&str>; 7] =
+        [None, Some("x"), None, None, Some("v"), Some("r"), Some("y")];
+    const X: usize = 1;
+    const CF: usize = 2;
+    const D: usize = 3;
+    const V: usize = 4;
+    const R: usize = 5;
+    const Y: usize = 6;
+
+    /// `Try::branch` is the function 0, `FromResidual::from_residual` the
+    /// function 1
+    fn ctx() -> Ctx {
+        Ctx {
+            branch: vec![FunDeclId::Id::new(0)].into_iter().collect(),
+            from_residual: vec![FunDeclId::Id::new(1)].into_iter().collect(),
+        }
+    }
+
+    fn call(id: usize, args: Vec<Operand>, dest: usize) -> Statement {
+        st(RawStatement::Call(Call {
+            func: FunId::Regular(FunDeclId::Id::new(id)),
+            region_args: Vec::new(),
+            type_args: Vec::new(),
+            args,
+            dest: local(dest),
+        }))
+    }
+
+    /// `(cf as Continue).0` or `(cf as Break).0`
+    fn variant_field(variant: i128) -> Place {
+        let mut p = local(CF);
+        p.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Adt(
+                TypeDeclId::Id::new(0),
+                Some(VariantId::Id::new(variant as usize)),
+            ),
+            FieldId::Id::new(0),
+        ));
+        p
+    }
+
+    /// The desugaring of `v := x?; @return := move v; return`, with the given
+    /// `Break` branch
+    fn desugaring(brk: Vec<Statement>) -> Statement {
+        let cont = seq(vec![
+            assign(
+                V,
+                Rvalue::Use(Operand::Move(variant_field(CONTINUE_DISCRIMINANT))),
+            ),
+            assign(0, Rvalue::Use(move_(V))),
+            st(RawStatement::Return),
+        ]);
+        let targets = vec![
+            (vec![ScalarValue::Isize(0)], cont),
+            (vec![ScalarValue::Isize(1)], seq(brk)),
+        ];
+        seq(vec![
+            call(0, vec![move_(X)], CF),
+            assign(D, Rvalue::Discriminant(local(CF))),
+            st(RawStatement::Switch(
+                move_(D),
+                SwitchTargets::SwitchInt(
+                    IntegerTy::Isize,
+                    targets,
+                    Box::new(st(RawStatement::Panic)),
+                ),
+            )),
+        ])
+    }
+
+    fn move_residual() -> Statement {
+        assign(
+            R,
+            Rvalue::Use(Operand::Move(variant_field(BREAK_DISCRIMINANT))),
+        )
+    }
+
+    fn is_unchanged(st: &Statement) -> bool {
+        matches!(flatten_seq(st)[0].content, RawStatement::Call(_))
+    }
+
+    #[test]
+    fn test_try() {
+        let st = desugaring(vec![
+            move_residual(),
+            call(1, vec![move_(R)], 0),
+            st(RawStatement::Drop(local(Y))),
+            st(RawStatement::Drop(local(CF))),
+            st(RawStatement::Return),
+        ]);
+        let st = transform_st(&ctx(), st);
+        let sts = flatten_seq(&st);
+        assert_eq!(sts.len(), 3);
+        match &sts[0].content {
+            RawStatement::Try(Try { op, dest, drops }) => {
+                assert!(matches!(op, Operand::Move(p) if p.var_id == local(X).var_id));
+                assert_eq!(dest.var_id, local(V).var_id);
+                // The drop of `cf` is not recorded
+                assert_eq!(drops.len(), 1);
+                assert_eq!(drops[0].var_id, local(Y).var_id);
+            }
+            _ => panic!("Expected a `?`"),
+        }
+        assert!(matches!(sts[1].content, RawStatement::Assign(_, _)));
+        assert!(sts[2].content.is_return());
+    }
+
+    #[test]
+    fn test_other_statement() {
+        // The `Break` branch does something else than dropping: the rewrite
+        // would lose it
+        let st = desugaring(vec![
+            move_residual(),
+            call(1, vec![move_(R)], 0),
+            assign(Y, add(copy(X), constant(1))),
+            st(RawStatement::Return),
+        ]);
+        assert!(is_unchanged(&transform_st(&ctx(), st)));
+    }
+
+    #[test]
+    fn test_wrong_residual() {
+        // The argument of `from_residual` is not the residual
+        let st = desugaring(vec![
+            move_residual(),
+            call(1, vec![move_(Y)], 0),
+            st(RawStatement::Return),
+        ]);
+        assert!(is_unchanged(&transform_st(&ctx(), st)));
+
+        // The residual is not moved out of the `Break` variant
+        let st = desugaring(vec![
+            assign(
+                R,
+                Rvalue::Use(Operand::Move(variant_field(CONTINUE_DISCRIMINANT))),
+            ),
+            call(1, vec![move_(R)], 0),
+            st(RawStatement::Return),
+        ]);
+        assert!(is_unchanged(&transform_st(&ctx(), st)));
+    }
+}
@@ -15,7 +15,7 @@ use macros::generate_index_type;
 use petgraph::algo::tarjan_scc;
 use petgraph::graphmap::DiGraphMap;
 use petgraph::Direction;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
@@ -39,7 +39,7 @@ type LifetimeConstraints = DiGraphMap<Region<RegionVarId::Id>, ()>;
 ///
 /// Is used to group regions with the same lifetime together, and express
 /// the lifetime hierarchy between different groups of regions.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionGroup {
     /// The region group identifier
     pub id: RegionGroupId::Id,
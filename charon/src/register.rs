@@ -1,10 +1,12 @@
 use crate::assumed;
 use crate::common::*;
 use crate::generics;
+use crate::get_mir::MirLevel;
 use crate::names::{
     function_def_id_to_name, hir_item_to_name, module_def_id_to_name, type_def_id_to_name, FunName,
     TypeName,
 };
+use crate::opacity;
 use crate::translate_functions_to_im;
 use hashlink::LinkedHashMap;
 use linked_hash_set::LinkedHashSet;
@@ -16,6 +18,16 @@ use rustc_session::Session;
 use rustc_span::Span;
 use std::collections::HashSet;
 
+/// Return true if the item was generated by the test harness (the test
+/// descriptors and the `main` function which runs the tests), when we extract
+/// a test or a benchmark. We ignore those items.
+fn is_test_harness_item(tcx: TyCtxt, item: &Item) -> bool {
+    tcx.hir().attrs(item.hir_id()).iter().any(|attr| {
+        attr.has_name(rustc_span::sym::rustc_test_marker)
+            || attr.has_name(rustc_span::sym::rustc_main)
+    })
+}
+
 fn is_fn_decl(item: &Item) -> bool {
     match item.kind {
         ItemKind::Fn(_, _, _) => true,
@@ -25,8 +37,12 @@ fn is_fn_decl(item: &Item) -> bool {
 
 pub struct CrateInfo {
     pub crate_name: String,
-    /// The set of opaque modules
-    pub opaque: HashSet<String>,
+    /// The selection of the opaque items
+    pub opacity: opacity::OpacityFilter,
+    /// The level at which to retrieve the MIR bodies
+    pub mir_level: MirLevel,
+    /// The user mapping from external functions to assumed functions
+    pub assumed_funs: assumed::AssumedFunsMap,
 }
 
 pub type TypeDependencies = LinkedHashSet<DefId>;
@@ -184,10 +200,9 @@ fn register_local_adt(
     // initialize the list of local dependencies to empty).
     let mut rtype_decl = RegisteredTypeDeclaration::new(type_id);
 
-    // We explore the type definition only if it is not in a module flagged
-    // as opaque
+    // We explore the type definition only if it is not flagged as opaque
     let name = type_def_id_to_name(tcx, adt.did);
-    if name.is_in_modules(&crate_info.crate_name, &crate_info.opaque) {
+    if crate_info.opacity.is_opaque(&name) {
         // The type is opaque
         // Register it as having no dependencies (dependencise are introduced
         // by exploring the type definition, to check the types used in the fields).
@@ -623,7 +638,7 @@ fn register_local_function_body(
     fn_decl: &mut RegisteredFunDeclaration,
 ) -> Result<()> {
     // Retrieve the MIR code
-    let body = crate::get_mir::get_mir_for_def_id(tcx, def_id);
+    let body = crate::get_mir::get_mir_for_def_id(tcx, def_id, crate_info.mir_level);
 
     // Start by registering the types found in the local variable declarations.
     // Note that those local variables include the parameters as well as the
@@ -737,6 +752,7 @@ fn register_local_function_body(
                 trace!("terminator:Call:fid {:?}", fid);
 
                 let name = function_def_id_to_name(tcx, fid);
+                let name = assumed::map_fun_name(&crate_info.assumed_funs, name);
                 trace!("called function: name: {:?}", name);
 
                 // We may need to filter the types and arguments, if the type
@@ -928,10 +944,9 @@ fn register_local_function(
     // we will progressively fill during exploration.
     let mut fn_decl = RegisteredFunDeclaration::new(def_id);
 
-    // We explore the function definition only if it is not in a module flagged
-    // as opaque
+    // We explore the function definition only if it is not flagged as opaque
     let name = function_def_id_to_name(tcx, def_id);
-    if name.is_in_modules(&crate_info.crate_name, &crate_info.opaque) {
+    if crate_info.opacity.is_opaque(&name) {
         // The function is opaque
         // Store the function declaration in the declaration map
         rdecls.funs.insert(def_id, fn_decl);
@@ -973,13 +988,23 @@ fn register_hir_item(
         return Ok(());
     }
 
+    if is_test_harness_item(tcx, item) {
+        trace!(
+            "Ignoring an item generated by the test harness: {:?}",
+            def_id
+        );
+        return Ok(());
+    }
+
     // The annoying thing is that when iterating over the items in a crate, we
     // iterate over *all* the items, which is annoying with regards to the
     // *opaque* modules: we see all the definitions which are in there, and
     // not only those which are transitively reachable from the root.
     // Because of this, we need the following check: if the item is a "top"
     // item (not an item transitively reachable from an item which is not
-    // opaque) and inside an opaque module (or sub-module), we ignore it.
+    // opaque) and is opaque (because it is in an opaque module, for instance),
+    // we ignore it. Note that we still explore the opaque modules and impl
+    // blocks which may contain transparent items.
     if top_item {
         match hir_item_to_name(tcx, item) {
             Option::None => {
@@ -987,7 +1012,9 @@ fn register_hir_item(
                 return Ok(());
             }
             Option::Some(item_name) => {
-                if item_name.is_in_modules(&crate_info.crate_name, &crate_info.opaque) {
+                if crate_info.opacity.is_opaque(&item_name)
+                    && !crate_info.opacity.may_contain_transparent(&item_name)
+                {
                     return Ok(());
                 }
             }
@@ -1040,13 +1067,14 @@ fn register_hir_item(
         ItemKind::Mod(module) => {
             trace!("module");
 
-            // Explore the module, only if it was not marked as "opaque"
-            // TODO: we may want to accumulate the set of modules we found,
-            // to check that all the opaque modules given as arguments actually
-            // exist
+            // Explore the module, only if it was not marked as "opaque" (or
+            // if it may contain transparent items).
+            // Note that the patterns which don't match any item are reported
+            // in [register_crate].
             trace!("{:?}", def_id);
             let module_name = module_def_id_to_name(tcx, def_id);
-            let opaque = module_name.is_in_modules(&crate_info.crate_name, &crate_info.opaque);
+            let opaque = crate_info.opacity.is_opaque(&module_name)
+                && !crate_info.opacity.may_contain_transparent(&module_name);
             if opaque {
                 // Ignore
                 trace!("Ignoring module [{}] because marked as opaque", module_name);
@@ -1123,5 +1151,11 @@ pub fn register_crate(
     for item in tcx.hir().items() {
         register_hir_item(crate_info, &mut registered_decls, sess, tcx, true, item)?;
     }
+
+    // Check that the opaque and transparent patterns all selected some items:
+    // as we looked up the names of all the items of the crate, the patterns
+    // which were never used don't match anything.
+    crate_info.opacity.check_all_patterns_used()?;
+
     return Ok(registered_decls);
 }
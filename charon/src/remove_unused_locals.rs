@@ -4,7 +4,7 @@
 use crate::expressions::*;
 use crate::id_vector::ToUsize;
 use crate::im_ast::Var;
-use crate::llbc_ast::{FunDecl, FunDecls, Statement, SwitchTargets};
+use crate::llbc_ast::{FunDecl, FunDecls, Loop, LoopInfo, RawStatement, Statement, SwitchTargets};
 use crate::values::*;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
@@ -17,6 +17,7 @@ fn compute_used_locals_in_operand(locals: &mut HashSet<VarId::Id>, op: &Operand)
     match op {
         Operand::Copy(p) | Operand::Move(p) => compute_used_locals_in_place(locals, p),
         Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => compute_used_locals_in_rvalue(locals, rv),
     }
 }
 
@@ -31,7 +32,7 @@ fn compute_used_locals_in_rvalue(locals: &mut HashSet<VarId::Id>, rv: &Rvalue) {
         Rvalue::Use(op) => compute_used_locals_in_operand(locals, op),
         Rvalue::Ref(p, _) => compute_used_locals_in_place(locals, p),
         Rvalue::UnaryOp(_, op) => compute_used_locals_in_operand(locals, op),
-        Rvalue::BinaryOp(_, op1, op2) => {
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
             compute_used_locals_in_operand(locals, op1);
             compute_used_locals_in_operand(locals, op2);
         }
@@ -44,25 +45,32 @@ fn compute_used_locals_in_rvalue(locals: &mut HashSet<VarId::Id>, rv: &Rvalue) {
 }
 
 fn compute_used_locals_in_statement(locals: &mut HashSet<VarId::Id>, st: &Statement) {
-    match st {
-        Statement::Return => (),
-        Statement::Assign(p, rv) => {
+    match &st.content {
+        RawStatement::Return => (),
+        RawStatement::Assign(p, rv) => {
             compute_used_locals_in_rvalue(locals, rv);
             compute_used_locals_in_place(locals, p);
         }
-        Statement::FakeRead(p) => compute_used_locals_in_place(locals, p),
-        Statement::SetDiscriminant(p, _) => compute_used_locals_in_place(locals, p),
-        Statement::Drop(p) => compute_used_locals_in_place(locals, p),
-        Statement::Assert(assert) => compute_used_locals_in_operand(locals, &assert.cond),
-        Statement::Call(call) => {
+        RawStatement::FakeRead(p) => compute_used_locals_in_place(locals, p),
+        RawStatement::SetDiscriminant(p, _) => compute_used_locals_in_place(locals, p),
+        RawStatement::Drop(p) => compute_used_locals_in_place(locals, p),
+        RawStatement::Assert(assert) => compute_used_locals_in_operand(locals, &assert.cond),
+        RawStatement::Call(call) => {
             compute_used_locals_in_operands(locals, &call.args);
             compute_used_locals_in_place(locals, &call.dest);
         }
-        Statement::Panic => (),
-        Statement::Break(_) => (),
-        Statement::Continue(_) => (),
-        Statement::Nop => (),
-        Statement::Switch(op, targets) => {
+        RawStatement::Try(t) => {
+            compute_used_locals_in_operand(locals, &t.op);
+            compute_used_locals_in_place(locals, &t.dest);
+            for p in &t.drops {
+                compute_used_locals_in_place(locals, p);
+            }
+        }
+        RawStatement::Panic => (),
+        RawStatement::Break(_) => (),
+        RawStatement::Continue(_) => (),
+        RawStatement::Nop => (),
+        RawStatement::Switch(op, targets) => {
             compute_used_locals_in_operand(locals, op);
             match targets {
                 SwitchTargets::If(st1, st2) => {
@@ -77,8 +85,12 @@ fn compute_used_locals_in_statement(locals: &mut HashSet<VarId::Id>, st: &Statem
                 }
             }
         }
-        Statement::Loop(loop_body) => compute_used_locals_in_statement(locals, loop_body),
-        Statement::Sequence(st1, st2) => {
+        RawStatement::Loop(loop_) => {
+            compute_used_locals_in_statement(locals, &loop_.body);
+            locals.extend(loop_.inputs.iter());
+            locals.extend(loop_.outputs.iter());
+        }
+        RawStatement::Sequence(st1, st2) => {
             compute_used_locals_in_statement(locals, st1);
             compute_used_locals_in_statement(locals, st2);
         }
@@ -96,6 +108,7 @@ fn transform_operand(vids_map: &HashMap<VarId::Id, VarId::Id>, op: Operand) -> O
         Operand::Copy(p) => Operand::Copy(transform_place(vids_map, p)),
         Operand::Move(p) => Operand::Move(transform_place(vids_map, p)),
         Operand::Constant(ty, cv) => Operand::Constant(ty, cv),
+        Operand::Expr(ty, rv) => Operand::Expr(ty, Box::new(transform_rvalue(vids_map, *rv))),
     }
 }
 
@@ -115,6 +128,11 @@ fn transform_rvalue(vids_map: &HashMap<VarId::Id, VarId::Id>, rv: Rvalue) -> Rva
             let op2 = transform_operand(vids_map, op2);
             Rvalue::BinaryOp(binop, op1, op2)
         }
+        Rvalue::CheckedBinaryOp(binop, op1, op2) => {
+            let op1 = transform_operand(vids_map, op1);
+            let op2 = transform_operand(vids_map, op2);
+            Rvalue::CheckedBinaryOp(binop, op1, op2)
+        }
         Rvalue::Discriminant(p) => Rvalue::Discriminant(transform_place(vids_map, p)),
         Rvalue::Aggregate(kind, ops) => {
             let ops = transform_operands(vids_map, ops);
@@ -123,37 +141,67 @@ fn transform_rvalue(vids_map: &HashMap<VarId::Id, VarId::Id>, rv: Rvalue) -> Rva
     }
 }
 
-fn transform_st(vids_map: &HashMap<VarId::Id, VarId::Id>, st: Statement) -> Statement {
-    match st {
-        Statement::Return => Statement::Return,
-        Statement::Assign(p, rv) => {
-            Statement::Assign(transform_place(vids_map, p), transform_rvalue(vids_map, rv))
+/// The loop analysis may mention locals which are not used anymore, if some
+/// statements were removed after it: we filter them out
+fn transform_loop_info(vids_map: &HashMap<VarId::Id, VarId::Id>, info: LoopInfo) -> LoopInfo {
+    let vars = |vars: Vec<VarId::Id>| {
+        Vec::from_iter(vars.iter().filter_map(|id| vids_map.get(id).copied()))
+    };
+    let places = |places: Vec<Place>| {
+        Vec::from_iter(
+            places
+                .into_iter()
+                .filter(|p| vids_map.contains_key(&p.var_id))
+                .map(|p| transform_place(vids_map, p)),
+        )
+    };
+    LoopInfo {
+        reads: vars(info.reads),
+        modifies: vars(info.modifies),
+        borrows: places(info.borrows),
+        live_at_break: places(info.live_at_break),
+    }
+}
+
+/// Rename the local variables of a statement. We also use it when inlining
+/// function bodies (see [crate::inline]).
+pub(crate) fn transform_st(vids_map: &HashMap<VarId::Id, VarId::Id>, st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::Return => RawStatement::Return,
+        RawStatement::Assign(p, rv) => {
+            RawStatement::Assign(transform_place(vids_map, p), transform_rvalue(vids_map, rv))
         }
-        Statement::FakeRead(p) => Statement::FakeRead(transform_place(vids_map, p)),
-        Statement::SetDiscriminant(p, variant_id) => {
-            Statement::SetDiscriminant(transform_place(vids_map, p), variant_id)
+        RawStatement::FakeRead(p) => RawStatement::FakeRead(transform_place(vids_map, p)),
+        RawStatement::SetDiscriminant(p, variant_id) => {
+            RawStatement::SetDiscriminant(transform_place(vids_map, p), variant_id)
         }
-        Statement::Drop(p) => Statement::Drop(transform_place(vids_map, p)),
-        Statement::Assert(mut assert) => {
+        RawStatement::Drop(p) => RawStatement::Drop(transform_place(vids_map, p)),
+        RawStatement::Assert(mut assert) => {
             assert.cond = transform_operand(vids_map, assert.cond);
-            Statement::Assert(assert)
+            RawStatement::Assert(assert)
         }
-        Statement::Call(mut call) => {
+        RawStatement::Call(mut call) => {
             call.args = transform_operands(vids_map, call.args);
             call.dest = transform_place(vids_map, call.dest);
-            Statement::Call(call)
+            RawStatement::Call(call)
         }
-        Statement::Panic => Statement::Panic,
-        Statement::Break(i) => Statement::Break(i),
-        Statement::Continue(i) => Statement::Continue(i),
-        Statement::Nop => Statement::Nop,
-        Statement::Switch(op, targets) => {
+        RawStatement::Try(mut t) => {
+            t.op = transform_operand(vids_map, t.op);
+            t.dest = transform_place(vids_map, t.dest);
+            t.drops = Vec::from_iter(t.drops.into_iter().map(|p| transform_place(vids_map, p)));
+            RawStatement::Try(t)
+        }
+        RawStatement::Panic => RawStatement::Panic,
+        RawStatement::Break(i) => RawStatement::Break(i),
+        RawStatement::Continue(i) => RawStatement::Continue(i),
+        RawStatement::Nop => RawStatement::Nop,
+        RawStatement::Switch(op, targets) => {
             let op = transform_operand(vids_map, op);
             match targets {
                 SwitchTargets::If(st1, st2) => {
                     let st1 = Box::new(transform_st(vids_map, *st1));
                     let st2 = Box::new(transform_st(vids_map, *st2));
-                    Statement::Switch(op, SwitchTargets::If(st1, st2))
+                    RawStatement::Switch(op, SwitchTargets::If(st1, st2))
                 }
                 SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
                     let targets = Vec::from_iter(
@@ -163,16 +211,22 @@ fn transform_st(vids_map: &HashMap<VarId::Id, VarId::Id>, st: Statement) -> Stat
                     );
                     let otherwise = transform_st(vids_map, *otherwise);
                     let targets = SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise));
-                    Statement::Switch(op, targets)
+                    RawStatement::Switch(op, targets)
                 }
             }
         }
-        Statement::Loop(loop_body) => Statement::Loop(Box::new(transform_st(vids_map, *loop_body))),
-        Statement::Sequence(st1, st2) => Statement::Sequence(
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(transform_st(vids_map, *loop_.body)),
+            inputs: Vec::from_iter(loop_.inputs.iter().map(|id| *vids_map.get(id).unwrap())),
+            outputs: Vec::from_iter(loop_.outputs.iter().map(|id| *vids_map.get(id).unwrap())),
+            info: loop_.info.map(|info| transform_loop_info(vids_map, info)),
+        }),
+        RawStatement::Sequence(st1, st2) => RawStatement::Sequence(
             Box::new(transform_st(vids_map, *st1)),
             Box::new(transform_st(vids_map, *st2)),
         ),
-    }
+    };
+    Statement::new(st.span, content)
 }
 
 fn transform_def(mut def: FunDecl) -> FunDecl {
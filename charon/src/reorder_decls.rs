@@ -6,14 +6,15 @@ use petgraph::algo::tarjan_scc;
 use petgraph::graphmap::DiGraphMap;
 use rustc_hir::def_id::DefId;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashSet;
 use std::fmt::{Debug, Display, Error, Formatter};
+use std::hash::Hash;
 use std::vec::Vec;
 
 /// A (group of) top-level declaration(s), properly reordered.
 /// "G" stands for "generic"
-#[derive(Debug, VariantIndexArity, VariantName)]
+#[derive(Debug, Clone, VariantIndexArity, VariantName)]
 pub enum GDeclarationGroup<Id: Copy> {
     /// A non-recursive declaration
     NonRec(Id),
@@ -22,7 +23,7 @@ pub enum GDeclarationGroup<Id: Copy> {
 }
 
 /// A (group of) top-level declaration(s), properly reordered.
-#[derive(Debug, VariantIndexArity, VariantName)]
+#[derive(Debug, Clone, VariantIndexArity, VariantName)]
 pub enum DeclarationGroup<TypeId: Copy, FunId: Copy> {
     /// A type declaration group
     Type(GDeclarationGroup<TypeId>),
@@ -44,6 +45,16 @@ pub struct DeclarationsGroups<TypeId: Copy, FunId: Copy> {
     pub external_fun_ids: HashSet<FunId>,
 }
 
+impl<Id: Copy> GDeclarationGroup<Id> {
+    /// The ids of the declarations in the group
+    pub fn get_ids(&self) -> Vec<Id> {
+        match self {
+            GDeclarationGroup::NonRec(id) => vec![*id],
+            GDeclarationGroup::Rec(ids) => ids.clone(),
+        }
+    }
+}
+
 /// We use the [Debug] trait instead of [Display] for the identifiers, because
 /// the rustc [DefId] doesn't implement [Display]...
 impl<Id: Copy + Debug> Display for GDeclarationGroup<Id> {
@@ -89,6 +100,28 @@ impl<Id: Copy + Serialize> Serialize for GDeclarationGroup<Id> {
     }
 }
 
+/// The shape of the serialized declaration groups (see the [Serialize]
+/// implementation for [GDeclarationGroup])
+#[derive(Deserialize)]
+#[serde(rename = "GDeclarationGroup")]
+enum SerializedGDeclarationGroup<Id> {
+    NonRec((Id,)),
+    Rec((Vec<Id>,)),
+}
+
+impl<'de, Id: Copy + Deserialize<'de>> Deserialize<'de> for GDeclarationGroup<Id> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let group = match SerializedGDeclarationGroup::deserialize(deserializer)? {
+            SerializedGDeclarationGroup::NonRec((id,)) => GDeclarationGroup::NonRec(id),
+            SerializedGDeclarationGroup::Rec((ids,)) => GDeclarationGroup::Rec(ids),
+        };
+        Ok(group)
+    }
+}
+
 /// We use the [Debug] trait instead of [Display] for the identifiers, because
 /// the rustc [DefId] doesn't implement [Display]...
 impl<TypeId: Copy + Debug, FunId: Copy + Debug> Display for DeclarationGroup<TypeId, FunId> {
@@ -131,6 +164,30 @@ impl<TypeId: Copy + Serialize, FunId: Copy + Serialize> Serialize
     }
 }
 
+/// The shape of the serialized declaration groups (see the [Serialize]
+/// implementation for [DeclarationGroup])
+#[derive(Deserialize)]
+#[serde(rename = "DeclarationGroup")]
+enum SerializedDeclarationGroup<TypeId: Copy, FunId: Copy> {
+    Type((GDeclarationGroup<TypeId>,)),
+    Fun((GDeclarationGroup<FunId>,)),
+}
+
+impl<'de, TypeId: Copy + Deserialize<'de>, FunId: Copy + Deserialize<'de>> Deserialize<'de>
+    for DeclarationGroup<TypeId, FunId>
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let group = match SerializedDeclarationGroup::deserialize(deserializer)? {
+            SerializedDeclarationGroup::Type((group,)) => DeclarationGroup::Type(group),
+            SerializedDeclarationGroup::Fun((group,)) => DeclarationGroup::Fun(group),
+        };
+        Ok(group)
+    }
+}
+
 impl<TypeId: Copy, FunId: Copy> DeclarationsGroups<TypeId, FunId> {
     pub fn new() -> DeclarationsGroups<TypeId, FunId> {
         DeclarationsGroups {
@@ -203,39 +260,24 @@ fn def_id_is_type(decls: &RegisteredDeclarations, def_id: &DefId) -> bool {
     }
 }
 
-pub fn reorder_declarations(
-    decls: &RegisteredDeclarations,
-) -> Result<DeclarationsGroups<DefId, DefId>> {
-    trace!();
-
+/// Group the declarations in strongly connected components, and order the
+/// groups so that every declaration comes after its dependencies.
+/// [ids] gives the declarations in the order in which the user wrote them: if
+/// we don't need to move declarations, we generate them in this order.
+pub fn group_declarations<Id: Copy + Debug + Ord + Hash>(
+    ids: &Vec<Id>,
+    get_id_dependencies: &dyn Fn(Id) -> Vec<Id>,
+) -> Vec<GDeclarationGroup<Id>> {
     // Step 1: Start by building the graph
-    let mut graph = DiGraphMap::<DefId, ()>::new();
-
-    // Add the nodes - note that we are using both local and external def ids.
-    for d in decls.decls.iter() {
-        graph.add_node(*d);
+    let mut graph = DiGraphMap::<Id, ()>::new();
+    for id in ids {
+        graph.add_node(*id);
+    }
+    for id in ids {
+        for dep_id in get_id_dependencies(*id) {
+            let _ = graph.add_edge(*id, dep_id, ());
+        }
     }
-
-    // Add the edges.
-    // Note that some of the dependencies might be foreign depedencies (i.e.:
-    // not defined in the local crate).
-    // Types -> types
-    decls.types.iter().for_each(|(id, d)| {
-        d.deps.iter().for_each(|dep_id| {
-            let _ = graph.add_edge(*id, *dep_id, ());
-        })
-    });
-    // Functions -> types
-    decls.funs.iter().for_each(|(id, d)| {
-        // Functions -> types
-        d.deps_tys.iter().for_each(|dep_id| {
-            let _ = graph.add_edge(*id, *dep_id, ());
-        });
-        // Functions -> functions
-        d.deps_funs.iter().for_each(|dep_id| {
-            let _ = graph.add_edge(*id, *dep_id, ());
-        })
-    });
 
     trace!("Graph: {:?}", graph);
 
@@ -243,12 +285,39 @@ pub fn reorder_declarations(
     let sccs = tarjan_scc(&graph);
 
     // Step 3: Reorder the declarations in an order as close as possible to the one
-    // given by the user. To be more precise, if we don't need to move
-    // definitions, the order in which we generate the declarations should
-    // be the same as the one in which the user wrote them.
+    // given by the user.
+    let SCCs {
+        sccs: reordered_sccs,
+        scc_deps: _,
+    } = reorder_sccs::<Id>(get_id_dependencies, ids, &sccs);
+
+    // Finally, generate the groups
+    reordered_sccs
+        .into_iter()
+        .map(|scc| {
+            assert!(scc.len() > 0);
+            // If an SCC has length one, the declaration may be simply recursive:
+            // we determine whether it is the case by checking if the id is in
+            // its own set of dependencies.
+            if scc.len() == 1 && !get_id_dependencies(scc[0]).contains(&scc[0]) {
+                GDeclarationGroup::NonRec(scc[0])
+            } else {
+                GDeclarationGroup::Rec(scc)
+            }
+        })
+        .collect()
+}
+
+pub fn reorder_declarations(
+    decls: &RegisteredDeclarations,
+) -> Result<DeclarationsGroups<DefId, DefId>> {
+    trace!();
+
+    // Note that some of the dependencies might be foreign depedencies (i.e.:
+    // not defined in the local crate).
     let get_id_dependencies: &dyn Fn(DefId) -> Vec<DefId> = &|id| {
         if def_id_is_type(decls, &id) {
-            // Retrieve the type dependencies, and filter the foreign ids
+            // Types -> types
             decls
                 .types
                 .get(&id)
@@ -258,9 +327,8 @@ pub fn reorder_declarations(
                 .map(|id| *id)
                 .collect()
         } else {
+            // Functions -> types and functions -> functions
             let decl = &decls.funs.get(&id).unwrap();
-            // We need to chain the type and the function dependencies, and
-            // filter the foreign ids
             decl.deps_tys
                 .iter()
                 .chain(decl.deps_funs.iter())
@@ -268,65 +336,26 @@ pub fn reorder_declarations(
                 .collect()
         }
     };
-    let SCCs {
-        sccs: reordered_sccs,
-        scc_deps: _,
-    } = reorder_sccs::<DefId>(
-        get_id_dependencies,
+    let groups = group_declarations(
         &decls.decls.iter().map(|id| *id).collect(),
-        &sccs,
+        get_id_dependencies,
     );
 
-    // Finally, generate the list of declarations
+    // Generate the list of declarations
     let mut reordered_decls = DeclarationsGroups::new();
-
-    // Iterate over the SCC ids in the proper order
-    for scc in reordered_sccs.iter() {
-        // Retrieve the SCC
-        assert!(scc.len() > 0);
-
-        // Sanity check: make sure an SCC is made of type declarations only,
+    for group in groups {
+        // Sanity check: make sure a group is made of type declarations only,
         // or of function declarations only.
-        // Note that the length of an SCC should be at least 1.
-        let mut it = scc.iter();
-        let id0 = it.next().unwrap();
-        let is_type = def_id_is_type(decls, &id0);
-
-        for id in it {
+        let ids = group.get_ids();
+        let is_type = def_id_is_type(decls, &ids[0]);
+        for id in &ids[1..] {
             assert!(is_type == decls.types.get(id).is_some());
         }
 
-        // If an SCC has length one, the declaration may be simply recursive:
-        // we determine whether it is the case by checking if the def id is in
-        // its own set of dependencies.
-        let is_simply_recursive;
-        if scc.len() == 1 {
-            let deps = if is_type {
-                &decls.types.get(&id0).unwrap().deps
-            } else {
-                &decls.funs.get(&id0).unwrap().deps_funs
-            };
-
-            is_simply_recursive = deps.contains(&id0);
-        } else {
-            is_simply_recursive = false;
-        }
-
-        // Add the declaration.
-        // Note that we clone the vectors: it is not optimal, but they should
-        // be pretty small.
         if is_type {
-            if scc.len() == 1 && !is_simply_recursive {
-                reordered_decls.push(DeclarationGroup::Type(GDeclarationGroup::NonRec(*id0)));
-            } else {
-                reordered_decls.push(DeclarationGroup::Type(GDeclarationGroup::Rec(scc.clone())));
-            }
+            reordered_decls.push(DeclarationGroup::Type(group));
         } else {
-            if scc.len() == 1 && !is_simply_recursive {
-                reordered_decls.push(DeclarationGroup::Fun(GDeclarationGroup::NonRec(*id0)));
-            } else {
-                reordered_decls.push(DeclarationGroup::Fun(GDeclarationGroup::Rec(scc.clone())));
-            }
+            reordered_decls.push(DeclarationGroup::Fun(group));
         }
     }
 
@@ -6,9 +6,15 @@
 //! This is a bit too low-level for us: we only want to have the binop (which will
 //! have a precondition in our theorem prover, or will be monadic...). We thus want
 //! to remove those unnecessary checks.
+//!
+//! Some backends (model checkers, for instance) want the checks to be explicit:
+//! we don't apply this pass if the user asked to keep the arithmetic checks
+//! (see [crate::driver::TransOptions]). In this case, the checked operations
+//! are translated to [Rvalue::CheckedBinaryOp], which returns the pair
+//! `(result, overflow)`, followed by an assertion.
 
 use crate::expressions::*;
-use crate::llbc_ast::{Assert, FunDecl, FunDecls, Statement, SwitchTargets};
+use crate::llbc_ast::{Assert, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
 use crate::types::*;
 use crate::values::*;
 use std::iter::FromIterator;
@@ -107,8 +113,8 @@ fn binop_can_fail(binop: BinOp) -> bool {
 /// a unary operation, then do this operation (ex.: check that negating a number
 /// won't lead to an overflow)"
 fn check_if_assert_then_unop(st1: &Statement, st2: &Statement, st3: &Statement) -> bool {
-    match st3 {
-        Statement::Assign(_, Rvalue::UnaryOp(unop, _)) => {
+    match &st3.content {
+        RawStatement::Assign(_, Rvalue::UnaryOp(unop, _)) => {
             if unop_requires_assert_before(*unop) {
                 // We found a unary op with a precondition
                 //
@@ -143,9 +149,9 @@ fn check_if_simplifiable_assert_then_unop(
     st2: &Statement,
     st3: &Statement,
 ) -> bool {
-    match (st1, st2, st3) {
+    match (&st1.content, &st2.content, &st3.content) {
         (
-            Statement::Assign(
+            RawStatement::Assign(
                 eq_dest,
                 Rvalue::BinaryOp(
                     BinOp::Eq,
@@ -156,11 +162,11 @@ fn check_if_simplifiable_assert_then_unop(
                     ),
                 ),
             ),
-            Statement::Assert(Assert {
+            RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
             }),
-            Statement::Assign(_mp, Rvalue::UnaryOp(unop, op1)),
+            RawStatement::Assign(_mp, Rvalue::UnaryOp(unop, op1)),
         ) => {
             // Case 1: pattern with assertion
             assert!(*unop == UnOp::Neg);
@@ -183,7 +189,7 @@ fn check_if_simplifiable_assert_then_unop(
         (
             _,
             _,
-            Statement::Assign(
+            RawStatement::Assign(
                 _mp,
                 Rvalue::UnaryOp(
                     unop,
@@ -218,8 +224,9 @@ fn check_if_simplifiable_assert_then_unop(
 ///   dest := -(move x); // `move x` can be a constant
 ///   ...
 ///   ```
-fn simplify_assert_then_unop(_st1: Statement, _st2: Statement, st3: Statement) -> Statement {
-    st3
+fn simplify_assert_then_unop(st1: Statement, st2: Statement, st3: Statement) -> Statement {
+    let span = st1.span.merge(&st2.span).merge(&st3.span);
+    Statement::new(span, st3.content)
 }
 
 /// Check if this is a group of statements of the form:
@@ -232,8 +239,8 @@ fn simplify_assert_then_unop(_st1: Statement, _st2: Statement, st3: Statement) -
 /// single checked binop.
 /// Simply check if the first statements is a checked binop.
 fn check_if_binop_then_assert(st1: &Statement, st2: &Statement, st3: &Statement) -> bool {
-    match st1 {
-        Statement::Assign(_, Rvalue::BinaryOp(binop, _, _)) => {
+    match &st1.content {
+        RawStatement::Assign(_, Rvalue::CheckedBinaryOp(binop, _, _)) => {
             if binop_requires_assert_after(*binop) {
                 // We found a checked binary op.
                 //
@@ -263,14 +270,14 @@ fn check_if_binop_then_assert(st1: &Statement, st2: &Statement, st3: &Statement)
 ///   ...
 ///   ```
 fn check_if_simplifiable_binop_then_assert(st1: &Statement, st2: &Statement, st3: &Statement) {
-    match (st1, st2, st3) {
+    match (&st1.content, &st2.content, &st3.content) {
         (
-            Statement::Assign(bp, Rvalue::BinaryOp(binop, _op1, _op2)),
-            Statement::Assert(Assert {
+            RawStatement::Assign(bp, Rvalue::CheckedBinaryOp(binop, _op1, _op2)),
+            RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
             }),
-            Statement::Assign(_mp, Rvalue::Use(Operand::Move(mr))),
+            RawStatement::Assign(_mp, Rvalue::Use(Operand::Move(mr))),
         ) => {
             assert!(binop_requires_assert_after(*binop));
             assert!(!(*expected));
@@ -307,16 +314,22 @@ fn check_if_simplifiable_binop_then_assert(st1: &Statement, st2: &Statement, st3
 ///   ```
 /// to:
 ///   ```
-///   tmp := copy x + copy y; // Possibly a different binop
+///   dest := op1 + op2; // Possibly a different binop
 ///   ...
 ///   ```
-/// Note that the type of the binop changes in the two situations (in the
-/// translation, before the transformation `+` returns a pair (bool, int),
-/// after it has a monadic type).
+/// Note that the type of the binop changes in the two situations: before the
+/// transformation, the checked binop returns a pair (int, bool), after it
+/// is an unchecked binop with a monadic type.
 fn simplify_binop_then_assert(st1: Statement, st2: Statement, st3: Statement) -> Statement {
-    match (st1, st2, st3) {
-        (Statement::Assign(_, binop), Statement::Assert(_), Statement::Assign(mp, _)) => {
-            return Statement::Assign(mp, binop);
+    let span = st1.span.merge(&st2.span).merge(&st3.span);
+    match (st1.content, st2.content, st3.content) {
+        (
+            RawStatement::Assign(_, Rvalue::CheckedBinaryOp(binop, op1, op2)),
+            RawStatement::Assert(_),
+            RawStatement::Assign(mp, _),
+        ) => {
+            let binop = Rvalue::BinaryOp(binop, op1, op2);
+            return Statement::new(span, RawStatement::Assign(mp, binop));
         }
         _ => {
             unreachable!();
@@ -328,8 +341,8 @@ fn simplify_binop_then_assert(st1: Statement, st2: Statement, st3: Statement) ->
 /// an binary operation, then do this operation (ex.: check that a divisor is
 /// non zero before doing a division, panic otherwise)"
 fn check_if_assert_then_binop(st1: &Statement, st2: &Statement, st3: &Statement) -> bool {
-    match st3 {
-        Statement::Assign(_, Rvalue::BinaryOp(binop, _, _)) => {
+    match &st3.content {
+        RawStatement::Assign(_, Rvalue::BinaryOp(binop, _, _)) => {
             if binop_requires_assert_before(*binop) {
                 // We found an unchecked binop which should be simplified (division
                 // or remainder computation).
@@ -375,9 +388,9 @@ fn check_if_simplifiable_assert_then_binop(
     st2: &Statement,
     st3: &Statement,
 ) -> bool {
-    match (st1, st2, st3) {
+    match (&st1.content, &st2.content, &st3.content) {
         (
-            Statement::Assign(
+            RawStatement::Assign(
                 eq_dest,
                 Rvalue::BinaryOp(
                     BinOp::Eq,
@@ -388,11 +401,11 @@ fn check_if_simplifiable_assert_then_binop(
                     ),
                 ),
             ),
-            Statement::Assert(Assert {
+            RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
             }),
-            Statement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, Operand::Move(divisor))),
+            RawStatement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, Operand::Move(divisor))),
         ) => {
             // Case 1: pattern with copy/move and assertion
             assert!(binop_requires_assert_before(*binop));
@@ -407,7 +420,7 @@ fn check_if_simplifiable_assert_then_binop(
             true
         }
         (
-            Statement::Assign(
+            RawStatement::Assign(
                 eq_dest,
                 Rvalue::BinaryOp(
                     BinOp::Eq,
@@ -418,11 +431,11 @@ fn check_if_simplifiable_assert_then_binop(
                     ),
                 ),
             ),
-            Statement::Assert(Assert {
+            RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
             }),
-            Statement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, divisor1)),
+            RawStatement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, divisor1)),
         ) => {
             // Case 2: pattern with constant divisor and assertion
             assert!(binop_requires_assert_before(*binop));
@@ -445,7 +458,11 @@ fn check_if_simplifiable_assert_then_binop(
             }
             true
         }
-        (_, _, Statement::Assign(_mp, Rvalue::BinaryOp(_, _, Operand::Constant(_, divisor)))) => {
+        (
+            _,
+            _,
+            RawStatement::Assign(_mp, Rvalue::BinaryOp(_, _, Operand::Constant(_, divisor))),
+        ) => {
             // Case 3: no assertion to check the divisor != 0, the divisor must be a
             // non-zero constant
             let cv = divisor.as_constant_value();
@@ -475,8 +492,9 @@ fn check_if_simplifiable_assert_then_binop(
 ///   dest := move dividend / move divisor; // Can also be a `%`
 ///   ...
 ///   ```
-fn simplify_assert_then_binop(_st1: Statement, _st2: Statement, st3: Statement) -> Statement {
-    st3
+fn simplify_assert_then_binop(st1: Statement, st2: Statement, st3: Statement) -> Statement {
+    let span = st1.span.merge(&st2.span).merge(&st3.span);
+    Statement::new(span, st3.content)
 }
 
 /// Attempt to simplify a sequence of statemnets
@@ -502,11 +520,11 @@ fn simplify_st_seq(
         } else {
             // Not simplifyable
             let next_st = match st4 {
-                Option::Some(st4) => Statement::Sequence(Box::new(st3), Box::new(st4)),
+                Option::Some(st4) => Statement::new_seq(st3, st4),
                 Option::None => st3,
             };
-            let next_st = Statement::Sequence(Box::new(st2), Box::new(next_st));
-            return Statement::Sequence(Box::new(simplify_st(st1)), Box::new(simplify_st(next_st)));
+            let next_st = Statement::new_seq(st2, next_st);
+            return Statement::new_seq(simplify_st(st1), simplify_st(next_st));
         }
     };
 
@@ -514,15 +532,16 @@ fn simplify_st_seq(
     match st4 {
         Option::Some(st4) => {
             let st4 = simplify_st(st4);
-            return Statement::Sequence(Box::new(simpl_st), Box::new(st4));
+            return Statement::new_seq(simpl_st, st4);
         }
         Option::None => return simpl_st,
     }
 }
 
 fn simplify_st(st: Statement) -> Statement {
-    match st {
-        Statement::Assign(p, rv) => {
+    let span = st.span;
+    let content = match st.content {
+        RawStatement::Assign(p, rv) => {
             // Check that we never failed to simplify a binop
             match &rv {
                 Rvalue::BinaryOp(binop, _, divisor) => {
@@ -546,6 +565,11 @@ fn simplify_st(st: Statement) -> Statement {
                         }
                     }
                 }
+                Rvalue::CheckedBinaryOp(_, _, _) => {
+                    // Rustc always introduces an assertion after a checked
+                    // binop: we must have collapsed them
+                    unreachable!();
+                }
                 Rvalue::UnaryOp(unop, v) => {
                     // If it is an unsimplified unop which can fail, it must be
                     // the negation, and the value must be a constant which won't
@@ -567,19 +591,20 @@ fn simplify_st(st: Statement) -> Statement {
                 }
                 _ => (),
             }
-            Statement::Assign(p, rv)
+            RawStatement::Assign(p, rv)
         }
-        Statement::FakeRead(p) => Statement::FakeRead(p),
-        Statement::SetDiscriminant(p, vid) => Statement::SetDiscriminant(p, vid),
-        Statement::Drop(p) => Statement::Drop(p),
-        Statement::Assert(assert) => Statement::Assert(assert),
-        Statement::Call(call) => Statement::Call(call),
-        Statement::Panic => Statement::Panic,
-        Statement::Return => Statement::Return,
-        Statement::Break(i) => Statement::Break(i),
-        Statement::Continue(i) => Statement::Continue(i),
-        Statement::Nop => Statement::Nop,
-        Statement::Switch(op, targets) => {
+        RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
+        RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
+        RawStatement::Drop(p) => RawStatement::Drop(p),
+        RawStatement::Assert(assert) => RawStatement::Assert(assert),
+        RawStatement::Call(call) => RawStatement::Call(call),
+        RawStatement::Try(t) => RawStatement::Try(t),
+        RawStatement::Panic => RawStatement::Panic,
+        RawStatement::Return => RawStatement::Return,
+        RawStatement::Break(i) => RawStatement::Break(i),
+        RawStatement::Continue(i) => RawStatement::Continue(i),
+        RawStatement::Nop => RawStatement::Nop,
+        RawStatement::Switch(op, targets) => {
             let targets = match targets {
                 SwitchTargets::If(st1, st2) => {
                     SwitchTargets::If(Box::new(simplify_st(*st1)), Box::new(simplify_st(*st2)))
@@ -591,19 +616,35 @@ fn simplify_st(st: Statement) -> Statement {
                     SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
                 }
             };
-            Statement::Switch(op, targets)
+            RawStatement::Switch(op, targets)
         }
-        Statement::Loop(loop_body) => Statement::Loop(Box::new(simplify_st(*loop_body))),
-        Statement::Sequence(st1, st2) => match *st2 {
-            Statement::Sequence(st2, st3) => match *st3 {
-                Statement::Sequence(st3, st4) => {
-                    simplify_st_seq(*st1, *st2, *st3, Option::Some(*st4))
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(simplify_st(*loop_.body)),
+            ..loop_
+        }),
+        RawStatement::Sequence(st1, st2) => {
+            let st2 = *st2;
+            match st2.content {
+                RawStatement::Sequence(st2_1, st3) => {
+                    let st3 = *st3;
+                    match st3.content {
+                        RawStatement::Sequence(st3_1, st4) => {
+                            return simplify_st_seq(*st1, *st2_1, *st3_1, Option::Some(*st4));
+                        }
+                        content => {
+                            let st3 = Statement::new(st3.span, content);
+                            return simplify_st_seq(*st1, *st2_1, st3, Option::None);
+                        }
+                    }
                 }
-                st3 => simplify_st_seq(*st1, *st2, st3, Option::None),
-            },
-            st2 => Statement::Sequence(Box::new(simplify_st(*st1)), Box::new(simplify_st(st2))),
-        },
-    }
+                content => {
+                    let st2 = Statement::new(st2.span, content);
+                    return Statement::new_seq(simplify_st(*st1), simplify_st(st2));
+                }
+            }
+        }
+    };
+    Statement::new(span, content)
 }
 
 fn simplify_def(mut def: FunDecl) -> FunDecl {
@@ -0,0 +1,920 @@
+//! Rename the local variables so that every local is assigned at most once
+//! on every control-flow path. MIR reuses the locals (across the branches of
+//! a match, across the iterations of a loop, etc.): the functional backends
+//! would otherwise have to redo this renaming themselves.
+//!
+//! Whenever a local which has already been assigned is assigned again, we
+//! introduce a fresh local (a new "version" of the local, with the same name
+//! and type), and update the uses which follow. After a switch, if the
+//! branches end with different versions of a local, we introduce a fresh
+//! version and assign it at the end of every branch. For instance:
+//!   ```
+//!   x := 0;
+//!   if b { x := 1; } else { x := 2; }
+//!   y := copy x;
+//!   ```
+//! becomes:
+//!   ```
+//!   x := 0;
+//!   if b { x1 := 1; x3 := move x1; } else { x2 := 2; x3 := move x2; }
+//!   y := copy x3;
+//!   ```
+//!
+//! The loops get explicit loop-carried variables (see [crate::llbc_ast::Loop]):
+//! the locals which are initialized before the loop and assigned in the loop
+//! body become loop inputs, which we assign before entering the loop and
+//! before every `continue` to the loop, and the versions defined by the loop
+//! body and valid after the loop are the loop outputs (if they differ
+//! between the `break`s, we merge them like for the switches, by assigning
+//! a fresh version before every `break`). Note that every iteration of the
+//! loop is seen as a separate control-flow path.
+//!
+//! We leave some locals alone:
+//! - the return variable, which is read by `return`;
+//! - the borrowed locals: renaming them would change the places the
+//!   borrows refer to;
+//! - the locals which are partially updated (`x.f := ...`, or the discriminant
+//!   updates), as we can't express those updates with a fresh local.
+//!
+//! The versions of a local are moved into the merged versions and the loop
+//! inputs only when they are (fully) initialized: we don't track the drop
+//! flags, and a partially moved local is considered as initialized.
+//!
+//! We leave the dead code (the statements following a statement which doesn't
+//! fall through, like a `return`) untransformed: it is unreachable, so it
+//! doesn't matter which versions of the locals it refers to.
+//!
+//! This pass is optional: it is not in [crate::passes::DEFAULT_PASSES]. The
+//! other passes don't preserve the invariants it establishes: it must be
+//! applied after the passes which transform the statements (see
+//! [crate::passes::check_pass_order]).
+
+use crate::expressions::*;
+use crate::im_ast::Var;
+use crate::llbc_ast::{FunBody, FunDecl, FunDecls, Loop, RawStatement, Statement, SwitchTargets};
+use crate::meta::Span;
+use crate::values::*;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::iter::FromIterator;
+
+/// The state of the renaming at a given program point
+#[derive(Debug, Clone)]
+struct State {
+    /// The current versions of the locals we rename
+    versions: BTreeMap<VarId::Id, VarId::Id>,
+    /// The versions which are initialized
+    init: HashSet<VarId::Id>,
+    /// The versions which may have been assigned on the paths leading here
+    assigned: HashSet<VarId::Id>,
+}
+
+#[derive(Debug, Clone)]
+struct LoopCtx {
+    /// The loop-carried locals, with their input versions
+    inputs: Vec<(VarId::Id, VarId::Id)>,
+    /// The states at the `break`s out of the loop, in the order of the `break`s
+    /// (`None` for the `break`s in dead code)
+    breaks: Vec<Option<State>>,
+    /// The loop-carried locals which are not initialized at some `continue`:
+    /// we have to explore the loop again without them
+    invalid_inputs: HashSet<VarId::Id>,
+}
+
+struct Ctx {
+    locals: VarId::Vector<Var>,
+    /// The loops we are in (the innermost loop is the last one)
+    loops: Vec<LoopCtx>,
+}
+
+/// The assignments we introduce: `(dest, src)` gives `dest := move src`
+type Assigns = Vec<(VarId::Id, VarId::Id)>;
+
+fn local(var_id: VarId::Id) -> Place {
+    Place {
+        var_id,
+        projection: Projection::new(),
+    }
+}
+
+/// Return true if writing to the place modifies only a part of the local
+/// (writing through a reference doesn't modify the local)
+fn is_partial_write(p: &Place) -> bool {
+    match p.projection.get(0) {
+        Option::None | Option::Some(ProjectionElem::Deref) => false,
+        Option::Some(_) => true,
+    }
+}
+
+fn not_renamed_in_operand(vars: &mut HashSet<VarId::Id>, op: &Operand) {
+    match op {
+        Operand::Copy(_) | Operand::Move(_) | Operand::Constant(_, _) => (),
+        Operand::Expr(_, rv) => not_renamed_in_rvalue(vars, rv),
+    }
+}
+
+fn not_renamed_in_rvalue(vars: &mut HashSet<VarId::Id>, rv: &Rvalue) {
+    match rv {
+        Rvalue::Ref(p, _) => {
+            vars.insert(p.var_id);
+        }
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => not_renamed_in_operand(vars, op),
+        Rvalue::BinaryOp(_, op1, op2) | Rvalue::CheckedBinaryOp(_, op1, op2) => {
+            not_renamed_in_operand(vars, op1);
+            not_renamed_in_operand(vars, op2);
+        }
+        Rvalue::Discriminant(_) => (),
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                not_renamed_in_operand(vars, op);
+            }
+        }
+    }
+}
+
+fn not_renamed_in_write(vars: &mut HashSet<VarId::Id>, p: &Place) {
+    if is_partial_write(p) {
+        vars.insert(p.var_id);
+    }
+}
+
+/// Compute the locals we don't rename: the borrowed locals, and the locals
+/// which are partially updated
+fn not_renamed_in_statement(vars: &mut HashSet<VarId::Id>, st: &Statement) {
+    match &st.content {
+        RawStatement::Assign(p, rv) => {
+            not_renamed_in_rvalue(vars, rv);
+            not_renamed_in_write(vars, p);
+        }
+        RawStatement::SetDiscriminant(p, _) => {
+            if p.projection.is_empty() || is_partial_write(p) {
+                vars.insert(p.var_id);
+            }
+        }
+        RawStatement::Assert(assert) => not_renamed_in_operand(vars, &assert.cond),
+        RawStatement::Call(call) => {
+            for op in &call.args {
+                not_renamed_in_operand(vars, op);
+            }
+            not_renamed_in_write(vars, &call.dest);
+        }
+        RawStatement::Try(t) => {
+            not_renamed_in_operand(vars, &t.op);
+            not_renamed_in_write(vars, &t.dest);
+        }
+        RawStatement::Switch(op, targets) => {
+            not_renamed_in_operand(vars, op);
+            for st in targets.get_targets() {
+                not_renamed_in_statement(vars, st);
+            }
+        }
+        RawStatement::Loop(loop_) => not_renamed_in_statement(vars, &loop_.body),
+        RawStatement::Sequence(st1, st2) => {
+            not_renamed_in_statement(vars, st1);
+            not_renamed_in_statement(vars, st2);
+        }
+        RawStatement::FakeRead(_)
+        | RawStatement::Drop(_)
+        | RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+    }
+}
+
+/// Compute the locals which are (fully) assigned in a statement
+fn written_in_statement(vars: &mut BTreeSet<VarId::Id>, st: &Statement) {
+    match &st.content {
+        RawStatement::Assign(p, _) if p.projection.is_empty() => {
+            vars.insert(p.var_id);
+        }
+        RawStatement::Call(call) if call.dest.projection.is_empty() => {
+            vars.insert(call.dest.var_id);
+        }
+        RawStatement::Try(t) if t.dest.projection.is_empty() => {
+            vars.insert(t.dest.var_id);
+        }
+        RawStatement::Switch(_, targets) => {
+            for st in targets.get_targets() {
+                written_in_statement(vars, st);
+            }
+        }
+        RawStatement::Loop(loop_) => written_in_statement(vars, &loop_.body),
+        RawStatement::Sequence(st1, st2) => {
+            written_in_statement(vars, st1);
+            written_in_statement(vars, st2);
+        }
+        _ => (),
+    }
+}
+
+fn place(state: &State, p: &Place) -> Place {
+    match state.versions.get(&p.var_id) {
+        Option::Some(var_id) => Place {
+            var_id: *var_id,
+            projection: p.projection.clone(),
+        },
+        Option::None => p.clone(),
+    }
+}
+
+fn operand(state: &mut State, op: Operand) -> Operand {
+    match op {
+        Operand::Copy(p) => Operand::Copy(place(state, &p)),
+        Operand::Move(p) => {
+            let p = place(state, &p);
+            if p.projection.is_empty() {
+                state.init.remove(&p.var_id);
+            }
+            Operand::Move(p)
+        }
+        Operand::Constant(ty, cv) => Operand::Constant(ty, cv),
+        Operand::Expr(ty, rv) => Operand::Expr(ty, Box::new(rvalue(state, *rv))),
+    }
+}
+
+fn rvalue(state: &mut State, rv: Rvalue) -> Rvalue {
+    match rv {
+        Rvalue::Use(op) => Rvalue::Use(operand(state, op)),
+        Rvalue::Ref(p, kind) => Rvalue::Ref(place(state, &p), kind),
+        Rvalue::UnaryOp(unop, op) => Rvalue::UnaryOp(unop, operand(state, op)),
+        Rvalue::BinaryOp(binop, op1, op2) => {
+            let op1 = operand(state, op1);
+            let op2 = operand(state, op2);
+            Rvalue::BinaryOp(binop, op1, op2)
+        }
+        Rvalue::CheckedBinaryOp(binop, op1, op2) => {
+            let op1 = operand(state, op1);
+            let op2 = operand(state, op2);
+            Rvalue::CheckedBinaryOp(binop, op1, op2)
+        }
+        Rvalue::Discriminant(p) => Rvalue::Discriminant(place(state, &p)),
+        Rvalue::Aggregate(kind, ops) => {
+            let ops = Vec::from_iter(ops.into_iter().map(|op| operand(state, op)));
+            Rvalue::Aggregate(kind, ops)
+        }
+    }
+}
+
+fn move_assigns(span: Span, assigns: &Assigns) -> Vec<Statement> {
+    assigns
+        .iter()
+        .map(|(dest, src)| {
+            let content =
+                RawStatement::Assign(local(*dest), Rvalue::Use(Operand::Move(local(*src))));
+            Statement::new(span, content)
+        })
+        .collect()
+}
+
+/// Build the sequence `st1; st2`, keeping the sequences right-nested
+fn chain(st1: Statement, st2: Statement) -> Statement {
+    match st1.content {
+        RawStatement::Sequence(st11, st12) => Statement::new_seq(*st11, chain(*st12, st2)),
+        _ => Statement::new_seq(st1, st2),
+    }
+}
+
+fn append(st: Statement, sts: Vec<Statement>) -> Statement {
+    sts.into_iter().fold(st, chain)
+}
+
+fn prepend(sts: Vec<Statement>, st: Statement) -> Statement {
+    sts.into_iter()
+        .rev()
+        .fold(st, |st, prev| Statement::new_seq(prev, st))
+}
+
+/// Insert the assignments to the outputs of a loop before the `break`s out of
+/// this loop. [depth] counts the loops we entered in the loop body.
+fn insert_break_assigns(
+    st: Statement,
+    depth: usize,
+    assigns: &mut std::vec::IntoIter<Assigns>,
+) -> Statement {
+    let content = match st.content {
+        RawStatement::Break(i) if i == depth => {
+            let sts = move_assigns(st.span, &assigns.next().unwrap());
+            return prepend(sts, Statement::new(st.span, RawStatement::Break(i)));
+        }
+        RawStatement::Sequence(st1, st2) => {
+            let st1 = insert_break_assigns(*st1, depth, assigns);
+            let st2 = insert_break_assigns(*st2, depth, assigns);
+            RawStatement::Sequence(Box::new(st1), Box::new(st2))
+        }
+        RawStatement::Switch(op, targets) => {
+            let targets = match targets {
+                SwitchTargets::If(st1, st2) => {
+                    let st1 = insert_break_assigns(*st1, depth, assigns);
+                    let st2 = insert_break_assigns(*st2, depth, assigns);
+                    SwitchTargets::If(Box::new(st1), Box::new(st2))
+                }
+                SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                    let targets = Vec::from_iter(
+                        targets
+                            .into_iter()
+                            .map(|(v, e)| (v, insert_break_assigns(e, depth, assigns))),
+                    );
+                    let otherwise = insert_break_assigns(*otherwise, depth, assigns);
+                    SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise))
+                }
+            };
+            RawStatement::Switch(op, targets)
+        }
+        RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+            body: Box::new(insert_break_assigns(*loop_.body, depth + 1, assigns)),
+            ..loop_
+        }),
+        content => content,
+    };
+    Statement::new(st.span, content)
+}
+
+impl Ctx {
+    /// Introduce a new version of a local
+    fn fresh_version(&mut self, var_id: VarId::Id) -> VarId::Id {
+        let index = VarId::Id::new(self.locals.len());
+        let var = Var {
+            index,
+            ..self.locals.get(var_id).unwrap().clone()
+        };
+        self.locals.push_back(var);
+        index
+    }
+
+    /// Update the state after an assignment to a place, and rename the place
+    fn write(&mut self, state: &mut State, p: &Place) -> Place {
+        if !p.projection.is_empty() || !state.versions.contains_key(&p.var_id) {
+            return place(state, p);
+        }
+        // We reuse the local itself for its first assignment
+        let var_id = if state.assigned.contains(&p.var_id) {
+            self.fresh_version(p.var_id)
+        } else {
+            p.var_id
+        };
+        state.versions.insert(p.var_id, var_id);
+        state.init.insert(var_id);
+        state.assigned.insert(var_id);
+        local(var_id)
+    }
+
+    /// Join the states at the end of several branches. Return the state after
+    /// the join (`None` if no branch falls through), and the assignments to
+    /// append to every branch.
+    fn join(&mut self, states: &[Option<State>]) -> (Option<State>, Vec<Assigns>) {
+        let mut assigns: Vec<Assigns> = vec![Vec::new(); states.len()];
+        let reachable: Vec<&State> = states.iter().flatten().collect();
+        if reachable.is_empty() {
+            return (Option::None, assigns);
+        }
+
+        let mut joined = State {
+            versions: BTreeMap::new(),
+            init: HashSet::new(),
+            assigned: HashSet::new(),
+        };
+        for state in &reachable {
+            joined.assigned.extend(state.assigned.iter());
+        }
+        for var_id in reachable[0].versions.keys() {
+            let versions: Vec<VarId::Id> = reachable
+                .iter()
+                .map(|state| *state.versions.get(var_id).unwrap())
+                .collect();
+            let init = reachable
+                .iter()
+                .zip(versions.iter())
+                .all(|(state, v)| state.init.contains(v));
+            let version = if versions.iter().all(|v| *v == versions[0]) {
+                versions[0]
+            } else if init {
+                // Merge the versions
+                let version = self.fresh_version(*var_id);
+                for (state, assigns) in states.iter().zip(assigns.iter_mut()) {
+                    if let Option::Some(state) = state {
+                        assigns.push((version, *state.versions.get(var_id).unwrap()));
+                    }
+                }
+                joined.assigned.insert(version);
+                version
+            } else {
+                // The local is not initialized after the join: it can't be
+                // read before being assigned again
+                versions[0]
+            };
+            joined.versions.insert(*var_id, version);
+            if init {
+                joined.init.insert(version);
+            }
+        }
+        (Option::Some(joined), assigns)
+    }
+
+    /// The assignments to the loop-carried locals before a `continue` to the
+    /// loop at the given depth (0 for the innermost loop)
+    fn continue_assigns(&mut self, state: &State, depth: usize) -> Assigns {
+        let i = self.loops.len() - 1 - depth;
+        let loop_ctx = &mut self.loops[i];
+        let mut assigns = Vec::new();
+        for (var_id, input) in &loop_ctx.inputs {
+            let version = *state.versions.get(var_id).unwrap();
+            if !state.init.contains(&version) {
+                loop_ctx.invalid_inputs.insert(*var_id);
+            } else if version != *input {
+                assigns.push((*input, version));
+            }
+        }
+        assigns
+    }
+
+    /// Transform the branches of a switch
+    fn branches(&mut self, state: &State, sts: Vec<Statement>) -> (Vec<Statement>, Option<State>) {
+        let mut nsts = Vec::new();
+        let mut states = Vec::new();
+        for st in sts {
+            let (st, state) = self.statement(state.clone(), st);
+            nsts.push(st);
+            states.push(state);
+        }
+        let (state, assigns) = self.join(&states);
+        let nsts = nsts
+            .into_iter()
+            .zip(assigns.iter())
+            .map(|(st, assigns)| {
+                let span = st.span;
+                append(st, move_assigns(span, assigns))
+            })
+            .collect();
+        (nsts, state)
+    }
+
+    fn loop_(&mut self, span: Span, state: State, loop_: Loop) -> (Statement, Option<State>) {
+        // The locals (that we rename) assigned in the loop body
+        let mut written = BTreeSet::new();
+        written_in_statement(&mut written, &loop_.body);
+        let written: BTreeSet<VarId::Id> = written
+            .into_iter()
+            .filter(|var_id| state.versions.contains_key(var_id))
+            .collect();
+
+        // The loop-carried locals are the locals assigned in the loop body
+        // which are initialized when entering the loop and at every
+        // `continue`. We start with the locals initialized before the loop,
+        // and explore the loop body again if we have to remove some of them.
+        let mut carried: Vec<VarId::Id> = written
+            .iter()
+            .filter(|var_id| state.init.contains(state.versions.get(*var_id).unwrap()))
+            .copied()
+            .collect();
+        loop {
+            let locals = self.locals.clone();
+            let loops = self.loops.clone();
+            match self.loop_with_inputs(span, &state, &written, &carried, &loop_.body) {
+                Ok(res) => return res,
+                Err(invalid) => {
+                    self.locals = locals;
+                    self.loops = loops;
+                    carried.retain(|var_id| !invalid.contains(var_id));
+                }
+            }
+        }
+    }
+
+    /// Transform a loop, given its loop-carried locals. Fail with the
+    /// loop-carried locals which are not initialized at some `continue`.
+    fn loop_with_inputs(
+        &mut self,
+        span: Span,
+        state: &State,
+        written: &BTreeSet<VarId::Id>,
+        carried: &[VarId::Id],
+        body: &Statement,
+    ) -> std::result::Result<(Statement, Option<State>), HashSet<VarId::Id>> {
+        // Introduce the input versions, and initialize them before entering
+        // the loop. The other locals assigned in the loop body are not
+        // initialized at the beginning of every iteration.
+        let mut body_state = state.clone();
+        let mut inputs = Vec::new();
+        let mut entry_assigns = Vec::new();
+        for var_id in written {
+            let version = *state.versions.get(var_id).unwrap();
+            body_state.init.remove(&version);
+            if carried.contains(var_id) {
+                let input = self.fresh_version(*var_id);
+                body_state.versions.insert(*var_id, input);
+                body_state.init.insert(input);
+                body_state.assigned.insert(input);
+                inputs.push((*var_id, input));
+                entry_assigns.push((input, version));
+            }
+        }
+        let input_vars: Vec<VarId::Id> = inputs.iter().map(|(_, input)| *input).collect();
+        self.loops.push(LoopCtx {
+            inputs,
+            breaks: Vec::new(),
+            invalid_inputs: HashSet::new(),
+        });
+
+        // Explore the body. Reaching the end of the body leads to the next
+        // iteration.
+        let (body, end_state) = self.statement(body_state, body.clone());
+        let body = match end_state {
+            Option::None => body,
+            Option::Some(end_state) => {
+                let assigns = self.continue_assigns(&end_state, 0);
+                append(body, move_assigns(span, &assigns))
+            }
+        };
+        let loop_ctx = self.loops.pop().unwrap();
+        if !loop_ctx.invalid_inputs.is_empty() {
+            return Err(loop_ctx.invalid_inputs);
+        }
+
+        // Join the states at the `break`s
+        let (after_state, break_assigns) = self.join(&loop_ctx.breaks);
+        let body = insert_break_assigns(body, 0, &mut break_assigns.into_iter());
+
+        // The outputs are the versions defined by the loop and initialized
+        // after it
+        let outputs = match &after_state {
+            Option::None => Vec::new(),
+            Option::Some(after_state) => after_state
+                .versions
+                .iter()
+                .filter(|(var_id, version)| {
+                    *version != state.versions.get(*var_id).unwrap()
+                        && after_state.init.contains(*version)
+                })
+                .map(|(_, version)| *version)
+                .collect(),
+        };
+
+        let loop_ = Loop {
+            body: Box::new(body),
+            inputs: input_vars,
+            outputs,
+            // The renaming invalidates the result of the loop analysis
+            info: Option::None,
+        };
+        let st = Statement::new(span, RawStatement::Loop(loop_));
+        Ok((prepend(move_assigns(span, &entry_assigns), st), after_state))
+    }
+
+    /// Register the `break`s in dead code, so that they match the assignments
+    /// computed by [Ctx::join] (there are none, as they are unreachable). We
+    /// visit the statements in the order of [insert_break_assigns]. [depth]
+    /// counts the loops we entered in the dead code.
+    fn dead_statement(&mut self, st: &Statement, depth: usize) {
+        match &st.content {
+            RawStatement::Break(i) if *i >= depth => {
+                let loop_index = self.loops.len() - 1 - (i - depth);
+                self.loops[loop_index].breaks.push(Option::None);
+            }
+            RawStatement::Sequence(st1, st2) => {
+                self.dead_statement(st1, depth);
+                self.dead_statement(st2, depth);
+            }
+            RawStatement::Switch(_, targets) => match targets {
+                SwitchTargets::If(st1, st2) => {
+                    self.dead_statement(st1, depth);
+                    self.dead_statement(st2, depth);
+                }
+                SwitchTargets::SwitchInt(_, targets, otherwise) => {
+                    for (_, st) in targets {
+                        self.dead_statement(st, depth);
+                    }
+                    self.dead_statement(otherwise, depth);
+                }
+            },
+            RawStatement::Loop(loop_) => self.dead_statement(&loop_.body, depth + 1),
+            _ => (),
+        }
+    }
+
+    /// Transform a statement. Return the state at the end of the statement,
+    /// or `None` if the statement doesn't fall through.
+    fn statement(&mut self, mut state: State, st: Statement) -> (Statement, Option<State>) {
+        let span = st.span;
+        let content = match st.content {
+            RawStatement::Assign(p, rv) => {
+                let rv = rvalue(&mut state, rv);
+                let p = self.write(&mut state, &p);
+                RawStatement::Assign(p, rv)
+            }
+            RawStatement::FakeRead(p) => RawStatement::FakeRead(place(&state, &p)),
+            RawStatement::SetDiscriminant(p, variant_id) => {
+                RawStatement::SetDiscriminant(place(&state, &p), variant_id)
+            }
+            RawStatement::Drop(p) => {
+                let p = place(&state, &p);
+                if p.projection.is_empty() {
+                    state.init.remove(&p.var_id);
+                }
+                RawStatement::Drop(p)
+            }
+            RawStatement::Assert(mut assert) => {
+                assert.cond = operand(&mut state, assert.cond);
+                RawStatement::Assert(assert)
+            }
+            RawStatement::Call(mut call) => {
+                call.args = Vec::from_iter(call.args.into_iter().map(|op| operand(&mut state, op)));
+                call.dest = self.write(&mut state, &call.dest);
+                RawStatement::Call(call)
+            }
+            RawStatement::Try(mut t) => {
+                t.op = operand(&mut state, t.op);
+                // The drops happen when returning early: they refer to the
+                // versions before the write
+                t.drops = Vec::from_iter(t.drops.iter().map(|p| place(&state, p)));
+                t.dest = self.write(&mut state, &t.dest);
+                RawStatement::Try(t)
+            }
+            RawStatement::Panic => {
+                return (Statement::new(span, RawStatement::Panic), Option::None)
+            }
+            RawStatement::Return => {
+                return (Statement::new(span, RawStatement::Return), Option::None)
+            }
+            RawStatement::Break(i) => {
+                let loop_index = self.loops.len() - 1 - i;
+                self.loops[loop_index].breaks.push(Option::Some(state));
+                return (Statement::new(span, RawStatement::Break(i)), Option::None);
+            }
+            RawStatement::Continue(i) => {
+                let assigns = self.continue_assigns(&state, i);
+                let st = Statement::new(span, RawStatement::Continue(i));
+                return (prepend(move_assigns(span, &assigns), st), Option::None);
+            }
+            RawStatement::Nop => RawStatement::Nop,
+            RawStatement::Sequence(st1, st2) => {
+                let (st1, state) = self.statement(state, *st1);
+                // If the first statement doesn't fall through, the second
+                // statement is dead code: we leave it untransformed
+                return match state {
+                    Option::None => {
+                        self.dead_statement(&st2, 0);
+                        (chain(st1, *st2), Option::None)
+                    }
+                    Option::Some(state) => {
+                        let (st2, state) = self.statement(state, *st2);
+                        (chain(st1, st2), state)
+                    }
+                };
+            }
+            RawStatement::Switch(op, targets) => {
+                let op = operand(&mut state, op);
+                let (targets, state) = match targets {
+                    SwitchTargets::If(st1, st2) => {
+                        let (sts, state) = self.branches(&state, vec![*st1, *st2]);
+                        let mut sts = sts.into_iter();
+                        let st1 = sts.next().unwrap();
+                        let st2 = sts.next().unwrap();
+                        (SwitchTargets::If(Box::new(st1), Box::new(st2)), state)
+                    }
+                    SwitchTargets::SwitchInt(int_ty, targets, otherwise) => {
+                        let (values, mut sts): (Vec<Vec<ScalarValue>>, Vec<Statement>) =
+                            targets.into_iter().unzip();
+                        sts.push(*otherwise);
+                        let (mut sts, state) = self.branches(&state, sts);
+                        let otherwise = sts.pop().unwrap();
+                        let targets = values.into_iter().zip(sts.into_iter()).collect();
+                        let targets =
+                            SwitchTargets::SwitchInt(int_ty, targets, Box::new(otherwise));
+                        (targets, state)
+                    }
+                };
+                return (
+                    Statement::new(span, RawStatement::Switch(op, targets)),
+                    state,
+                );
+            }
+            RawStatement::Loop(loop_) => return self.loop_(span, state, loop_),
+        };
+        (Statement::new(span, content), Option::Some(state))
+    }
+}
+
+fn transform_body(mut body: FunBody) -> FunBody {
+    // Compute the locals we rename: we don't rename the return variable, which
+    // is read by `return`
+    let mut not_renamed: HashSet<VarId::Id> = HashSet::new();
+    not_renamed.insert(VarId::ZERO);
+    not_renamed_in_statement(&mut not_renamed, &body.body);
+    let mut state = State {
+        versions: BTreeMap::new(),
+        init: HashSet::new(),
+        assigned: HashSet::new(),
+    };
+    for var in body.locals.iter() {
+        if !not_renamed.contains(&var.index) {
+            state.versions.insert(var.index, var.index);
+        }
+    }
+    // The parameters are assigned when entering the function
+    for i in 1..=body.arg_count {
+        state.init.insert(VarId::Id::new(i));
+        state.assigned.insert(VarId::Id::new(i));
+    }
+
+    let mut ctx = Ctx {
+        locals: body.locals,
+        loops: Vec::new(),
+    };
+    let (st, _) = ctx.statement(state, body.body);
+    body.body = st;
+    body.locals = ctx.locals;
+    body
+}
+
+fn transform_def(mut def: FunDecl) -> FunDecl {
+    trace!("About to update: {}", def.name);
+    def.body = def.body.map(transform_body);
+    def
+}
+
+pub fn transform(defs: FunDecls) -> FunDecls {
+    FunDecls::from_iter(defs.into_iter().map(|def| transform_def(def)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::id_vector::ToUsize;
+    use crate::llbc_ast::{RawStatement, Statement, SwitchTargets};
+    use crate::llbc_ast_utils::test_utils::*;
+    use crate::ssa::transform_body;
+    use crate::types::*;
+    use crate::values::*;
+
+    /// The locals of the tests: `b` is the input
+    static LOCALS: [Option<&str>; 5] = [None, Some("b"), Some("x"), Some("y"), Some("r")];
+
+    fn transform(st: Statement) -> Statement {
+        transform_body(body(1, &LOCALS, st)).body
+    }
+
+    fn use_(op: Operand) -> Rvalue {
+        Rvalue::Use(op)
+    }
+
+    fn break_() -> Statement {
+        st(RawStatement::Break(0))
+    }
+
+    /// The assignments of a statement, in the order of the statements
+    fn assignments(st: &Statement) -> Vec<(usize, Rvalue)> {
+        match &st.content {
+            RawStatement::Assign(p, rv) => {
+                assert!(p.projection.is_empty());
+                vec![(p.var_id.to_usize(), rv.clone())]
+            }
+            RawStatement::Sequence(st1, st2) => {
+                let mut assigns = assignments(st1);
+                assigns.extend(assignments(st2));
+                assigns
+            }
+            RawStatement::Switch(_, SwitchTargets::If(st1, st2)) => {
+                let mut assigns = assignments(st1);
+                assigns.extend(assignments(st2));
+                assigns
+            }
+            RawStatement::Loop(loop_) => assignments(&loop_.body),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_switch_join() {
+        // x := 0;
+        // if b { x := 1 } else { x := 2 };
+        // y := copy x
+        let st = transform(seq(vec![
+            assign(2, use_(constant(0))),
+            if_(
+                1,
+                assign(2, use_(constant(1))),
+                assign(2, use_(constant(2))),
+            ),
+            assign(3, use_(copy(2))),
+        ]));
+        // The branches assign fresh versions of x, which are merged in a
+        // third one
+        let expected = vec![
+            (2, use_(constant(0))),
+            (5, use_(constant(1))),
+            (7, use_(move_(5))),
+            (6, use_(constant(2))),
+            (7, use_(move_(6))),
+            (3, use_(copy(7))),
+        ];
+        assert!(assignments(&st) == expected);
+    }
+
+    #[test]
+    fn test_loop_carried() {
+        // x := 0;
+        // loop {
+        //   if b { x := copy x + 1; continue 0 } else { break 0 }
+        // };
+        // y := copy x
+        let st = transform(seq(vec![
+            assign(2, use_(constant(0))),
+            loop_(if_(
+                1,
+                seq(vec![
+                    assign(2, add(copy(2), constant(1))),
+                    st(RawStatement::Continue(0)),
+                ]),
+                break_(),
+            )),
+            assign(3, use_(copy(2))),
+        ]));
+        // x is a loop input: we assign it before entering the loop and before
+        // the `continue`
+        let expected = vec![
+            (2, use_(constant(0))),
+            (5, use_(move_(2))),
+            (6, add(copy(5), constant(1))),
+            (5, use_(move_(6))),
+            (3, use_(copy(5))),
+        ];
+        assert!(assignments(&st) == expected);
+        let sts = flatten_seq(&st);
+        match &sts[2].content {
+            RawStatement::Loop(loop_) => {
+                assert!(loop_.inputs == vec![VarId::Id::new(5)]);
+                assert!(loop_.outputs == vec![VarId::Id::new(5)]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_dead_code() {
+        // x := 0;
+        // loop {
+        //   if b { x := 1; break 0 } else { x := 2; break 0 };
+        //   y := copy x; // Dead code
+        //   break 0
+        // };
+        // y := copy x
+        let st = transform(seq(vec![
+            assign(2, use_(constant(0))),
+            loop_(seq(vec![
+                if_(
+                    1,
+                    seq(vec![assign(2, use_(constant(1))), break_()]),
+                    seq(vec![assign(2, use_(constant(2))), break_()]),
+                ),
+                assign(3, use_(copy(2))),
+                break_(),
+            ])),
+            assign(3, use_(copy(2))),
+        ]));
+        // The dead code is left untransformed, and there is no assignment
+        // to the loop output before its `break`
+        let expected = vec![
+            (2, use_(constant(0))),
+            (5, use_(move_(2))),
+            (6, use_(constant(1))),
+            (8, use_(move_(6))),
+            (7, use_(constant(2))),
+            (8, use_(move_(7))),
+            (3, use_(copy(2))),
+            (3, use_(copy(8))),
+        ];
+        assert!(assignments(&st) == expected);
+    }
+
+    #[test]
+    fn test_not_renamed() {
+        // x := 0; r := &x; x := 1;
+        // y := 0; y.0 := 1; y := 2
+        let mut field = local(3);
+        field.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Tuple(2),
+            FieldId::Id::new(0),
+        ));
+        let st = transform(seq(vec![
+            assign(2, use_(constant(0))),
+            assign(4, Rvalue::Ref(local(2), BorrowKind::Shared)),
+            assign(2, use_(constant(1))),
+            assign(3, use_(constant(0))),
+            st(RawStatement::Assign(field.clone(), use_(constant(1)))),
+            assign(3, use_(constant(2))),
+        ]));
+        // The borrowed local and the partially updated local keep their
+        // indices
+        let sts = flatten_seq(&st);
+        let dests: Vec<Place> = sts
+            .iter()
+            .map(|st| match &st.content {
+                RawStatement::Assign(p, _) => p.clone(),
+                _ => panic!(),
+            })
+            .collect();
+        let expected = vec![local(2), local(4), local(2), local(3), field, local(3)];
+        assert!(dests == expected);
+    }
+}
@@ -0,0 +1,421 @@
+//! Stable identifiers for the declarations (`--stable-ids`).
+//!
+//! By default, the type and function ids are assigned in the order in which
+//! the declarations are registered: adding a single function renumbers all the
+//! declarations which come after it, and the diffs between two versions of a
+//! .llbc file become useless. With `--stable-ids`, the id of a declaration is
+//! instead a hash of its name (which contains the disambiguators of the impl
+//! blocks), so that it only changes if the declaration is renamed, and the
+//! declarations are sorted by name: a small edit of the crate leads to a small
+//! diff of the generated file.
+//!
+//! During the translation, we still use dense ids, because we use them to index
+//! the vectors of declarations: we relabel the declarations just before
+//! serializing them, and relabel them back with dense ids when loading a file
+//! (see [crate::llbc_import]).
+
+use crate::common::*;
+use crate::expressions::*;
+use crate::im_ast::{FunDeclId, FunId, FunSig, Var};
+use crate::llbc_ast::*;
+use crate::rust_to_local_ids::*;
+use crate::types::*;
+use std::collections::HashMap;
+
+/// Hash a declaration name with the 32-bit FNV-1a hash function: the ids are
+/// serialized as 32-bit integers. Note that we can't use the hashers of the
+/// standard library, whose output is not guaranteed to be the same across
+/// releases.
+fn stable_hash(name: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in name.as_bytes() {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// Allocate the stable ids of a list of declarations, given by their names.
+/// Return the list of ids, in the same order as the names.
+///
+/// The id of a declaration is the hash of its name: it doesn't depend on the
+/// other declarations of the crate. If two declarations have the same id (the
+/// hashes of their names collide), we fail: resolving the collision would
+/// make the id of a declaration depend on the presence of the other one.
+fn allocate_stable_ids(names: &Vec<String>) -> Result<Vec<usize>> {
+    let mut taken: HashMap<u32, &String> = HashMap::new();
+    let mut ids = Vec::new();
+    for name in names {
+        let id = stable_hash(name);
+        if let Option::Some(other) = taken.insert(id, name) {
+            error!(
+                "Could not compute the stable ids: the names {} and {} have the same hash ({}). Please don't use `--stable-ids`.",
+                other, name, id
+            );
+            return Err(());
+        }
+        ids.push(id as usize);
+    }
+    Ok(ids)
+}
+
+/// A mapping from type and function ids to new ids
+pub struct IdMap {
+    pub types: HashMap<TypeDeclId::Id, TypeDeclId::Id>,
+    pub funs: HashMap<FunDeclId::Id, FunDeclId::Id>,
+}
+
+impl IdMap {
+    /// Compute the stable ids of the declarations (see the module
+    /// documentation)
+    pub fn stable_ids(type_defs: &TypeDecls, fun_defs: &FunDecls) -> Result<IdMap> {
+        let type_names: Vec<String> = type_defs.types.iter().map(|d| d.name.to_string()).collect();
+        let fun_names: Vec<String> = fun_defs.iter().map(|d| d.name.to_string()).collect();
+        let type_ids = allocate_stable_ids(&type_names)?;
+        let fun_ids = allocate_stable_ids(&fun_names)?;
+        Ok(IdMap {
+            types: type_defs
+                .types
+                .iter()
+                .zip(type_ids.into_iter())
+                .map(|(d, id)| (d.def_id, TypeDeclId::Id::new(id)))
+                .collect(),
+            funs: fun_defs
+                .iter()
+                .zip(fun_ids.into_iter())
+                .map(|(d, id)| (d.def_id, FunDeclId::Id::new(id)))
+                .collect(),
+        })
+    }
+
+    /// Map the ids of the declarations to their positions in the vectors of
+    /// declarations. This is the inverse of the relabeling performed with
+    /// [IdMap::stable_ids], once the declarations have been sorted.
+    pub fn dense_ids(type_defs: &TypeDecls, fun_defs: &FunDecls) -> IdMap {
+        IdMap {
+            types: type_defs
+                .types
+                .iter()
+                .enumerate()
+                .map(|(i, d)| (d.def_id, TypeDeclId::Id::new(i)))
+                .collect(),
+            funs: fun_defs
+                .iter()
+                .enumerate()
+                .map(|(i, d)| (d.def_id, FunDeclId::Id::new(i)))
+                .collect(),
+        }
+    }
+
+    pub fn type_id(&self, id: TypeDeclId::Id) -> TypeDeclId::Id {
+        *self.types.get(&id).unwrap()
+    }
+
+    pub fn fun_id(&self, id: FunDeclId::Id) -> FunDeclId::Id {
+        *self.funs.get(&id).unwrap()
+    }
+
+    fn ty<R: Clone + Eq>(&self, ty: &Ty<R>) -> Ty<R> {
+        match ty {
+            Ty::Adt(id, regions, tys) => {
+                let id = match id {
+                    TypeId::Adt(id) => TypeId::Adt(self.type_id(*id)),
+                    TypeId::Tuple | TypeId::Assumed(_) => id.clone(),
+                };
+                let tys = tys.iter().map(|ty| self.ty(ty)).collect();
+                Ty::Adt(id, regions.clone(), tys)
+            }
+            Ty::Array(ty) => Ty::Array(Box::new(self.ty(ty))),
+            Ty::Slice(ty) => Ty::Slice(Box::new(self.ty(ty))),
+            Ty::Ref(r, ty, kind) => Ty::Ref(r.clone(), Box::new(self.ty(ty)), *kind),
+            Ty::TypeVar(_) | Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => {
+                ty.clone()
+            }
+        }
+    }
+
+    fn place(&self, p: &Place) -> Place {
+        let projection = p
+            .projection
+            .iter()
+            .map(|pe| match pe {
+                ProjectionElem::Field(FieldProjKind::Adt(id, vid), fid) => {
+                    ProjectionElem::Field(FieldProjKind::Adt(self.type_id(*id), *vid), *fid)
+                }
+                _ => pe.clone(),
+            })
+            .collect();
+        Place {
+            var_id: p.var_id,
+            projection,
+        }
+    }
+
+    fn operand(&self, op: &Operand) -> Operand {
+        match op {
+            Operand::Copy(p) => Operand::Copy(self.place(p)),
+            Operand::Move(p) => Operand::Move(self.place(p)),
+            Operand::Constant(ty, cv) => Operand::Constant(self.ty(ty), cv.clone()),
+            Operand::Expr(ty, rv) => Operand::Expr(self.ty(ty), Box::new(self.rvalue(rv))),
+        }
+    }
+
+    fn operands(&self, ops: &Vec<Operand>) -> Vec<Operand> {
+        ops.iter().map(|op| self.operand(op)).collect()
+    }
+
+    fn rvalue(&self, rv: &Rvalue) -> Rvalue {
+        match rv {
+            Rvalue::Use(op) => Rvalue::Use(self.operand(op)),
+            Rvalue::Ref(p, kind) => Rvalue::Ref(self.place(p), *kind),
+            Rvalue::UnaryOp(unop, op) => Rvalue::UnaryOp(*unop, self.operand(op)),
+            Rvalue::BinaryOp(binop, op1, op2) => {
+                Rvalue::BinaryOp(*binop, self.operand(op1), self.operand(op2))
+            }
+            Rvalue::CheckedBinaryOp(binop, op1, op2) => {
+                Rvalue::CheckedBinaryOp(*binop, self.operand(op1), self.operand(op2))
+            }
+            Rvalue::Discriminant(p) => Rvalue::Discriminant(self.place(p)),
+            Rvalue::Aggregate(kind, ops) => {
+                let kind = match kind {
+                    AggregateKind::Tuple => AggregateKind::Tuple,
+                    AggregateKind::Option(vid, ty) => AggregateKind::Option(*vid, self.ty(ty)),
+                    AggregateKind::Adt(id, vid, regions, tys) => AggregateKind::Adt(
+                        self.type_id(*id),
+                        *vid,
+                        regions.clone(),
+                        tys.iter().map(|ty| self.ty(ty)).collect(),
+                    ),
+                };
+                Rvalue::Aggregate(kind, self.operands(ops))
+            }
+        }
+    }
+
+    fn statement(&self, st: &Statement) -> Statement {
+        let content = match &st.content {
+            RawStatement::Assign(p, rv) => RawStatement::Assign(self.place(p), self.rvalue(rv)),
+            RawStatement::FakeRead(p) => RawStatement::FakeRead(self.place(p)),
+            RawStatement::SetDiscriminant(p, vid) => {
+                RawStatement::SetDiscriminant(self.place(p), *vid)
+            }
+            RawStatement::Drop(p) => RawStatement::Drop(self.place(p)),
+            RawStatement::Assert(assert) => RawStatement::Assert(Assert {
+                cond: self.operand(&assert.cond),
+                expected: assert.expected,
+            }),
+            RawStatement::Call(call) => {
+                let func = match &call.func {
+                    FunId::Regular(id) => FunId::Regular(self.fun_id(*id)),
+                    FunId::Assumed(_) => call.func.clone(),
+                };
+                RawStatement::Call(Call {
+                    func,
+                    region_args: call.region_args.clone(),
+                    type_args: call.type_args.iter().map(|ty| self.ty(ty)).collect(),
+                    args: self.operands(&call.args),
+                    dest: self.place(&call.dest),
+                })
+            }
+            RawStatement::Try(t) => RawStatement::Try(Try {
+                op: self.operand(&t.op),
+                dest: self.place(&t.dest),
+                drops: t.drops.iter().map(|p| self.place(p)).collect(),
+            }),
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop => st.content.clone(),
+            RawStatement::Sequence(st1, st2) => {
+                RawStatement::Sequence(Box::new(self.statement(st1)), Box::new(self.statement(st2)))
+            }
+            RawStatement::Switch(op, targets) => {
+                let targets = match targets {
+                    SwitchTargets::If(st1, st2) => SwitchTargets::If(
+                        Box::new(self.statement(st1)),
+                        Box::new(self.statement(st2)),
+                    ),
+                    SwitchTargets::SwitchInt(int_ty, branches, otherwise) => {
+                        let branches = branches
+                            .iter()
+                            .map(|(values, st)| (values.clone(), self.statement(st)))
+                            .collect();
+                        SwitchTargets::SwitchInt(
+                            *int_ty,
+                            branches,
+                            Box::new(self.statement(otherwise)),
+                        )
+                    }
+                };
+                RawStatement::Switch(self.operand(op), targets)
+            }
+            RawStatement::Loop(loop_) => RawStatement::Loop(Loop {
+                body: Box::new(self.statement(&loop_.body)),
+                inputs: loop_.inputs.clone(),
+                outputs: loop_.outputs.clone(),
+                info: loop_.info.as_ref().map(|info| LoopInfo {
+                    borrows: info.borrows.iter().map(|p| self.place(p)).collect(),
+                    live_at_break: info.live_at_break.iter().map(|p| self.place(p)).collect(),
+                    ..info.clone()
+                }),
+            }),
+        };
+        Statement {
+            dataflow: st.dataflow.clone(),
+            ..Statement::new(st.span, content)
+        }
+    }
+
+    fn fields(&self, fields: &FieldId::Vector<Field>) -> FieldId::Vector<Field> {
+        fields
+            .iter()
+            .map(|f| Field {
+                ty: self.ty(&f.ty),
+                ..f.clone()
+            })
+            .collect()
+    }
+
+    fn type_decl(&self, def: &TypeDecl) -> TypeDecl {
+        let kind = match &def.kind {
+            TypeDeclKind::Struct(fields) => TypeDeclKind::Struct(self.fields(fields)),
+            TypeDeclKind::Enum(variants) => TypeDeclKind::Enum(
+                variants
+                    .iter()
+                    .map(|v| Variant {
+                        fields: self.fields(&v.fields),
+                        ..v.clone()
+                    })
+                    .collect(),
+            ),
+            TypeDeclKind::Opaque => TypeDeclKind::Opaque,
+        };
+        TypeDecl {
+            def_id: self.type_id(def.def_id),
+            kind,
+            ..def.clone()
+        }
+    }
+
+    fn fun_decl(&self, def: &FunDecl) -> FunDecl {
+        let signature = FunSig {
+            inputs: def.signature.inputs.iter().map(|ty| self.ty(ty)).collect(),
+            output: self.ty(&def.signature.output),
+            ..def.signature.clone()
+        };
+        let body = def.body.as_ref().map(|body| FunBody {
+            arg_count: body.arg_count,
+            locals: body
+                .locals
+                .iter()
+                .map(|v| Var {
+                    ty: self.ty(&v.ty),
+                    ..v.clone()
+                })
+                .collect(),
+            body: self.statement(&body.body),
+        });
+        FunDecl {
+            def_id: self.fun_id(def.def_id),
+            signature,
+            body,
+            ..def.clone()
+        }
+    }
+
+    fn decl_group<Id: Copy>(
+        group: &GDeclarationGroup<Id>,
+        f: &dyn Fn(Id) -> Id,
+    ) -> GDeclarationGroup<Id> {
+        match group {
+            GDeclarationGroup::NonRec(id) => GDeclarationGroup::NonRec(f(*id)),
+            GDeclarationGroup::Rec(ids) => {
+                GDeclarationGroup::Rec(ids.iter().map(|id| f(*id)).collect())
+            }
+        }
+    }
+
+    /// Relabel the declaration groups
+    pub fn relabel_decl_groups(&self, decls: &Vec<DeclarationGroup>) -> Vec<DeclarationGroup> {
+        decls
+            .iter()
+            .map(|group| match group {
+                DeclarationGroup::Type(group) => {
+                    DeclarationGroup::Type(IdMap::decl_group(group, &|id| self.type_id(id)))
+                }
+                DeclarationGroup::Fun(group) => {
+                    DeclarationGroup::Fun(IdMap::decl_group(group, &|id| self.fun_id(id)))
+                }
+            })
+            .collect()
+    }
+
+    /// Relabel the declarations. We preserve the order of the declarations.
+    pub fn relabel(
+        &self,
+        decls: &Vec<DeclarationGroup>,
+        type_defs: &TypeDecls,
+        fun_defs: &FunDecls,
+    ) -> (Vec<DeclarationGroup>, TypeDecls, FunDecls) {
+        let decls = self.relabel_decl_groups(decls);
+        let type_defs = TypeDecls {
+            types: type_defs.types.iter().map(|d| self.type_decl(d)).collect(),
+        };
+        let fun_defs = fun_defs.iter().map(|d| self.fun_decl(d)).collect();
+        (decls, type_defs, fun_defs)
+    }
+}
+
+/// Relabel the declarations with stable ids, and sort them by name (see the
+/// module documentation). The map must have been computed with
+/// [IdMap::stable_ids] over all the declarations of the crate: the declarations
+/// given here may be a subset of those (for instance, the declarations of a
+/// module).
+pub fn relabel_with_stable_ids(
+    map: &IdMap,
+    decls: &Vec<DeclarationGroup>,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+) -> (Vec<DeclarationGroup>, TypeDecls, FunDecls) {
+    let (decls, type_defs, fun_defs) = map.relabel(decls, type_defs, fun_defs);
+
+    let mut types: Vec<TypeDecl> = type_defs.types.into_iter().collect();
+    types.sort_by(|d1, d2| d1.name.to_string().cmp(&d2.name.to_string()));
+    let mut funs: Vec<FunDecl> = fun_defs.into_iter().collect();
+    funs.sort_by(|d1, d2| d1.name.to_string().cmp(&d2.name.to_string()));
+
+    (
+        decls,
+        TypeDecls {
+            types: TypeDeclId::Vector::from(types),
+        },
+        FunDeclId::Vector::from(funs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_stable_hash() {
+        // Reference values of the FNV-1a hash function
+        assert!(super::stable_hash("") == 0x811c9dc5);
+        assert!(super::stable_hash("a") == 0xe40c292c);
+        assert!(super::stable_hash("foobar") == 0xbf9cf968);
+    }
+
+    #[test]
+    fn test_allocate_stable_ids() {
+        // The ids don't depend on the other names
+        let names = vec!["b".to_string(), "a".to_string()];
+        let ids = super::allocate_stable_ids(&names).unwrap();
+        assert!(ids[0] == super::stable_hash("b") as usize);
+        assert!(ids[1] == super::stable_hash("a") as usize);
+        let names = vec!["a".to_string()];
+        assert!(super::allocate_stable_ids(&names).unwrap()[0] == ids[1]);
+
+        // We fail in case of collision
+        let names = vec!["b".to_string(), "a".to_string(), "a".to_string()];
+        assert!(super::allocate_stable_ids(&names).is_err());
+    }
+}
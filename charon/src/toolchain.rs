@@ -0,0 +1,313 @@
+//! Utilities to check that the compiled external dependencies were built
+//! with the same version of the compiler as Charon, and to rebuild them with
+//! the proper toolchain if necessary.
+//!
+//! Charon relies on a specific nightly version of rustc (see the
+//! `rust-toolchain` file), which may be different from the one used by the
+//! user to build his project. When this happens, rustc refuses to load the
+//! compiled dependencies, and the error message it generates is not very
+//! informative. We thus check the versions ourselves beforehand.
+
+use crate::common::*;
+use log::info;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The content of the `rust-toolchain` file Charon was built with.
+static RUST_TOOLCHAIN_FILE: &str = include_str!("../rust-toolchain");
+
+/// The header which starts all the metadata files generated by rustc.
+/// The header is actually followed by some zeros then by the metadata
+/// version number, which we ignore.
+static METADATA_MAGIC: &[u8] = b"rust";
+/// The size of the full metadata header (magic + zeros + version number)
+const METADATA_HEADER_LEN: usize = 8;
+/// The metadata header is followed by the position of the crate root
+/// (a 32-bit integer), then by the rustc version string.
+const METADATA_ROOT_POS_LEN: usize = 4;
+
+/// The header of the `ar` archives (.rlib files are `ar` archives)
+static AR_MAGIC: &[u8] = b"!<arch>\n";
+/// The size of the header of a member in an `ar` archive
+const AR_MEMBER_HEADER_LEN: usize = 60;
+/// The name of the member containing the metadata in a .rlib archive
+static RLIB_METADATA_MEMBER: &str = "lib.rmeta";
+
+#[derive(Deserialize)]
+struct ToolchainSection {
+    channel: String,
+}
+
+#[derive(Deserialize)]
+struct ToolchainFile {
+    toolchain: ToolchainSection,
+}
+
+/// Return the toolchain Charon is pinned to (ex.: "nightly-2022-01-29").
+pub fn pinned_toolchain() -> String {
+    let file: ToolchainFile = toml::from_str(RUST_TOOLCHAIN_FILE)
+        .expect(" ⚠️  Error reading the `rust-toolchain` file Charon was built with");
+    file.toolchain.channel
+}
+
+/// Return the version string of the compiler Charon is linked with, in the
+/// same format as the one rustc writes in the metadata of the compiled
+/// libraries (ex.: "rustc 1.60.0-nightly (a00e130da 2022-01-29)").
+pub fn charon_rustc_version() -> String {
+    format!(
+        "rustc {}",
+        rustc_interface::util::version_str().unwrap_or("unknown version")
+    )
+}
+
+/// Read an unsigned LEB128-encoded integer (this is the encoding rustc uses
+/// for the integers in the metadata).
+fn read_leb128_usize(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut result: usize = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= ((byte & 0x7F) as usize).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+        shift += 7;
+    }
+}
+
+/// Read the version of the compiler which generated some metadata.
+///
+/// The metadata starts with a header, followed by the position of the crate
+/// root, followed by the version of the compiler, encoded as a string
+/// (i.e., its length followed by its bytes).
+fn read_metadata_rustc_version(metadata: &[u8]) -> Option<String> {
+    if !metadata.starts_with(METADATA_MAGIC) {
+        return None;
+    }
+
+    let mut pos = METADATA_HEADER_LEN + METADATA_ROOT_POS_LEN;
+    let len = read_leb128_usize(metadata, &mut pos)?;
+    let version = metadata.get(pos..pos + len)?;
+    let version = std::str::from_utf8(version).ok()?;
+
+    // Sanity check
+    if version.starts_with("rustc ") {
+        Some(version.to_string())
+    } else {
+        None
+    }
+}
+
+/// Retrieve the `lib.rmeta` member from a .rlib file.
+///
+/// A .rlib file is an `ar` archive: it starts with a global header, followed
+/// by a sequence of members. Every member has a header (which contains the
+/// name of the member and the size of its data) followed by its data (padded
+/// so that its size is even).
+fn read_rlib_metadata(archive: &[u8]) -> Option<&[u8]> {
+    if !archive.starts_with(AR_MAGIC) {
+        return None;
+    }
+
+    let mut pos = AR_MAGIC.len();
+    while pos + AR_MEMBER_HEADER_LEN <= archive.len() {
+        let header = &archive[pos..pos + AR_MEMBER_HEADER_LEN];
+        // The name is stored in the first 16 bytes, the size is stored in
+        // the bytes 48 to 57. Note that GNU `ar` terminates the names with
+        // a '/'.
+        let name = std::str::from_utf8(&header[0..16]).ok()?;
+        let name = name.trim_end().trim_end_matches('/');
+        let size = std::str::from_utf8(&header[48..58]).ok()?;
+        let size: usize = size.trim().parse().ok()?;
+
+        let data_start = pos + AR_MEMBER_HEADER_LEN;
+        let data = archive.get(data_start..data_start + size)?;
+        if name == RLIB_METADATA_MEMBER {
+            return Some(data);
+        }
+
+        pos = data_start + size + (size % 2);
+    }
+    None
+}
+
+/// Read the version of the compiler which was used to build a compiled
+/// library (.rmeta or .rlib file).
+///
+/// Return `None` if we couldn't find this information (for instance, the
+/// metadata of the .so files is compressed: we don't analyze those files).
+pub fn read_compiled_rustc_version(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?;
+    if extension != "rmeta" && extension != "rlib" {
+        return None;
+    }
+
+    let mut data = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .read_to_end(&mut data)
+        .ok()?;
+
+    if extension == "rmeta" {
+        read_metadata_rustc_version(&data)
+    } else {
+        read_metadata_rustc_version(read_rlib_metadata(&data)?)
+    }
+}
+
+/// Return the private target directory in which we build the dependencies,
+/// when the user asks us to do so.
+///
+/// We use a sub-directory of the target directory of the project, so that
+/// we don't overwrite the files compiled by the user.
+pub fn private_target_directory(target_directory: &str) -> PathBuf {
+    PathBuf::from(target_directory).join("charon")
+}
+
+/// Build the crate (and thus its dependencies) in debug mode, by using the
+/// toolchain Charon is pinned to, in a private target directory.
+///
+/// If [all_targets] is true, we build all the targets of the crate (so that
+/// the dev-dependencies get built).
+pub fn rebuild_dependencies(
+    manifest_path: &str,
+    target_dir: &Path,
+    all_targets: bool,
+) -> Result<()> {
+    let toolchain = pinned_toolchain();
+    info!(
+        "Building the dependencies with toolchain {} in: {:?}",
+        toolchain, target_dir
+    );
+
+    let mut output_args = vec![
+        format!("+{}", toolchain),
+        "build".to_string(),
+        "--manifest-path".to_string(),
+        manifest_path.to_string(),
+        "--target-dir".to_string(),
+        target_dir.to_str().unwrap().to_string(),
+    ];
+    if all_targets {
+        output_args.push("--all-targets".to_string());
+    }
+    trace!("cargo build command args: {:?}", output_args);
+
+    let output = std::process::Command::new("cargo")
+        .args(output_args)
+        // We are called through `cargo run`, which sets the toolchain in
+        // the environment: we don't want it to override the one we select.
+        .env_remove("RUSTUP_TOOLCHAIN")
+        .env_remove("RUSTC")
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => {
+            error!("Could not call cargo to build the dependencies: {}", err);
+            return Err(());
+        }
+    };
+    if !output.status.success() {
+        error!(
+            "Error while building the dependencies with toolchain {}:\n{}",
+            toolchain,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::*;
+
+    static VERSION: &str = "rustc 1.60.0-nightly (a00e130da 2022-01-29)";
+
+    /// Build the content of a .rmeta file generated by the given compiler
+    fn metadata(version: &str) -> Vec<u8> {
+        let mut data = b"rust\0\0\0\x06".to_vec();
+        // The position of the crate root
+        data.extend_from_slice(&[0, 0, 0x10, 0]);
+        data.push(version.len() as u8);
+        data.extend_from_slice(version.as_bytes());
+        data.extend_from_slice(b"...");
+        data
+    }
+
+    /// Build an `ar` member
+    fn ar_member(name: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            644,
+            data.len()
+        );
+        assert!(header.len() == AR_MEMBER_HEADER_LEN);
+        let mut member = header.into_bytes();
+        member.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    #[test]
+    fn test_read_leb128_usize() {
+        let mut pos = 0;
+        assert!(read_leb128_usize(&[0x05], &mut pos) == Some(5));
+        assert!(pos == 1);
+
+        let mut pos = 1;
+        assert!(read_leb128_usize(&[0xFF, 0xE5, 0x8E, 0x26, 0x00], &mut pos) == Some(624485));
+        assert!(pos == 4);
+
+        // Truncated data
+        let mut pos = 0;
+        assert!(read_leb128_usize(&[0x80, 0x80], &mut pos).is_none());
+    }
+
+    #[test]
+    fn test_read_metadata_rustc_version() {
+        assert!(read_metadata_rustc_version(&metadata(VERSION)) == Some(VERSION.to_string()));
+
+        // Not a metadata file
+        let mut data = metadata(VERSION);
+        data[0] = b'R';
+        assert!(read_metadata_rustc_version(&data).is_none());
+
+        // Not a version string
+        assert!(read_metadata_rustc_version(&metadata("1.60.0")).is_none());
+
+        // Truncated data
+        let data = metadata(VERSION);
+        assert!(read_metadata_rustc_version(&data[..20]).is_none());
+    }
+
+    #[test]
+    fn test_read_rlib_metadata() {
+        let rmeta = metadata(VERSION);
+        let mut archive = AR_MAGIC.to_vec();
+        // A member with an odd size, to check the padding
+        archive.extend(ar_member("foo-1234.o/", b"abc"));
+        archive.extend(ar_member("lib.rmeta/", &rmeta));
+        assert!(read_rlib_metadata(&archive) == Some(&rmeta[..]));
+        assert!(
+            read_metadata_rustc_version(read_rlib_metadata(&archive).unwrap())
+                == Some(VERSION.to_string())
+        );
+
+        // No metadata member
+        let mut archive = AR_MAGIC.to_vec();
+        archive.extend(ar_member("foo-1234.o/", b"abc"));
+        assert!(read_rlib_metadata(&archive).is_none());
+
+        // Not an archive
+        assert!(read_rlib_metadata(&rmeta).is_none());
+    }
+}
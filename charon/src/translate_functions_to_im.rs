@@ -9,8 +9,10 @@ use crate::common::*;
 use crate::expressions as e;
 use crate::formatter::Formatter;
 use crate::generics;
+use crate::get_mir::MirLevel;
 use crate::im_ast as ast;
-use crate::names::{function_def_id_to_name, type_def_id_to_name};
+use crate::meta;
+use crate::names::{function_def_id_to_name, type_def_id_to_name, FunName};
 use crate::regions_hierarchy as rh;
 use crate::regions_hierarchy::TypesConstraintsMap;
 use crate::rust_to_local_ids::*;
@@ -42,6 +44,12 @@ pub struct FunTransContext<'ctx> {
     /// The function definitions
     /// TODO: rename to fun_defs
     pub defs: &'ctx ast::FunDecls,
+    /// The level at which to retrieve the MIR bodies
+    pub mir_level: MirLevel,
+    /// The user mapping from external functions to assumed functions
+    pub assumed_funs: &'ctx assumed::AssumedFunsMap,
+    /// The table of the files referenced by the spans
+    pub file_table: &'ctx meta::FileTable,
 }
 
 /// A translation context for function bodies.
@@ -178,7 +186,7 @@ impl<'ctx, 'ctx1> BodyTransContext<'ctx, 'ctx1> {
         return var_id;
     }
 
-    fn push_var(&mut self, rid: u32, ty: ty::ETy, name: Option<String>) {
+    fn push_var(&mut self, rid: u32, ty: ty::ETy, name: Option<String>, span: meta::Span) {
         use crate::id_vector::ToUsize;
         let var_id = self.vars_counter.fresh_id();
         assert!(var_id.to_usize() == self.vars.len());
@@ -186,6 +194,7 @@ impl<'ctx, 'ctx1> BodyTransContext<'ctx, 'ctx1> {
             index: var_id,
             name: name.clone(),
             ty,
+            span,
         };
         self.vars.insert(var_id, var);
         self.rvars_to_ids.insert(rid, var_id);
@@ -204,6 +213,10 @@ impl<'ctx, 'ctx1> BodyTransContext<'ctx, 'ctx1> {
     fn get_type_defs(&self) -> &ty::TypeDecls {
         &self.ft_ctx.type_defs
     }
+
+    fn translate_span(&self, tcx: TyCtxt, span: Span) -> meta::Span {
+        meta::translate_span(tcx.sess.source_map(), self.ft_ctx.file_table, span)
+    }
 }
 
 impl<'ctx> Formatter<ty::TypeDeclId::Id> for FunTransContext<'ctx> {
@@ -329,7 +342,8 @@ fn translate_body_locals<'tcx, 'ctx, 'ctx1>(
         let ty = translate_ety(tcx, bt_ctx, &var.ty)?;
 
         // Add the variable to the environment
-        bt_ctx.push_var(index.as_u32(), ty, name);
+        let span = bt_ctx.translate_span(tcx, span);
+        bt_ctx.push_var(index.as_u32(), ty, name, span);
     }
 
     return Ok(());
@@ -376,14 +390,21 @@ fn translate_basic_block<'tcx, 'ctx, 'ctx1>(
         // Some statements might be ignored, hence the optional returned value
         let opt_statement = translate_statement(tcx, bt_ctx, &statement)?;
         match opt_statement {
-            Some(statement) => statements.push(statement),
+            Some(content) => statements.push(ast::Statement {
+                span: bt_ctx.translate_span(tcx, statement.source_info.span),
+                content,
+            }),
             None => (),
         }
     }
 
     // Translate the terminator
     let terminator = block.terminator();
-    let terminator = translate_terminator(tcx, bt_ctx, body, terminator)?;
+    let span = bt_ctx.translate_span(tcx, terminator.source_info.span);
+    let terminator = ast::Terminator {
+        span,
+        content: translate_terminator(tcx, bt_ctx, body, terminator)?,
+    };
 
     // Insert the block in the translated blocks
     let block = ast::BlockData {
@@ -1101,8 +1122,7 @@ fn translate_rvalue<'tcx, 'ctx, 'ctx1>(
 
             e::Rvalue::UnaryOp(e::UnOp::Cast(src_ty, tgt_ty), op)
         }
-        mir::Rvalue::BinaryOp(binop, operands) | mir::Rvalue::CheckedBinaryOp(binop, operands) => {
-            // We merge checked and unchecked binary operations
+        mir::Rvalue::BinaryOp(binop, operands) => {
             let (left, right) = operands.deref();
             e::Rvalue::BinaryOp(
                 translate_binaryop_kind(*binop),
@@ -1110,6 +1130,14 @@ fn translate_rvalue<'tcx, 'ctx, 'ctx1>(
                 translate_operand(tcx, bt_ctx, right),
             )
         }
+        mir::Rvalue::CheckedBinaryOp(binop, operands) => {
+            let (left, right) = operands.deref();
+            e::Rvalue::CheckedBinaryOp(
+                translate_binaryop_kind(*binop),
+                translate_operand(tcx, bt_ctx, left),
+                translate_operand(tcx, bt_ctx, right),
+            )
+        }
         mir::Rvalue::NullaryOp(nullop, _ty) => {
             trace!("NullOp: {:?}", nullop);
             // Nullary operations are very low-level and shouldn't be necessary
@@ -1254,7 +1282,7 @@ fn translate_statement<'tcx, 'ctx, 'ctx1>(
     tcx: TyCtxt<'tcx>,
     bt_ctx: &BodyTransContext<'ctx, 'ctx1>,
     statement: &Statement<'tcx>,
-) -> Result<Option<ast::Statement>> {
+) -> Result<Option<ast::RawStatement>> {
     trace!("About to translate statement (MIR) {:?}", statement);
 
     use ::std::ops::Deref;
@@ -1265,13 +1293,13 @@ fn translate_statement<'tcx, 'ctx, 'ctx1>(
             let t_place = translate_place(bt_ctx, place);
             let t_rvalue = translate_rvalue(tcx, bt_ctx, rvalue);
 
-            Ok(Some(ast::Statement::Assign(t_place, t_rvalue)))
+            Ok(Some(ast::RawStatement::Assign(t_place, t_rvalue)))
         }
         StatementKind::FakeRead(info) => {
             let (_read_cause, place) = info.deref();
             let t_place = translate_place(bt_ctx, place);
 
-            Ok(Some(ast::Statement::FakeRead(t_place)))
+            Ok(Some(ast::RawStatement::FakeRead(t_place)))
         }
         StatementKind::SetDiscriminant {
             place,
@@ -1279,7 +1307,9 @@ fn translate_statement<'tcx, 'ctx, 'ctx1>(
         } => {
             let t_place = translate_place(bt_ctx, place);
             let variant_id = translate_variant_id(*variant_index);
-            Ok(Some(ast::Statement::SetDiscriminant(t_place, variant_id)))
+            Ok(Some(ast::RawStatement::SetDiscriminant(
+                t_place, variant_id,
+            )))
         }
         StatementKind::StorageLive(_) => {
             // For now we ignore StorageLive
@@ -1287,7 +1317,7 @@ fn translate_statement<'tcx, 'ctx, 'ctx1>(
         }
         StatementKind::StorageDead(local) => {
             let var_id = bt_ctx.get_local(local).unwrap();
-            Ok(Some(ast::Statement::StorageDead(var_id)))
+            Ok(Some(ast::RawStatement::StorageDead(var_id)))
         }
         StatementKind::CopyNonOverlapping(_) => {
             // The program should have been rejected before
@@ -1322,13 +1352,13 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
     bt_ctx: &mut BodyTransContext<'ctx, 'ctx1>,
     body: &Body<'tcx>,
     terminator: &Terminator<'tcx>,
-) -> Result<ast::Terminator> {
+) -> Result<ast::RawTerminator> {
     trace!("About to translate terminator (MIR) {:?}", terminator);
 
     match &terminator.kind {
         TerminatorKind::Goto { target } => {
             let target = translate_basic_block(tcx, bt_ctx, body, *target)?;
-            Ok(ast::Terminator::Goto { target })
+            Ok(ast::RawTerminator::Goto { target })
         }
         TerminatorKind::SwitchInt {
             discr,
@@ -1344,7 +1374,7 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
             // Translate the switch targets
             let targets = translate_switch_targets(tcx, bt_ctx, body, &switch_ty, targets)?;
 
-            Ok(ast::Terminator::Switch { discr, targets })
+            Ok(ast::RawTerminator::Switch { discr, targets })
         }
         TerminatorKind::Resume => {
             // This is used to correctly unwind. We shouldn't get there: if
@@ -1356,13 +1386,13 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
             // but I want to see in which situations Abort appears.
             unimplemented!();
         }
-        TerminatorKind::Return => Ok(ast::Terminator::Return),
-        TerminatorKind::Unreachable => Ok(ast::Terminator::Unreachable),
+        TerminatorKind::Return => Ok(ast::RawTerminator::Return),
+        TerminatorKind::Unreachable => Ok(ast::RawTerminator::Unreachable),
         TerminatorKind::Drop {
             place,
             target,
             unwind: _,
-        } => Ok(ast::Terminator::Drop {
+        } => Ok(ast::RawTerminator::Drop {
             place: translate_place(bt_ctx, place),
             target: translate_basic_block(tcx, bt_ctx, body, *target)?,
         }),
@@ -1380,17 +1410,25 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
             // Translate the assignment
             let place = translate_place(bt_ctx, place);
             let rv = e::Rvalue::Use(translate_operand(tcx, bt_ctx, value));
-            let assign = ast::Statement::Assign(place.clone(), rv);
-            // This introduces a new block, which doesn't appear in the original MIR
+            let assign = ast::RawStatement::Assign(place.clone(), rv);
+            // This introduces a new block, which doesn't appear in the original MIR:
+            // we give it the span of the terminator
+            let span = bt_ctx.translate_span(tcx, terminator.source_info.span);
             let assign_id = bt_ctx.blocks_counter.fresh_id();
             let assign_block = ast::BlockData {
-                statements: vec![assign],
-                terminator: ast::Terminator::Goto { target },
+                statements: vec![ast::Statement {
+                    span,
+                    content: assign,
+                }],
+                terminator: ast::Terminator {
+                    span,
+                    content: ast::RawTerminator::Goto { target },
+                },
             };
             bt_ctx.push_block(assign_id, assign_block);
 
             // Translate the drop
-            let drop = ast::Terminator::Drop {
+            let drop = ast::RawTerminator::Drop {
                 place,
                 target: assign_id,
             };
@@ -1416,7 +1454,7 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
         } => {
             let cond = translate_operand(tcx, bt_ctx, cond);
             let target = translate_basic_block(tcx, bt_ctx, body, *target)?;
-            Ok(ast::Terminator::Assert {
+            Ok(ast::RawTerminator::Assert {
                 cond,
                 expected: *expected,
                 target,
@@ -1450,7 +1488,7 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
             // Also note that they are used in some passes, and not in some others
             // (they are present in mir_promoted, but not mir_optimized).
             let target = translate_basic_block(tcx, bt_ctx, body, *real_target)?;
-            Ok(ast::Terminator::Goto { target })
+            Ok(ast::RawTerminator::Goto { target })
         }
         TerminatorKind::FalseUnwind {
             real_target,
@@ -1458,7 +1496,7 @@ fn translate_terminator<'tcx, 'ctx, 'ctx1>(
         } => {
             // We consider this to be a goto
             let target = translate_basic_block(tcx, bt_ctx, body, *real_target)?;
-            Ok(ast::Terminator::Goto { target })
+            Ok(ast::RawTerminator::Goto { target })
         }
         TerminatorKind::InlineAsm {
             template: _,
@@ -1638,7 +1676,7 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
     func: &Operand<'tcx>,
     args: &Vec<Operand<'tcx>>,
     destination: &Option<(Place<'tcx>, BasicBlock)>,
-) -> Result<ast::Terminator> {
+) -> Result<ast::RawTerminator> {
     trace!();
 
     // Translate the function operand - should be a constant: we don't
@@ -1648,8 +1686,10 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
     // Retrieve the function's identifier and instantiation
     let (def_id, substs) = get_function_from_operand(func);
 
-    // Translate the name to check if is is `core::panicking::panic`
+    // Translate the name to check if is is `core::panicking::panic`.
+    // Note that the user may have mapped the function to an assumed function.
     let name = function_def_id_to_name(tcx, def_id);
+    let name = assumed::map_fun_name(bt_ctx.ft_ctx.assumed_funs, name);
 
     // If the call is `panic!`, then the destination is `None`.
     // I don't know in which other cases it can be `None`.
@@ -1660,7 +1700,7 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
         assert!(destination.is_none());
 
         // We ignore the arguments
-        Ok(ast::Terminator::Panic)
+        Ok(ast::RawTerminator::Panic)
     } else {
         assert!(destination.is_some());
         let destination = destination.unwrap();
@@ -1700,7 +1740,7 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
             let t_arg = translate_move_box_first_projector_operand(bt_ctx, arg);
 
             // Return
-            Ok(ast::Terminator::Call {
+            Ok(ast::RawTerminator::Call {
                 func: ast::FunId::Assumed(ast::AssumedFunId::BoxFree),
                 region_args: vec![],
                 type_args: vec![t_ty],
@@ -1732,7 +1772,6 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
 
             // Check if the function is considered primitive: primitive
             // functions benefit from special treatment.
-            let name = function_def_id_to_name(tcx, def_id);
             let is_prim = if def_id.is_local() {
                 false
             } else {
@@ -1745,7 +1784,7 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
 
                 let func = ast::FunId::Regular(def_id);
 
-                Ok(ast::Terminator::Call {
+                Ok(ast::RawTerminator::Call {
                     func,
                     region_args,
                     type_args,
@@ -1764,8 +1803,8 @@ fn translate_function_call<'tcx, 'ctx, 'ctx1>(
                 // `box_deref<T>`
                 // (the type parameter is not `Box<T>` but `T`).
                 translate_primitive_function_call(
-                    tcx,
                     def_id,
+                    &name,
                     region_args,
                     type_args,
                     args,
@@ -1860,24 +1899,21 @@ fn translate_arguments<'tcx, 'ctx, 'ctx1>(
 
 /// Translate a call to a function considered primitive and which is not:
 /// panic, begin_panic, box_free (those have a *very* special treatment).
-fn translate_primitive_function_call<'tcx>(
-    tcx: TyCtxt<'tcx>,
+fn translate_primitive_function_call(
     def_id: DefId,
+    name: &FunName,
     region_args: Vec<ty::ErasedRegion>,
     type_args: Vec<ty::ETy>,
     args: Vec<e::Operand>,
     dest: e::Place,
     target: ast::BlockId::Id,
-) -> Result<ast::Terminator> {
+) -> Result<ast::RawTerminator> {
     trace!("- def_id: {:?}", def_id,);
-
-    // Translate the function name
-    let name = function_def_id_to_name(tcx, def_id);
     trace!("name: {}", name);
 
     // Check if the function has primitive support, by trying to look up
     // its primitive identifier
-    let aid = assumed::get_fun_id_from_name(&name).unwrap();
+    let aid = assumed::get_fun_id_from_name(name).unwrap();
     // The function is considered primitive
 
     // Translate the function call
@@ -1898,7 +1934,7 @@ fn translate_primitive_function_call<'tcx>(
         | ast::AssumedFunId::VecNew
         | ast::AssumedFunId::VecPush
         | ast::AssumedFunId::VecInsert
-        | ast::AssumedFunId::VecLen => Ok(ast::Terminator::Call {
+        | ast::AssumedFunId::VecLen => Ok(ast::RawTerminator::Call {
             func: ast::FunId::Assumed(aid),
             region_args,
             type_args,
@@ -1927,7 +1963,7 @@ fn translate_box_deref(
     args: Vec<e::Operand>,
     dest: e::Place,
     target: ast::BlockId::Id,
-) -> Result<ast::Terminator> {
+) -> Result<ast::RawTerminator> {
     // Check the arguments
     assert!(region_args.len() == 0);
     assert!(type_args.len() == 1);
@@ -1946,7 +1982,7 @@ fn translate_box_deref(
     let boxed_ty = boxed_ty.unwrap();
     let type_args = vec![boxed_ty.clone()];
 
-    Ok(ast::Terminator::Call {
+    Ok(ast::RawTerminator::Call {
         func: ast::FunId::Assumed(aid),
         region_args,
         type_args,
@@ -1965,7 +2001,7 @@ fn translate_vec_index(
     args: Vec<e::Operand>,
     dest: e::Place,
     target: ast::BlockId::Id,
-) -> Result<ast::Terminator> {
+) -> Result<ast::RawTerminator> {
     // Check the arguments
     assert!(region_args.len() == 0);
     assert!(type_args.len() == 1);
@@ -1985,7 +2021,7 @@ fn translate_vec_index(
     };
 
     let type_args = vec![arg_ty.clone()];
-    Ok(ast::Terminator::Call {
+    Ok(ast::RawTerminator::Call {
         func: ast::FunId::Assumed(aid),
         region_args,
         type_args,
@@ -2164,6 +2200,9 @@ fn translate_function(
     ordered: &OrderedDecls,
     types_constraints: &TypesConstraintsMap,
     type_defs: &ty::TypeDecls,
+    mir_level: MirLevel,
+    assumed_funs: &assumed::AssumedFunsMap,
+    file_table: &meta::FileTable,
     fun_defs: &mut ast::FunDecls,
     def_id: ast::FunDeclId::Id,
 ) -> Result<ast::FunDecl> {
@@ -2177,10 +2216,14 @@ fn translate_function(
         ordered: ordered,
         type_defs: type_defs,
         defs: &fun_defs,
+        mir_level,
+        assumed_funs,
+        file_table,
     };
 
     // Translate the function name
     let name = function_def_id_to_name(tcx, rid);
+    let span = meta::translate_span(tcx.sess.source_map(), file_table, tcx.def_span(rid));
 
     // Translate the function signature and initialize the body translation context
     // at the same time (the signature gives us the region and type parameters,
@@ -2195,7 +2238,7 @@ fn translate_function(
         Option::None
     } else {
         // Retrieve the MIR body
-        let body = crate::get_mir::get_mir_for_def_id(tcx, rid.expect_local());
+        let body = crate::get_mir::get_mir_for_def_id(tcx, rid.expect_local(), ft_ctx.mir_level);
 
         // Initialize the local variables
         trace!("Translating the body locals");
@@ -2228,6 +2271,8 @@ fn translate_function(
     let fun_def = ast::FunDecl {
         def_id,
         name,
+        span,
+        attributes: meta::translate_attributes(tcx, rid),
         signature,
         body,
     };
@@ -2241,6 +2286,9 @@ pub fn translate_functions(
     ordered: &OrderedDecls,
     types_constraints: &TypesConstraintsMap,
     type_defs: &ty::TypeDecls,
+    mir_level: MirLevel,
+    assumed_funs: &assumed::AssumedFunsMap,
+    file_table: &meta::FileTable,
 ) -> Result<ast::FunDecls> {
     let mut fun_defs = ast::FunDecls::new();
 
@@ -2254,6 +2302,9 @@ pub fn translate_functions(
                     ordered,
                     &types_constraints,
                     type_defs,
+                    mir_level,
+                    assumed_funs,
+                    file_table,
                     &mut fun_defs,
                     *def_id,
                 )?;
@@ -2269,6 +2320,9 @@ pub fn translate_functions(
                         ordered,
                         &types_constraints,
                         type_defs,
+                        mir_level,
+                        assumed_funs,
+                        file_table,
                         &mut fun_defs,
                         *def_id,
                     )?;
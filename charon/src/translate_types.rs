@@ -3,6 +3,7 @@ use crate::common::*;
 use crate::formatter::Formatter;
 use crate::generics;
 use crate::id_vector::ToUsize;
+use crate::meta;
 use crate::names::type_def_id_to_name;
 use crate::regions_hierarchy;
 use crate::regions_hierarchy::TypesConstraintsMap;
@@ -546,6 +547,7 @@ fn translate_type_generics<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> TypeGeneri
 fn translate_transparent_type<'tcx>(
     tcx: TyCtxt<'tcx>,
     decls: &OrderedDecls,
+    file_table: &meta::FileTable,
     type_defs: &mut ty::TypeDecls,
     trans_id: ty::TypeDeclId::Id,
     def_id: DefId,
@@ -618,8 +620,15 @@ fn translate_transparent_type<'tcx>(
             };
 
             // Store the field
+            let span = meta::translate_span(
+                tcx.sess.source_map(),
+                file_table,
+                tcx.def_span(field_def.did),
+            );
             let field = ty::Field {
                 name: field_name.clone(),
+                span,
+                attributes: meta::translate_attributes(tcx, field_def.did),
                 ty: ty,
             };
             fields.push(field);
@@ -628,8 +637,15 @@ fn translate_transparent_type<'tcx>(
         }
 
         let variant_name = var_def.ident(tcx).name.to_ident_string();
+        let span = meta::translate_span(
+            tcx.sess.source_map(),
+            file_table,
+            tcx.def_span(var_def.def_id),
+        );
         variants.push(ty::Variant {
             name: variant_name,
+            span,
+            attributes: meta::translate_attributes(tcx, var_def.def_id),
             fields: ty::FieldId::Vector::from(fields),
         });
 
@@ -659,6 +675,7 @@ fn translate_transparent_type<'tcx>(
 fn translate_type<'ctx>(
     tcx: TyCtxt,
     decls: &OrderedDecls,
+    file_table: &meta::FileTable,
     type_defs: &mut ty::TypeDecls,
     trans_id: ty::TypeDeclId::Id,
 ) -> Result<()> {
@@ -674,7 +691,9 @@ fn translate_type<'ctx>(
         // - local types flagged as opaque
         ty::TypeDeclKind::Opaque
     } else {
-        translate_transparent_type(tcx, decls, type_defs, trans_id, def_id, &generics)?
+        translate_transparent_type(
+            tcx, decls, file_table, type_defs, trans_id, def_id, &generics,
+        )?
     };
 
     // Register the type
@@ -687,12 +706,15 @@ fn translate_type<'ctx>(
     } = generics;
 
     let name = type_def_id_to_name(tcx, def_id);
+    let span = meta::translate_span(tcx.sess.source_map(), file_table, tcx.def_span(def_id));
     let region_params = ty::RegionVarId::Vector::from(region_params);
     let type_params = ty::TypeVarId::Vector::from(type_params);
 
     let type_def = ty::TypeDecl {
         def_id: trans_id,
         name,
+        span,
+        attributes: meta::translate_attributes(tcx, def_id),
         region_params: region_params,
         type_params: type_params,
         kind,
@@ -720,6 +742,7 @@ fn translate_type<'ctx>(
 pub fn translate_types(
     tcx: TyCtxt,
     decls: &OrderedDecls,
+    file_table: &meta::FileTable,
 ) -> Result<(TypesConstraintsMap, ty::TypeDecls)> {
     trace!();
 
@@ -731,7 +754,7 @@ pub fn translate_types(
         match decl {
             DeclarationGroup::Type(decl) => match decl {
                 TypeDeclarationGroup::NonRec(id) => {
-                    translate_type(tcx, decls, &mut type_defs, *id)?;
+                    translate_type(tcx, decls, file_table, &mut type_defs, *id)?;
                     regions_hierarchy::compute_regions_hierarchy_for_type_decl_group(
                         &mut types_cover_regions,
                         &mut type_defs,
@@ -740,7 +763,7 @@ pub fn translate_types(
                 }
                 TypeDeclarationGroup::Rec(ids) => {
                     for id in ids {
-                        translate_type(tcx, decls, &mut type_defs, *id)?;
+                        translate_type(tcx, decls, file_table, &mut type_defs, *id)?;
                     }
                     regions_hierarchy::compute_regions_hierarchy_for_type_decl_group(
                         &mut types_cover_regions,
@@ -1,11 +1,12 @@
 #![allow(dead_code)]
 
+use crate::meta::{Attribute, Span};
 use crate::names::TypeName;
 use crate::regions_hierarchy::RegionGroups;
 pub use crate::types_utils::*;
 use im::Vector;
 use macros::{generate_index_type, EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub type FieldName = String;
 
@@ -23,7 +24,7 @@ generate_index_type!(RegionVarId);
 /// Type variable.
 /// We make sure not to mix variables and type variables by having two distinct
 /// definitions.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeVar {
     /// Unique index identifying the variable
     pub index: TypeVarId::Id,
@@ -32,7 +33,7 @@ pub struct TypeVar {
 }
 
 /// Region variable.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionVar {
     /// Unique index identifying the variable
     pub index: RegionVarId::Id,
@@ -44,7 +45,18 @@ pub struct RegionVar {
 /// ids) and in symbolic variables and projections (in which case we use region
 /// ids).
 #[derive(
-    Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, EnumIsA, EnumAsGetters, Serialize,
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    Hash,
+    PartialOrd,
+    Ord,
+    EnumIsA,
+    EnumAsGetters,
+    Serialize,
+    Deserialize,
 )]
 pub enum Region<Rid: Copy + Eq> {
     /// Static region
@@ -55,7 +67,7 @@ pub enum Region<Rid: Copy + Eq> {
 
 /// The type of erased regions. See [`Ty`](Ty) for more explanations.
 /// We could use `()`, but having a dedicated type makes things more explicit.
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum ErasedRegion {
     Erased,
 }
@@ -73,10 +85,14 @@ pub enum ErasedRegion {
 ///
 /// A type can only be an ADT (structure or enumeration), as type aliases are
 /// inlined in MIR.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeDecl {
     pub def_id: TypeDeclId::Id,
     pub name: TypeName,
+    /// The location of the definition in the source code
+    pub span: Span,
+    /// The doc comments and the tool attributes of the definition
+    pub attributes: Vec<Attribute>,
     pub region_params: RegionVarId::Vector<RegionVar>,
     pub type_params: TypeVarId::Vector<TypeVar>,
     /// The lifetime's hierarchy between the different regions.
@@ -85,7 +101,7 @@ pub struct TypeDecl {
     pub kind: TypeDeclKind,
 }
 
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum TypeDeclKind {
     Struct(FieldId::Vector<Field>),
     Enum(VariantId::Vector<Variant>),
@@ -95,19 +111,23 @@ pub enum TypeDeclKind {
     Opaque,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variant {
     pub name: String,
+    pub span: Span,
+    pub attributes: Vec<Attribute>,
     pub fields: FieldId::Vector<Field>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Field {
     pub name: Option<String>,
+    pub span: Span,
+    pub attributes: Vec<Attribute>,
     pub ty: RTy,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
 pub enum IntegerTy {
     Isize,
     I8,
@@ -123,7 +143,7 @@ pub enum IntegerTy {
     U128,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, VariantName, EnumIsA, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, VariantName, EnumIsA, Serialize, Deserialize)]
 pub enum RefKind {
     Mut,
     Shared,
@@ -132,7 +152,9 @@ pub enum RefKind {
 /// Type identifier.
 ///
 /// Allows us to factorize the code for assumed types, adts and tuples
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumAsGetters, EnumIsA, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, VariantName, EnumAsGetters, EnumIsA, Serialize, Deserialize,
+)]
 pub enum TypeId {
     /// A "regular" ADT type.
     ///
@@ -217,7 +239,9 @@ pub type ETy = Ty<ErasedRegion>;
 /// parameters (if there are). Adding types which don't satisfy this
 /// will require to update the code abstracting the signatures (to properly
 /// take into account the lifetime constraints).
-#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, EnumIsA, EnumAsGetters, VariantName, Serialize, Deserialize,
+)]
 pub enum AssumedTy {
     /// Boxes have a special treatment: we translate them as identity.
     Box,
@@ -8,7 +8,7 @@ use crate::types::*;
 use im::{HashMap, OrdSet, Vector};
 use rustc_middle::ty::{IntTy, UintTy};
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::FromIterator;
 use std::iter::Iterator;
 
@@ -263,6 +263,12 @@ impl std::string::ToString for TypeDecl {
     }
 }
 
+impl TypeDecl {
+    pub fn fmt_with_defs(&self, ty_ctx: &TypeDecls) -> String {
+        self.fmt_with_ctx(&TypeDeclFormatter { ty_ctx, def: self })
+    }
+}
+
 impl Variant {
     pub fn fmt_with_ctx<'a, T>(&'a self, ctx: &'a T) -> String
     where
@@ -658,6 +664,40 @@ impl<'a> Formatter<TypeDeclId::Id> for IncompleteFormatter<'a> {
     }
 }
 
+/// Same as [IncompleteFormatter], but uses the type declarations to print
+/// the names of the types.
+pub struct TypeDeclFormatter<'a> {
+    ty_ctx: &'a TypeDecls,
+    def: &'a TypeDecl,
+}
+
+impl<'a> Formatter<TypeVarId::Id> for TypeDeclFormatter<'a> {
+    fn format_object(&self, id: TypeVarId::Id) -> String {
+        self.def.format_object(id)
+    }
+}
+
+impl<'a, 'b, Rid: Copy + Eq> Formatter<&'b Region<Rid>> for TypeDeclFormatter<'a>
+where
+    TypeDecl: Formatter<&'b Region<Rid>>,
+{
+    fn format_object(&self, r: &'b Region<Rid>) -> String {
+        self.def.format_object(r)
+    }
+}
+
+impl<'a> Formatter<RegionVarId::Id> for TypeDeclFormatter<'a> {
+    fn format_object(&self, id: RegionVarId::Id) -> String {
+        self.def.format_object(id)
+    }
+}
+
+impl<'a> Formatter<TypeDeclId::Id> for TypeDeclFormatter<'a> {
+    fn format_object(&self, id: TypeDeclId::Id) -> String {
+        self.ty_ctx.format_object(id)
+    }
+}
+
 pub struct DummyFormatter {}
 
 impl Formatter<TypeVarId::Id> for DummyFormatter {
@@ -920,7 +960,9 @@ impl<R: Clone + std::cmp::Eq + Serialize> Serialize for Ty<R> {
         // It seems the "standard" way of doing is the following (this is
         // consistent with what the automatically generated serializer does):
         // - if the arity is > 0, use `serialize_tuple_variant`
-        // - otherwise simply serialize a string with the variant name
+        // - otherwise use `serialize_unit_variant` (which serializes a string
+        //   with the variant name in JSON, and the variant index in the binary
+        //   formats)
         if variant_arity > 0 {
             let mut vs = serializer.serialize_tuple_variant(
                 enum_name,
@@ -959,11 +1001,51 @@ impl<R: Clone + std::cmp::Eq + Serialize> Serialize for Ty<R> {
             }
             vs.end()
         } else {
-            variant_name.serialize(serializer)
+            serializer.serialize_unit_variant(enum_name, variant_index, variant_name)
         }
     }
 }
 
+/// The shape of the serialized types (see the [Serialize] implementation for
+/// [Ty]). Note that the variants with one field are serialized as tuple variants.
+#[derive(Deserialize)]
+#[serde(rename = "Ty")]
+enum SerializedTy<R: Clone + std::cmp::Eq> {
+    Adt(TypeId, Vec<R>, Vec<Ty<R>>),
+    TypeVar((TypeVarId::Id,)),
+    Bool,
+    Char,
+    Never,
+    Integer((IntegerTy,)),
+    Str,
+    Array((Box<Ty<R>>,)),
+    Slice((Box<Ty<R>>,)),
+    Ref(R, Box<Ty<R>>, RefKind),
+}
+
+impl<'de, R: Clone + std::cmp::Eq + Deserialize<'de>> Deserialize<'de> for Ty<R> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ty = match SerializedTy::deserialize(deserializer)? {
+            SerializedTy::Adt(id, regions, tys) => {
+                Ty::Adt(id, Vector::from(regions), Vector::from(tys))
+            }
+            SerializedTy::TypeVar((var_id,)) => Ty::TypeVar(var_id),
+            SerializedTy::Bool => Ty::Bool,
+            SerializedTy::Char => Ty::Char,
+            SerializedTy::Never => Ty::Never,
+            SerializedTy::Integer((int_ty,)) => Ty::Integer(int_ty),
+            SerializedTy::Str => Ty::Str,
+            SerializedTy::Array((ty,)) => Ty::Array(ty),
+            SerializedTy::Slice((ty,)) => Ty::Slice(ty),
+            SerializedTy::Ref(region, ty, ref_kind) => Ty::Ref(region, ty, ref_kind),
+        };
+        Ok(ty)
+    }
+}
+
 impl<R: Clone + std::cmp::Eq> Ty<R> {
     pub fn contains_never(&self) -> bool {
         match self {
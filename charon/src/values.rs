@@ -6,7 +6,7 @@ pub use crate::values_utils::DummyFormatter; // Don't understand why we need to
 pub use crate::values_utils::*;
 use core::hash::Hash;
 use macros::{generate_index_type, EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // We need to manipulate a lot of indices for the types, variables, definitions,
 // etc. In order not to confuse them, we define an index type for every one of
@@ -16,7 +16,9 @@ use serde::Serialize;
 generate_index_type!(VarId);
 
 /// Constant value
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, VariantName, EnumIsA, EnumAsGetters, Serialize, Deserialize,
+)]
 pub enum ConstantValue {
     Scalar(ScalarValue),
     Bool(bool),
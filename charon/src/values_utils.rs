@@ -7,7 +7,7 @@ use crate::formatter::Formatter;
 use crate::types::*;
 use crate::values::*;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub fn var_id_to_pretty_string(id: VarId::Id) -> String {
     format!("var@{}", id.to_string()).to_owned()
@@ -319,7 +319,60 @@ impl Serialize for ScalarValue {
             };
             vs.end()
         } else {
-            variant_name.serialize(serializer)
+            serializer.serialize_unit_variant(enum_name, variant_index, variant_name)
         }
     }
 }
+
+/// The shape of the serialized scalar values (see the [Serialize] implementation
+/// for [ScalarValue]): the integers are encoded as strings.
+#[derive(Deserialize)]
+#[serde(rename = "ScalarValue")]
+enum SerializedScalarValue {
+    Isize((String,)),
+    I8((String,)),
+    I16((String,)),
+    I32((String,)),
+    I64((String,)),
+    I128((String,)),
+    Usize((String,)),
+    U8((String,)),
+    U16((String,)),
+    U32((String,)),
+    U64((String,)),
+    U128((String,)),
+}
+
+/// Parse an integer serialized as a string
+fn parse_scalar<'de, D, T>(s: &str) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    use serde::de::Error;
+    s.parse()
+        .map_err(|_| D::Error::custom(format!("Invalid scalar value: {:?}", s)))
+}
+
+impl<'de> Deserialize<'de> for ScalarValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v = match SerializedScalarValue::deserialize(deserializer)? {
+            SerializedScalarValue::Isize((s,)) => ScalarValue::Isize(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::I8((s,)) => ScalarValue::I8(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::I16((s,)) => ScalarValue::I16(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::I32((s,)) => ScalarValue::I32(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::I64((s,)) => ScalarValue::I64(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::I128((s,)) => ScalarValue::I128(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::Usize((s,)) => ScalarValue::Usize(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::U8((s,)) => ScalarValue::U8(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::U16((s,)) => ScalarValue::U16(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::U32((s,)) => ScalarValue::U32(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::U64((s,)) => ScalarValue::U64(parse_scalar::<D, _>(&s)?),
+            SerializedScalarValue::U128((s,)) => ScalarValue::U128(parse_scalar::<D, _>(&s)?),
+        };
+        Ok(v)
+    }
+}
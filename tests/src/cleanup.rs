@@ -0,0 +1,49 @@
+//! Exercise the `cleanup` micro-pass: the drops of the values whose type has
+//! no drop glue are removed, while the drops of the boxes, the vectors and the
+//! opaque types are kept (see the `test-cleanup` target of the Makefile).
+#![allow(dead_code)]
+
+/// A structure whose fields don't have drop glue
+struct Pair {
+    x: u32,
+    y: u32,
+}
+
+/// An enumeration whose variants don't have drop glue
+enum Shape {
+    Point,
+    Rect(u32, u32),
+}
+
+fn no_drop_glue_copy(x: u32, b: bool, r: &mut u32) -> u32 {
+    let y = x;
+    let z = if b { y } else { x + 1 };
+    *r = z;
+    let s = &y;
+    *s + z
+}
+
+fn no_drop_glue_adt(p: Pair, s: Shape) -> u32 {
+    let q = p;
+    match s {
+        Shape::Point => q.x,
+        Shape::Rect(w, h) => q.y + w * h,
+    }
+}
+
+fn drop_glue_box(x: u32) -> u32 {
+    let b = Box::new(x);
+    *b + 1
+}
+
+fn drop_glue_vec(x: u32) -> usize {
+    let mut v: Vec<u32> = Vec::new();
+    v.push(x);
+    v.len()
+}
+
+/// `String` is opaque: we consider it has drop glue
+fn drop_glue_opaque(s: String) -> bool {
+    let t = s;
+    t.is_empty()
+}
@@ -0,0 +1,51 @@
+//! Exercise the `inline` micro-pass (see the `test-inline` target of the
+//! Makefile). This file uses tool attributes, which can only be registered at
+//! the crate root: it is extracted on its own, and is not a module of the
+//! tests crate.
+#![feature(register_tool)]
+#![register_tool(charon)]
+#![allow(dead_code)]
+
+/// Small enough to be inlined without attribute (the test uses
+/// `--inline-threshold`). Its locals have the same names as the locals of
+/// the caller: they must become fresh locals of the caller.
+fn add_one(x: u32) -> u32 {
+    let y = x + 1;
+    y
+}
+
+fn call_add_one(x: u32) -> u32 {
+    let y = add_one(x);
+    let z = add_one(y);
+    x + z
+}
+
+/// Returns early: once inlined, its body is wrapped in a loop and the
+/// `return`s become `break`s
+#[charon::inline]
+fn clamp(x: u32, min: u32, max: u32) -> u32 {
+    if x < min {
+        return min;
+    }
+    if x > max {
+        return max;
+    }
+    let mut y = x;
+    y = y * 2;
+    y = y / 2;
+    y
+}
+
+fn call_clamp(x: u32) -> u32 {
+    clamp(x, 1, 10) + clamp(x, 2, 20)
+}
+
+/// Small, but never inlined
+#[charon::inline(never)]
+fn id(x: u32) -> u32 {
+    x
+}
+
+fn call_id(x: u32) -> u32 {
+    id(x)
+}
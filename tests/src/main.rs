@@ -1,9 +1,12 @@
+mod cleanup;
 mod external;
 mod hashmap;
 mod loops;
 mod matches;
+mod monomorphize;
 mod nested_borrows;
 mod no_nested_borrows;
 mod paper;
+mod question_mark;
 
 fn main() {}
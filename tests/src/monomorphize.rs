@@ -0,0 +1,35 @@
+//! Exercise `--monomorphize`: the generic declarations reachable from the root
+//! are replaced with their instances, and the other declarations are dropped
+//! (see the `test-monomorphize` target of the Makefile).
+#![allow(dead_code)]
+
+struct Wrapper<T> {
+    value: T,
+}
+
+fn wrap<T>(x: T) -> Wrapper<T> {
+    Wrapper { value: x }
+}
+
+fn get<T>(w: Wrapper<T>) -> T {
+    w.value
+}
+
+/// Only called by the instances: its instances are collected transitively
+fn rewrap<T>(w: Wrapper<T>) -> Wrapper<T> {
+    wrap(get(w))
+}
+
+fn use_wrappers(x: u32, b: bool) -> u32 {
+    let w = rewrap(wrap(x));
+    if get(wrap(b)) {
+        get(w)
+    } else {
+        0
+    }
+}
+
+/// Not reachable from the root
+fn unused(x: u32) -> Wrapper<u32> {
+    wrap(x)
+}
@@ -0,0 +1,23 @@
+//! Exercise the `reconstruct_try` micro-pass: the desugarings of `?` are
+//! replaced with `Try` statements, which record the drops performed before
+//! returning early (see the `test-question_mark` target of the Makefile).
+#![allow(dead_code)]
+
+fn result_add(x: Result<u32, ()>, y: Result<u32, ()>) -> Result<u32, ()> {
+    let x = x?;
+    let y = y?;
+    Ok(x + y)
+}
+
+fn option_first(p: Option<(u32, u32)>) -> Option<u32> {
+    let (x, _) = p?;
+    Some(x)
+}
+
+/// The vector is live when `?` returns early: it must be dropped
+fn result_drop(v: Vec<u32>, x: Result<u32, ()>) -> Result<Vec<u32>, ()> {
+    let mut v = v;
+    let x = x?;
+    v.push(x);
+    Ok(v)
+}